@@ -1,9 +1,28 @@
 use super::parse::CallDef;
-use quote::quote;
+use quote::{format_ident, quote};
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
-pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
-	let CallDef { pallet_struct, methods } = def;
+pub fn expand_call(framework: syn::Path, def: CallDef) -> proc_macro2::TokenStream {
+	let CallDef { pallet_type, generics, methods } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// The predicates of the `where` clause of the `impl`, which we extend with our own bounds.
+	let where_predicate =
+		generics.where_clause.iter().flat_map(|clause| &clause.predicates).collect::<Vec<_>>();
+
+	// These are the generics of the `Call` enum, the same as those of the `impl`, except that the
+	// instance `I` of an instantiable pallet defaults to `()`, so `Call<T>` is the call of its
+	// default instance.
+	let mut call_generics = generics.clone();
+	for param in call_generics.type_params_mut() {
+		if param.ident == "I" && param.default.is_none() {
+			param.eq_token = Some(Default::default());
+			param.default = Some(syn::parse_quote!(()));
+		}
+	}
+
+	// This is a vector of all the type parameters, like `T` and `I`.
+	let type_param = generics.type_params().map(|param| &param.ident).collect::<Vec<_>>();
 
 	// This is a vector of all the callable function names.
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
@@ -23,27 +42,161 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// This is a nested vector of the types in `args_type` rendered as strings, for the metadata.
+	let args_type_name = methods
+		.iter()
+		.map(|method| method.args.iter().map(|(_, type_)| type_name(type_)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// This is a nested vector of the doc comment lines of each of the functions in `fn_name`.
+	let docs = methods.iter().map(|method| &method.docs).collect::<Vec<_>>();
+
+	// This is a vector of the index of each of the functions in `fn_name`.
+	let call_index = methods.iter().map(|method| method.call_index).collect::<Vec<_>>();
+
+	// This is a vector of the weight expression of each of the functions in `fn_name`. Functions
+	// without a `#[weight(..)]` attribute weigh nothing.
+	let weight = methods
+		.iter()
+		.map(|method| match &method.weight {
+			Some(weight) => quote!(#weight),
+			None => quote!(0),
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of all the distinct argument types, which bound the trait implementations of
+	// `Call<T>`. We bound the types of the arguments, like `T::AccountId: Clone`, and not `T`
+	// itself, since the config of a runtime does not implement any of these traits.
+	let mut bound_type = Vec::<&syn::Type>::new();
+	for type_ in methods.iter().flat_map(|method| method.args.iter().map(|(_, type_)| &**type_)) {
+		if !bound_type.contains(&type_) {
+			bound_type.push(type_);
+		}
+	}
+
+	// The path to `serde` and the bounds of the `Serialize` and `Deserialize` implementations of
+	// `Call<T>`, as the strings expected by the `#[serde(..)]` attribute.
+	let serde_crate = format!("{}::support::serde", quote!(#framework));
+	let serialize_bound = bound_type
+		.iter()
+		.map(|type_| format!("{}: {}::Serialize", quote!(#type_), serde_crate))
+		.collect::<Vec<_>>()
+		.join(", ");
+	let deserialize_bound = bound_type
+		.iter()
+		.map(|type_| format!("{}: {}::Deserialize<'de>", quote!(#type_), serde_crate))
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	// This is a nested vector of the names of the arguments of the other call when comparing two
+	// calls, like `other_to` for `to`.
+	let other_args_name = args_name
+		.iter()
+		.map(|names| names.iter().map(|name| format_ident!("other_{}", name)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// This is a vector of the format string displaying each of the functions in `fn_name`, like
+	// `transfer(to={}, amount={})`.
+	let display_format = methods
+		.iter()
+		.map(|method| {
+			let args = method
+				.args
+				.iter()
+				.map(|(name, _)| format!("{}={{}}", name))
+				.collect::<Vec<_>>()
+				.join(", ");
+			format!("{}({})", method.name, args)
+		})
+		.collect::<Vec<_>>();
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
 	// and the `Dispatch` trait logic to route a `caller` to access those functions.
 	let dispatch_impl = quote! {
 		// The callable functions exposed by this pallet.
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
+		//
+		// The hidden `__Ignore` variant uses `T`, even when no call has an argument using it. It
+		// can never be created.
 		#[allow(non_camel_case_types)]
-		pub enum Call<T: Config> {
+		#[derive(#framework::support::serde::Serialize, #framework::support::serde::Deserialize)]
+		#[serde(
+			crate = #serde_crate,
+			bound(serialize = #serialize_bound, deserialize = #deserialize_bound)
+		)]
+		pub enum Call #call_generics #where_clause {
 			#(
 				#fn_name { #( #args_name: #args_type),* },
 			)*
+			#[doc(hidden)]
+			#[serde(skip)]
+			__Ignore(::core::marker::PhantomData<( #( #type_param, )* )>, ::core::convert::Infallible),
+		}
+
+		impl #impl_generics Call #ty_generics #where_clause {
+			// The index of this call, from its `#[call_index(..)]`, which stays the same when
+			// functions are reordered.
+			pub fn call_index(&self) -> u8 {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { .. } => #call_index,
+					)*
+				}
+			}
+
+			// The name of the function of this call.
+			pub fn name(&self) -> &'static str {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { .. } => stringify!(#fn_name),
+					)*
+				}
+			}
+
+			// The description of all the calls exposed by this pallet, in declaration order.
+			pub fn metadata() -> Vec<#framework::support::CallMetadata> {
+				vec![
+					#(
+						#framework::support::CallMetadata {
+							name: stringify!(#fn_name),
+							index: #call_index,
+							args: vec![
+								#(
+									#framework::support::ArgMetadata {
+										name: stringify!(#args_name),
+										ty: #args_type_name,
+									},
+								)*
+							],
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
+
+			// The weight of this call, as declared by its `#[weight(..)]`.
+			pub fn weight(&self) -> #framework::support::Weight {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { .. } => #weight,
+					)*
+				}
+			}
 		}
 
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
 		// appropriate function call with all arguments, including the `caller`.
-		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
+		impl #impl_generics #framework::support::Dispatch for #pallet_type #where_clause {
 			type Caller = T::AccountId;
-			type Call = Call<T>;
+			type Call = Call #ty_generics;
 
-			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
+			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> #framework::support::DispatchResult {
 				match call {
+					Call::__Ignore(_, never) => match never {},
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
 							self.#fn_name(
@@ -59,6 +212,113 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		}
 	};
 
+	// This quote block implements `Clone`, `PartialEq`, `Eq`, `Debug` and `Display` for `Call<T>`,
+	// whenever the types of all its arguments implement the trait.
+	let traits_impl = quote! {
+		impl #impl_generics Clone for Call #ty_generics
+		where
+			#( #where_predicate, )*
+			#( #bound_type: Clone ),*
+		{
+			fn clone(&self) -> Self {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { #( #args_name ),* } => Call::#fn_name {
+							#( #args_name: #args_name.clone() ),*
+						},
+					)*
+				}
+			}
+		}
+
+		impl #impl_generics PartialEq for Call #ty_generics
+		where
+			#( #where_predicate, )*
+			#( #bound_type: PartialEq ),*
+		{
+			fn eq(&self, other: &Self) -> bool {
+				#[allow(unreachable_patterns)]
+				match (self, other) {
+					#(
+						(
+							Call::#fn_name { #( #args_name ),* },
+							Call::#fn_name { #( #args_name: #other_args_name ),* },
+						) => true #( && #args_name == #other_args_name )*,
+					)*
+					_ => false,
+				}
+			}
+		}
+
+		impl #impl_generics Eq for Call #ty_generics
+		where
+			#( #where_predicate, )*
+			#( #bound_type: Eq ),*
+		{}
+
+		impl #impl_generics ::core::fmt::Debug for Call #ty_generics
+		where
+			#( #where_predicate, )*
+			#( #bound_type: ::core::fmt::Debug ),*
+		{
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { #( #args_name ),* } => f
+							.debug_struct(stringify!(#fn_name))
+							#( .field(stringify!(#args_name), #args_name) )*
+							.finish(),
+					)*
+				}
+			}
+		}
+
+		// Display a call compactly, like `transfer(to=bob, amount=30)`.
+		impl #impl_generics ::core::fmt::Display for Call #ty_generics
+		where
+			#( #where_predicate, )*
+			#( #bound_type: ::core::fmt::Display ),*
+		{
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match self {
+					Call::__Ignore(_, never) => match *never {},
+					#(
+						Call::#fn_name { #( #args_name ),* } =>
+							write!(f, #display_format, #( #args_name ),*),
+					)*
+				}
+			}
+		}
+	};
+
 	// Return the generated code.
-	dispatch_impl.into()
+	quote! {
+		#dispatch_impl
+		#traits_impl
+	}
+}
+
+/// Render a type as a string for the metadata, such as `Vec<T::AccountId>`.
+///
+/// The tokens are printed with a space between each of them, which we remove unless it separates
+/// two words, like in `&'static str` or `dyn Trait`.
+fn type_name(type_: &syn::Type) -> String {
+	let tokens = quote!(#type_).to_string();
+	let chars = tokens.chars().collect::<Vec<_>>();
+	let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+	let mut name = String::with_capacity(tokens.len());
+	for (i, c) in chars.iter().enumerate() {
+		if *c == ' ' {
+			let prev = chars.get(i.wrapping_sub(1)).copied().unwrap_or(' ');
+			let next = chars.get(i + 1).copied().unwrap_or(' ');
+			if !(is_word(prev) && is_word(next)) {
+				continue
+			}
+		}
+		name.push(*c);
+	}
+	name
 }
@@ -3,23 +3,28 @@ pub mod parse;
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
 pub fn call(
-	_attr: proc_macro::TokenStream,
+	attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-	// The final expanded code will be placed here.
-	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
 
-	// First we parse the call functions implemented for the pallet...
-	let generated: proc_macro::TokenStream = match parse::CallDef::try_from(item_mod.clone()) {
+	// First we parse the path to the framework crate from the macro arguments, and the call
+	// functions implemented for the pallet. This also strips the attributes only meaningful to
+	// this macro, like `#[call_index(..)]`...
+	let framework = crate::framework::parse_framework(attr);
+	let generated = match (framework, parse::CallDef::try_from(&mut item_mod)) {
 		// ..then we generate our new code.
-		Ok(def) => expand::expand_call(def).into(),
-		Err(e) => e.to_compile_error().into(),
+		(Ok(framework), Ok(def)) => expand::expand_call(framework, def),
+		(Err(e), _) | (_, Err(e)) => {
+			parse::strip_function_attrs(&mut item_mod);
+			e.to_compile_error()
+		},
 	};
 
-	// Add our generated code to the end, and return the final result.
-	finished.extend(generated);
-	return finished;
+	// The final product contains all of our old code too, followed by our generated code.
+	quote::quote! {
+		#item_mod
+		#generated
+	}
+	.into()
 }
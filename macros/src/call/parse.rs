@@ -5,15 +5,20 @@ use syn::spanned::Spanned;
 mod keyword {
 	syn::custom_keyword!(T);
 	syn::custom_keyword!(AccountId);
+	syn::custom_keyword!(call_index);
+	syn::custom_keyword!(weight);
 }
 
 /// This object will collect all the information we need to keep while parsing the callable
 /// functions.
 #[derive(Debug)]
 pub struct CallDef {
-	/// This is the name of the pallet struct where the callable functions are implemented. We
-	/// mostly assume it is `Pallet`.
-	pub pallet_struct: syn::Ident,
+	/// This is the type of the pallet struct where the callable functions are implemented. We
+	/// mostly assume it is `Pallet<T>`, or `Pallet<T, I>` for an instantiable pallet.
+	pub pallet_type: syn::Type,
+	/// The generics of the `impl` block, usually `<T: Config>`, or `<T: Config<I>, I: 'static>`
+	/// for an instantiable pallet.
+	pub generics: syn::Generics,
 	/// This is a list of the callable functions exposed by this pallet. See `CallVariantDef`.
 	pub methods: Vec<CallVariantDef>,
 }
@@ -25,10 +30,51 @@ pub struct CallVariantDef {
 	pub name: syn::Ident,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The index of the call, from `#[call_index(n)]`.
+	pub call_index: u8,
+	/// The weight of the call, from `#[weight(expr)]`. Defaults to zero.
+	pub weight: Option<syn::Expr>,
+	/// The doc comments of the function, one entry per line.
+	pub docs: Vec<String>,
+}
+
+/// The attributes we support on each callable function.
+pub enum FunctionAttr {
+	/// `#[call_index(n)]`, where `n` is a `u8` literal.
+	CallIndex(syn::LitInt),
+	/// `#[weight(expr)]`, where `expr` evaluates to a `support::Weight`.
+	Weight(syn::Expr),
+}
+
+impl syn::parse::Parse for FunctionAttr {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		input.parse::<syn::Token![#]>()?;
+		let content;
+		syn::bracketed!(content in input);
+
+		let lookahead = content.lookahead1();
+		if lookahead.peek(keyword::call_index) {
+			content.parse::<keyword::call_index>()?;
+			let index;
+			syn::parenthesized!(index in content);
+			Ok(Self::CallIndex(index.parse()?))
+		} else if lookahead.peek(keyword::weight) {
+			content.parse::<keyword::weight>()?;
+			let weight;
+			syn::parenthesized!(weight in content);
+			Ok(Self::Weight(weight.parse()?))
+		} else {
+			Err(lookahead.error())
+		}
+	}
 }
 
 impl CallDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	/// Parse the callable functions of `item`.
+	///
+	/// The attributes consumed by this macro (`#[call_index(..)]` and `#[weight(..)]`) are removed
+	/// from `item`, so that it can be emitted again without them.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
 		// First we check that we are parsing an `impl`.
 		let item_impl = if let syn::Item::Impl(item) = item {
 			item
@@ -36,16 +82,14 @@ impl CallDef {
 			return Err(syn::Error::new(item.span(), "Invalid pallet::call, expected item impl"))
 		};
 
-		// Extract the name of the struct. We mostly assume it is `Pallet`, but we can handle it
-		// when it isn't.
-		let pallet_struct = match &*item_impl.self_ty {
-			syn::Type::Path(tp) => tp.path.segments.first().unwrap().ident.clone(),
-			_ => panic!("not supported tokens"),
-		};
+		// Extract the type of the struct and the generics of the `impl`. We mostly assume it is
+		// `Pallet<T>`, but we can handle it when it isn't.
+		let pallet_type = (*item_impl.self_ty).clone();
+		let generics = item_impl.generics.clone();
 
 		// Here is where we will store all the callable functions.
-		let mut methods = vec![];
-		for item in item_impl.items {
+		let mut methods: Vec<CallVariantDef> = vec![];
+		for item in item_impl.items.iter_mut() {
 			if let syn::ImplItem::Fn(method) = item {
 				// Here is where we will store all the args for each callable functions.
 				let mut args = vec![];
@@ -60,7 +104,7 @@ impl CallDef {
 				}
 
 				// The second argument should be the `caller: T::AccountId` argument.
-				match method.sig.inputs.iter().skip(1).next() {
+				match method.sig.inputs.iter().nth(1) {
 					Some(syn::FnArg::Typed(arg)) => {
 						// Here we specifically check that this argument is as we expect for
 						// `caller: T::AccountId`.
@@ -95,13 +139,93 @@ impl CallDef {
 					args.push((arg_ident, arg.ty.clone()));
 				}
 
+				// Extract our attributes, and leave the others on the function.
+				let mut call_index = None;
+				let mut weight = None;
+				for attr in take_function_attrs(&mut method.attrs)? {
+					match attr {
+						FunctionAttr::CallIndex(lit) => {
+							if call_index.is_some() {
+								let msg = "Invalid call, duplicate `call_index` attribute";
+								return Err(syn::Error::new(lit.span(), msg))
+							}
+							call_index = Some((lit.base10_parse::<u8>()?, lit.span()));
+						},
+						FunctionAttr::Weight(expr) => {
+							if weight.is_some() {
+								let msg = "Invalid call, duplicate `weight` attribute";
+								return Err(syn::Error::new(expr.span(), msg))
+							}
+							weight = Some(expr);
+						},
+					}
+				}
+
+				// Every call needs an explicit index, so reordering the functions never changes the
+				// encoding of their calls.
+				let (call_index, index_span) = call_index.ok_or_else(|| {
+					let msg = "Invalid call, missing `#[call_index(..)]` attribute";
+					syn::Error::new(fn_name.span(), msg)
+				})?;
+
+				// Two calls can never share the same index.
+				if let Some(other) = methods.iter().find(|m| m.call_index == call_index) {
+					let msg = format!(
+						"Invalid call_index, index {} is already used by `{}`",
+						call_index, other.name
+					);
+					return Err(syn::Error::new(index_span, msg))
+				}
+
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				let docs = get_doc_literals(&method.attrs);
+				methods.push(CallVariantDef { name: fn_name, args, call_index, weight, docs });
 			}
 		}
 
 		// Return all callable functions for this pallet.
-		Ok(Self { pallet_struct, methods })
+		Ok(Self { pallet_type, generics, methods })
+	}
+}
+
+/// Extract the doc comments from `attrs`, which the compiler turns into `#[doc = "..."]`.
+fn get_doc_literals(attrs: &[syn::Attribute]) -> Vec<String> {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("doc"))
+		.filter_map(|attr| match &attr.meta {
+			syn::Meta::NameValue(syn::MetaNameValue {
+				value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+				..
+			}) => Some(doc.value().trim().to_string()),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Check if `attr` is one of the attributes this macro supports on callable functions.
+fn is_function_attr(attr: &syn::Attribute) -> bool {
+	attr.path().is_ident("call_index") || attr.path().is_ident("weight")
+}
+
+/// Remove and parse the attributes this macro supports from `attrs`.
+fn take_function_attrs(attrs: &mut Vec<syn::Attribute>) -> syn::Result<Vec<FunctionAttr>> {
+	let (taken, kept) = attrs.drain(..).partition::<Vec<_>, _>(is_function_attr);
+	*attrs = kept;
+	taken.iter().map(|attr| syn::parse2::<FunctionAttr>(attr.to_token_stream())).collect()
+}
+
+/// Remove the attributes this macro supports from all functions of `item`, without parsing them.
+///
+/// This is used when parsing failed, so the compiler only reports the parsing error and not also
+/// the attributes it does not know about.
+pub fn strip_function_attrs(item: &mut syn::Item) {
+	if let syn::Item::Impl(item_impl) = item {
+		for item in item_impl.items.iter_mut() {
+			if let syn::ImplItem::Fn(method) = item {
+				method.attrs.retain(|attr| !is_function_attr(attr));
+			}
+		}
 	}
 }
 
@@ -0,0 +1,24 @@
+// Custom keywords we match to when parsing the arguments of a macro.
+mod keyword {
+	syn::custom_keyword!(framework);
+}
+
+/// Parse the path to the framework crate from the `framework = path` argument of a macro.
+///
+/// The framework crate is the one providing the `support`, `storage` and `system` modules the
+/// generated code refers to. Without the argument we assume the macro is used inside the framework
+/// crate itself, and the path is `crate`.
+pub fn parse_framework(attr: proc_macro::TokenStream) -> syn::Result<syn::Path> {
+	if attr.is_empty() {
+		return Ok(syn::parse_quote!(crate));
+	}
+
+	syn::parse::Parser::parse(
+		|input: syn::parse::ParseStream| {
+			input.parse::<keyword::framework>()?;
+			input.parse::<syn::Token![=]>()?;
+			syn::Path::parse_mod_style(input)
+		},
+		attr,
+	)
+}
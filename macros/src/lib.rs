@@ -1,6 +1,34 @@
 mod call;
+mod framework;
 mod runtime;
+mod storage;
 
+/// Expand the callable functions of a pallet.
+///
+/// This is placed on an `impl` block of the pallet struct, where every function takes `&mut self`
+/// and `caller: T::AccountId` as its first two parameters. It generates:
+/// - `enum Call<T>` - with one variant per function, holding the remaining arguments.
+/// - `fn call_index()`, `fn name()` and `fn weight()` on `Call<T>`.
+/// - implementations of `Clone`, `PartialEq`, `Eq`, `Debug`, `Display`, and serde's `Serialize`
+///   and `Deserialize` for `Call<T>`, bounded on the types of the arguments rather than on `T`.
+///   `Display` is compact, like `transfer(to=bob, amount=30)`, with each argument in its `Display`
+///   format, so it is only implemented if every argument type implements `Display`.
+/// - `fn metadata()` on `Call<T>` - which describes each call: its name, index, arguments and doc
+///   comments.
+/// - an implementation of the trait `support::Dispatch` for the pallet, which routes each variant
+///   of `Call<T>` to its function.
+///
+/// Each function is annotated with:
+/// - `#[call_index(n)]` - the `u8` index of the call, which must be unique within the pallet. It is
+///   required, so the index stays the same when functions are reordered.
+/// - `#[weight(expr)]` - an expression evaluating to the `support::Weight` of the call. It
+///   defaults to zero.
+///
+/// An instantiable pallet implements its calls with an instance type parameter `I`, like
+/// `impl<T: Config<I>, I: 'static> Pallet<T, I>`. The generated `Call<T, I = ()>` has the same
+/// generics, where the instance defaults to `()`.
+///
+/// Outside of the framework crate, pass its path with `#[macros::call(framework = path)]`.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -11,20 +39,91 @@ pub fn call(
 
 /// Expand the `Runtime` definition.
 ///
+/// Each pallet field, except `system`, can declare the parts the pallet provides with
+/// `#[pallet(calls, events, genesis, hooks, versioned)]`, listing any subset of:
+/// - `calls` - the pallet has a `Call<T>` generated by `#[macros::call]`.
+/// - `events` - the pallet implements `support::Events`.
+/// - `genesis` - the pallet implements `support::BuildGenesis`.
+/// - `hooks` - the pallet implements `support::Hooks`.
+/// - `versioned` - the pallet implements `support::Versioned`.
+///
+/// A pallet without the attribute provides `calls` and `hooks`. The system pallet always provides
+/// `hooks` only.
+///
 /// This generates function implementations on `Runtime`:
 /// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
+///   included in the runtime. The storage version of each pallet providing `versioned` is set to
+///   its current one in the system pallet.
+/// - `fn from_genesis()` - which generates a new instance of the runtime, and builds the genesis of
+///   all the pallets providing `genesis` from a `RuntimeGenesisConfig`.
+/// - `fn take_events()` - which takes the events emitted by all the pallets providing `events`, as
+///   `RuntimeEvent`s.
+/// - `fn metadata()` - which describes all the pallets providing `calls` and their calls, using the
+///   `Call::metadata()` generated by `#[macros::call]` for each pallet.
 /// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
 ///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   valid block number. `on_initialize` is called on system and all the pallets providing `hooks`
+///   in declaration order before the extrinsics, and `on_finalize` after them.
+///   It returns a `support::BlockReceipt`, with the result, weight and events of each extrinsic,
+///   and the state root after the block.
+///   Before dispatching an extrinsic, it checks its signature against the key of the caller given
+///   by the `support::AccountKeys<AccountId>` the runtime must implement, checks its nonce against
+///   the caller's nonce in system, and withdraws its fees with the
+///   `support::ChargeTransaction<AccountId, RuntimeCall>` the runtime must implement. If any of
+///   these fails, the extrinsic is skipped.
+/// - `fn state()` - which encodes the storage of system and all the pallets into a
+///   `storage::State`, each under the name of its pallet, with the `storage::EncodeState`
+///   implemented by `#[macros::storage]`.
+/// - `fn state_root()` and `fn read_proof()` - which commit to the `state()` with a
+///   `sparse_merkle::SparseMerkleTree`, and prove the value of a single key against its root.
+/// - `fn dry_run()` and `fn simulate_block()` - which apply a single extrinsic or execute a block
+///   on a clone of the runtime, and return their outcome and the `storage::StateDiff` they would
+///   cause. The runtime struct must implement `Clone` for these.
+/// - `fn from_state()` - which decodes a `storage::State`, like the one returned by `state()`,
+///   into a new instance of the runtime, with the `storage::DecodeState` implemented by
+///   `#[macros::storage]`. A state holding values the runtime does not store is an error.
+/// - `fn upgrade()` - which runs the `support::Migrations` of every pallet providing `versioned` on
+///   a state stored by an older version of the runtime, from the storage version recorded for it in
+///   the system pallet, and then decodes it with `from_state()`. A pallet missing from the system
+///   pallet is at version zero.
+///   The keys and values stored by every pallet must be deserializable for these.
+/// - `fn take_accesses()`, `fn encode_keys()` and `fn decode_keys()` - which take the keys of the
+///   values accessed while recording accesses with `storage::record_accesses`, and encode or
+///   decode only the values under some keys, with the `storage::TrackAccesses` implemented by
+///   `#[macros::storage]`.
+/// - `fn initialize_block()`, `fn apply_extrinsic()` and `fn finalize_block()` - the three steps of
+///   `fn execute_block()`, so a block can also be built one extrinsic at a time. `apply_extrinsic`
+///   returns an error, without changing any state, for an extrinsic which would be skipped.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
 /// - `enum RuntimeCall` - an "outer"-enum representing the accumulation of all possible calls to
-///   all pallets. The system pallet is not included.
+///   all pallets providing `calls`. The system pallet is not included.
+///   It implements `Clone`, `PartialEq`, `Eq`, `Debug`, `Serialize`, `Deserialize`, and a
+///   `Display` prefixed by the pallet, like `balances.transfer(to=bob, amount=30)`. The arguments
+///   of every call must be deserializable, and implement `Display`.
+///   It has `fn pallet_name()` and `fn call_name()`, to identify a call by name.
+///   The call of each pallet converts into it with `From`, and back with `TryFrom`, and it
+///   implements `support::IsSubType` for each of them.
 /// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
 ///   logic like incrementing the nonce of the user is included in the generated code. The system
 ///   pallet is not included.
+///   Before routing a call, it checks the call with the `support::CallFilter<RuntimeCall>` the
+///   runtime must implement, and returns `support::CALL_FILTERED` if the call is rejected.
+///
+/// Finally, it generates the `enum RuntimeEvent` and the `struct RuntimeGenesisConfig`, which
+/// accumulate the events and genesis configs of the pallets providing `events` and `genesis`.
+/// `RuntimeGenesisConfig` implements `Deserialize`, so a chain can start from a genesis file, and
+/// the genesis config of every pallet must be deserializable.
+///
+/// The generated code only refers to the framework crate, which provides the `support`, `storage`,
+/// `merkle`, `sparse_merkle` and `system` modules, through fully qualified paths. Outside of the
+/// framework crate, pass its path with `#[macros::runtime(framework = path)]`. The `Call` of each
+/// pallet is found next to its type, so `balances::Pallet<Self, RewardPoints>` uses
+/// `balances::Call<Self, RewardPoints>`. This lets a runtime include several instances of the same
+/// pallet, each with its own field, and so its own variants in the generated enums. The blocks
+/// executed by `fn execute_block()` are built from the `AccountId`, `Nonce` and `BlockNumber` of
+/// the runtime's `system::Config`.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
@@ -32,3 +131,34 @@ pub fn runtime(
 ) -> proc_macro::TokenStream {
 	runtime::runtime(attr, item)
 }
+
+/// Expand the storage items of a pallet.
+///
+/// This is placed on the pallet struct, before any `#[derive(..)]`. Every field whose type is a
+/// `StorageValue<V>`, `StorageMap<K, V>` or `StorageDoubleMap<K1, K2, V>` is a storage item, and
+/// can be configured with `#[storage(getter = name, default = expr)]`, where both arguments are
+/// optional:
+/// - `getter` - the name of the generated getter, which defaults to the name of the field.
+/// - `default` - the value of the item when nothing is stored. It defaults to
+///   `Default::default()` for a `StorageValue`. A map without a default returns an `Option`.
+///
+/// This generates function implementations on the pallet struct:
+/// - `fn new()` - which creates the pallet with all its storage items holding their default. The
+///   fields which are not storage items are created with `Default::default()`.
+/// - for each storage item, a getter, and `set_`, `mutate_` and `take_` functions named after the
+///   getter. The functions of a map take its keys by reference.
+///
+/// It also implements `storage::EncodeState` for the pallet, writing each storage item under
+/// `prefix.field`, when the keys and values of all its storage items are serializable, and
+/// `storage::DecodeState`, reading them back, when they are deserializable. When they are both, it
+/// implements `storage::TrackAccesses`, telling which of those values were accessed.
+///
+/// Like `#[macros::call]`, it takes an optional `framework = path` argument, the path to the crate
+/// providing the `storage` module, which defaults to `crate`.
+#[proc_macro_attribute]
+pub fn storage(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	storage::storage(attr, item)
+}
@@ -1,49 +1,461 @@
-use super::parse::RuntimeDef;
+use super::parse::{PalletDef, PalletParts, RuntimeDef};
 use quote::quote;
 
 /// See the `fn runtime` docs at the `lib.rs` of this crate for a high level definition.
-pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
-	let RuntimeDef { runtime_struct, pallets } = def;
+pub fn expand_runtime(framework: syn::Path, def: RuntimeDef) -> proc_macro2::TokenStream {
+	let RuntimeDef { runtime_struct, system_type, pallets } = def;
 
 	// This is a vector of all the pallet names, not including system.
-	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+	let pallet_names = pallets.iter().map(|pallet| pallet.name.clone()).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
-	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
+	let pallet_types = pallets.iter().map(|pallet| pallet.type_.clone()).collect::<Vec<_>>();
+
+	// These are the names and types of the pallets providing each part, not including system.
+	let with_part = |part: fn(&PalletParts) -> bool| {
+		pallets.iter().filter(|pallet| part(&pallet.parts)).collect::<Vec<&PalletDef>>()
+	};
+	let call_pallets = with_part(|parts| parts.calls);
+	let call_names = call_pallets.iter().map(|pallet| &pallet.name).collect::<Vec<_>>();
+	let call_paths = call_pallets
+		.iter()
+		.map(|pallet| {
+			let call_path = &pallet.call_path;
+			replace_self(quote!(#call_path), &runtime_struct)
+		})
+		.collect::<Vec<_>>();
+	let event_pallets = with_part(|parts| parts.events);
+	let event_names = event_pallets.iter().map(|pallet| &pallet.name).collect::<Vec<_>>();
+	let event_types = event_pallets
+		.iter()
+		.map(|pallet| {
+			let type_ = &pallet.type_;
+			replace_self(quote!(#type_), &runtime_struct)
+		})
+		.collect::<Vec<_>>();
+	let genesis_pallets = with_part(|parts| parts.genesis);
+	let genesis_names = genesis_pallets.iter().map(|pallet| &pallet.name).collect::<Vec<_>>();
+	let genesis_types = genesis_pallets
+		.iter()
+		.map(|pallet| {
+			let type_ = &pallet.type_;
+			replace_self(quote!(#type_), &runtime_struct)
+		})
+		.collect::<Vec<_>>();
+	let hook_names = with_part(|parts| parts.hooks)
+		.into_iter()
+		.map(|pallet| &pallet.name)
+		.collect::<Vec<_>>();
+	let versioned_pallets = with_part(|parts| parts.versioned);
+	let versioned_names = versioned_pallets.iter().map(|pallet| &pallet.name).collect::<Vec<_>>();
+	let versioned_types = versioned_pallets
+		.iter()
+		.map(|pallet| {
+			let type_ = &pallet.type_;
+			replace_self(quote!(#type_), &runtime_struct)
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of the index of each pallet with calls in the `RuntimeCall` enum.
+	let call_index = (0..call_names.len()).map(|index| index as u8).collect::<Vec<_>>();
+
+	// The types of the system pallet config of the runtime, which make up the extrinsic and block
+	// types.
+	let system_config = quote!(<#runtime_struct as #framework::system::Config>);
+	let extrinsic_type = quote! {
+		#framework::support::Extrinsic<
+			#system_config::AccountId,
+			#system_config::Nonce,
+			RuntimeCall,
+		>
+	};
+	let block_type = quote! {
+		#framework::support::Block<
+			#framework::support::Header<#system_config::BlockNumber>,
+			#extrinsic_type,
+		>
+	};
 
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
 			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
-			fn new() -> Self {
-				Self {
+			//
+			// A new runtime stores the current version of the storage of every versioned pallet.
+			pub fn new() -> Self {
+				#[allow(unused_mut)]
+				let mut runtime = Self {
 					// Since system is not included in the list of pallets, we manually add it here.
-					system: <system::Pallet::<Self>>::new(),
+					system: <#system_type>::new(),
 					#(
 						#pallet_names: <#pallet_types>::new()
 					),*
+				};
+				#(
+					runtime.system.set_storage_version(
+						&stringify!(#versioned_names).to_string(),
+						<#versioned_types as #framework::support::Versioned>::STORAGE_VERSION,
+					);
+				)*
+				runtime
+			}
+
+			// Create a new instance of the main Runtime, where the pallets providing a genesis are
+			// initialized with their part of `config`.
+			pub fn from_genesis(config: RuntimeGenesisConfig) -> Self {
+				let mut runtime = Self::new();
+				#(
+					#framework::support::BuildGenesis::build_genesis(
+						&mut runtime.#genesis_names,
+						&config.#genesis_names,
+					);
+				)*
+				runtime
+			}
+
+			// Take all the events emitted by the pallets since the last call, in declaration order
+			// of the pallets.
+			pub fn take_events(&mut self) -> Vec<RuntimeEvent> {
+				let mut events = Vec::new();
+				#(
+					events.extend(
+						#framework::support::Events::take_events(&mut self.#event_names)
+							.into_iter()
+							.map(RuntimeEvent::#event_names),
+					);
+				)*
+				events
+			}
+
+			// The state of all the pallets, including system, with the storage of each pallet under
+			// its name.
+			pub fn state(&self) -> #framework::storage::State {
+				let mut state = #framework::storage::State::new();
+				#framework::storage::EncodeState::encode_state(&self.system, "system", &mut state);
+				#(
+					#framework::storage::EncodeState::encode_state(
+						&self.#pallet_names,
+						stringify!(#pallet_names),
+						&mut state,
+					);
+				)*
+				state
+			}
+
+			// The root of the sparse Merkle tree over `state`, which commits to all the stored
+			// values.
+			pub fn state_root(&self) -> #framework::merkle::Hash {
+				#framework::sparse_merkle::SparseMerkleTree::from_state(&self.state()).root()
+			}
+
+			// The encoded value of `key` in `state`, if any, with the proof of it against
+			// `state_root`. Keys are built with `storage::map_key` and `storage::double_map_key`.
+			pub fn read_proof(
+				&self,
+				key: &str,
+			) -> (Option<String>, #framework::sparse_merkle::StorageProof) {
+				let state = self.state();
+				let tree = #framework::sparse_merkle::SparseMerkleTree::from_state(&state);
+				(state.get(key).cloned(), tree.proof(key))
+			}
+
+			// Create a new instance of the main Runtime from a `state` like the one returned by
+			// `state`, with the storage of each pallet under its name.
+			//
+			// Returns an error if a value does not decode to its stored type, or if `state` holds
+			// values which the runtime does not store.
+			pub fn from_state(
+				state: &#framework::storage::State,
+			) -> Result<Self, &'static str> {
+				let mut runtime = Self::new();
+				#framework::storage::DecodeState::decode_state(
+					&mut runtime.system,
+					"system",
+					state,
+				)?;
+				#(
+					#framework::storage::DecodeState::decode_state(
+						&mut runtime.#pallet_names,
+						stringify!(#pallet_names),
+						state,
+					)?;
+				)*
+
+				let decoded = runtime.state();
+				if state.keys().any(|key| !decoded.contains_key(key)) {
+					return Err(&"state holds values which are not stored by the runtime")
 				}
+				Ok(runtime)
 			}
 
-			// Execute a block of extrinsics. Increments the block number.
-			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
-				self.system.inc_block_number();
-				if block.header.block_number != self.system.block_number() {
+			// The keys of the values accessed in all the pallets, including system, since the last
+			// call, while recording accesses with `storage::record_accesses`.
+			pub fn take_accesses(&self) -> #framework::storage::Accesses {
+				let mut accesses = #framework::storage::Accesses::new();
+				#framework::storage::TrackAccesses::take_accesses(
+					&self.system,
+					"system",
+					&mut accesses,
+				);
+				#(
+					#framework::storage::TrackAccesses::take_accesses(
+						&self.#pallet_names,
+						stringify!(#pallet_names),
+						&mut accesses,
+					);
+				)*
+				accesses
+			}
+
+			// The values of `state` under `keys`, like the keys returned by `take_accesses`.
+			pub fn encode_keys(
+				&self,
+				keys: &#framework::storage::Accesses,
+			) -> #framework::storage::State {
+				let mut state = #framework::storage::State::new();
+				#framework::storage::TrackAccesses::encode_keys(
+					&self.system,
+					"system",
+					keys,
+					&mut state,
+				);
+				#(
+					#framework::storage::TrackAccesses::encode_keys(
+						&self.#pallet_names,
+						stringify!(#pallet_names),
+						keys,
+						&mut state,
+					);
+				)*
+				state
+			}
+
+			// Replace the values under `keys` with the ones in `state`, like the one returned by
+			// `encode_keys`, and remove the ones missing from it.
+			//
+			// Returns an error if a key or a value does not decode to its stored type, in which
+			// case only some of the values may have been replaced.
+			pub fn decode_keys(
+				&mut self,
+				keys: &#framework::storage::Accesses,
+				state: &#framework::storage::State,
+			) -> Result<(), &'static str> {
+				#framework::storage::TrackAccesses::decode_keys(
+					&mut self.system,
+					"system",
+					keys,
+					state,
+				)?;
+				#(
+					#framework::storage::TrackAccesses::decode_keys(
+						&mut self.#pallet_names,
+						stringify!(#pallet_names),
+						keys,
+						state,
+					)?;
+				)*
+				Ok(())
+			}
+
+			// Create a new instance of the main Runtime from a `state` stored by an older version
+			// of it, after migrating the storage of each versioned pallet to its current version.
+			//
+			// The migrations of a pallet are run in order from the storage version recorded in
+			// the system pallet. Returns an error if one of their checks fails, if there is no
+			// migration to the current version, or if the migrated state can not be decoded.
+			pub fn upgrade(
+				mut state: #framework::storage::State,
+			) -> Result<Self, &'static str> {
+				#(
+					let current_version =
+						<#versioned_types as #framework::support::Versioned>::STORAGE_VERSION;
+					let key = #framework::storage::map_key(
+						"system.storage_versions",
+						stringify!(#versioned_names),
+					);
+					let mut version = match state.get(&key) {
+						Some(version) => #framework::storage::decode(version)?,
+						None => #framework::support::StorageVersion::default(),
+					};
+					<
+						<#versioned_types as #framework::support::Versioned>::Migrations
+							as #framework::support::Migrations
+					>::migrate(stringify!(#versioned_names), &mut version, &mut state)?;
+					if version != current_version {
+						return Err(&"no migration to the current storage version of the pallet")
+					}
+					state.insert(key, #framework::storage::encode(&version));
+				)*
+				Self::from_state(&state)
+			}
+
+			// Describe all the pallets and calls of this runtime, in declaration order.
+			pub fn metadata() -> #framework::support::RuntimeMetadata {
+				#framework::support::RuntimeMetadata {
+					pallets: vec![
+						#(
+							#framework::support::PalletMetadata {
+								name: stringify!(#call_names),
+								index: #call_index,
+								calls: <#call_paths>::metadata(),
+							},
+						)*
+					],
+				}
+			}
+
+			// Start executing block `block_number`. Increments the block number, only if
+			// `block_number` is the next one.
+			//
+			// The `on_initialize` hook of every pallet, including system, is called in declaration
+			// order, and the weight they consume is registered in the system pallet.
+			//
+			// Returns an error if `block_number` does not follow the current block number,
+			// otherwise the events emitted by the hooks.
+			pub fn initialize_block(
+				&mut self,
+				block_number: #system_config::BlockNumber,
+			) -> Result<Vec<RuntimeEvent>, &'static str> {
+				if block_number != self.system.next_block_number() {
 					return Err(&"block number does not match what is expected")
 				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
-					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
+				self.system.inc_block_number();
+
+				let weight = #framework::support::Hooks::on_initialize(&mut self.system, block_number);
+				self.system.register_weight(weight);
+				#(
+					let weight = #framework::support::Hooks::on_initialize(&mut self.#hook_names, block_number);
+					self.system.register_weight(weight);
+				)*
+				Ok(self.take_events())
+			}
+
+			// Apply a single extrinsic of the block being executed.
+			//
+			// An extrinsic which is not signed with the key of its caller, with the wrong nonce, or
+			// whose fees can not be paid, is not executed at all: an error is returned, its nonce is
+			// not used and it consumes no weight.
+			// Otherwise, the weight of its call is registered in the system pallet, and the result
+			// of dispatching it is returned. The events it emitted can be taken with
+			// `take_events`.
+			pub fn apply_extrinsic(
+				&mut self,
+				extrinsic: #extrinsic_type,
+			) -> Result<#framework::support::DispatchResult, &'static str> {
+				let signed_by_caller =
+					#framework::support::AccountKeys::account_key(self, &extrinsic.caller)
+						.is_some_and(|key| extrinsic.is_signed_by(&key));
+				if !signed_by_caller {
+					return Err(#framework::support::BAD_SIGNATURE)
+				}
+				let #framework::support::Extrinsic { caller, nonce, tip, call, .. } = extrinsic;
+				if nonce != self.system.nonce(&caller) {
+					return Err(#framework::support::INVALID_NONCE)
+				}
+				#framework::support::ChargeTransaction::withdraw_fee(self, &caller, &call, tip)?;
+
+				self.system.inc_nonce(&caller);
+				self.system.register_weight(call.weight());
+				Ok(#framework::support::Dispatch::dispatch(self, caller, call))
+			}
+
+			// Finish executing block `block_number`.
+			//
+			// The `on_finalize` hook of every pallet, including system, is called in declaration
+			// order, and the weight they consume is registered in the system pallet.
+			//
+			// Returns the events emitted by the hooks.
+			pub fn finalize_block(
+				&mut self,
+				block_number: #system_config::BlockNumber,
+			) -> Vec<RuntimeEvent> {
+				let weight = #framework::support::Hooks::on_finalize(&mut self.system, block_number);
+				self.system.register_weight(weight);
+				#(
+					let weight = #framework::support::Hooks::on_finalize(&mut self.#hook_names, block_number);
+					self.system.register_weight(weight);
+				)*
+				self.take_events()
+			}
+
+			// Execute a block of extrinsics, with `initialize_block`, `apply_extrinsic` for each
+			// extrinsic, and `finalize_block`. The state root of the header is not checked: the
+			// receipt holds the state root after the block, for the caller to compare.
+			//
+			// Returns an error if the block is invalid, otherwise a receipt with the outcome, weight
+			// and events of each extrinsic. Extrinsics failing to dispatch do not make the block
+			// invalid, and neither do extrinsics with the wrong nonce or unpaid fees, which are
+			// skipped.
+			pub fn execute_block(
+				&mut self,
+				block: #block_type,
+			) -> Result<#framework::support::BlockReceipt<RuntimeEvent>, &'static str> {
+				let block_number = block.header.block_number;
+				let initialize_events = self.initialize_block(block_number)?;
+
+				let mut extrinsics = Vec::with_capacity(block.extrinsics.len());
+				for (index, extrinsic) in block.extrinsics.into_iter().enumerate() {
+					let weight = extrinsic.call.weight();
+					let (weight, result) = match self.apply_extrinsic(extrinsic) {
+						Ok(result) => (weight, result),
+						Err(e) => (0, Err(e)),
+					};
+					extrinsics.push(#framework::support::ExtrinsicReceipt {
+						index,
+						result,
+						weight,
+						events: self.take_events(),
 					});
 				}
-				Ok(())
+
+				let finalize_events = self.finalize_block(block_number);
+				Ok(#framework::support::BlockReceipt {
+					extrinsics,
+					initialize_events,
+					finalize_events,
+					weight: self.system.block_weight(),
+					state_root: self.state_root(),
+				})
+			}
+
+			// Apply a single extrinsic on a copy of the runtime, outside of any block, and return
+			// its outcome and the changes it would make to the state. The runtime is not changed.
+			pub fn dry_run(
+				&self,
+				extrinsic: #extrinsic_type,
+			) -> #framework::support::DryRun<RuntimeEvent> {
+				let mut overlay = self.clone();
+				let weight = extrinsic.call.weight();
+				let (weight, result) = match overlay.apply_extrinsic(extrinsic) {
+					Ok(result) => (weight, result),
+					Err(e) => (0, Err(e)),
+				};
+				#framework::support::DryRun {
+					result,
+					weight,
+					events: overlay.take_events(),
+					state_diff: #framework::storage::diff(&self.state(), &overlay.state()),
+				}
+			}
+
+			// Execute a block on a copy of the runtime, and return its receipt and the changes it
+			// would make to the state. The runtime is not changed.
+			pub fn simulate_block(
+				&self,
+				block: #block_type,
+			) -> Result<#framework::support::Simulation<RuntimeEvent>, &'static str> {
+				let mut overlay = self.clone();
+				let receipt = overlay.execute_block(block)?;
+				Ok(#framework::support::Simulation {
+					receipt,
+					state_diff: #framework::storage::diff(&self.state(), &overlay.state()),
+				})
 			}
 		}
 	};
 
+	// The path to `serde`, as the string expected by the `#[serde(..)]` attribute.
+	let serde_crate = format!("{}::support::serde", quote!(#framework));
+
 	// This quote block implements the `RuntimeCall` enum and implements the `Dispatch` trait.
 	let dispatch_impl = quote! {
 		// These are all the calls which are exposed to the world.
@@ -51,12 +463,96 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(
+			Clone,
+			PartialEq,
+			Eq,
+			Debug,
+			#framework::support::serde::Serialize,
+			#framework::support::serde::Deserialize,
+		)]
+		#[serde(crate = #serde_crate)]
 		pub enum RuntimeCall {
-			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
+			#( #call_names(#call_paths) ),*
 		}
 
-		impl crate::support::Dispatch for #runtime_struct {
-			type Caller = <Runtime as system::Config>::AccountId;
+		impl RuntimeCall {
+			// The name of the pallet this call belongs to.
+			pub fn pallet_name(&self) -> &'static str {
+				match *self {
+					#(
+						RuntimeCall::#call_names(_) => stringify!(#call_names),
+					)*
+				}
+			}
+
+			// The name of the function of this call in its pallet.
+			pub fn call_name(&self) -> &'static str {
+				match *self {
+					#(
+						RuntimeCall::#call_names(ref call) => call.name(),
+					)*
+				}
+			}
+
+			// The weight of the call, as declared by the pallet it belongs to.
+			pub fn weight(&self) -> #framework::support::Weight {
+				match *self {
+					#(
+						RuntimeCall::#call_names(ref call) => call.weight(),
+					)*
+				}
+			}
+		}
+
+		#(
+			impl From<#call_paths> for RuntimeCall {
+				fn from(call: #call_paths) -> Self {
+					RuntimeCall::#call_names(call)
+				}
+			}
+
+			// Get the call of a single pallet back, or the `RuntimeCall` if it belongs to another
+			// pallet.
+			impl TryFrom<RuntimeCall> for #call_paths {
+				type Error = RuntimeCall;
+
+				#[allow(unreachable_patterns)]
+				fn try_from(call: RuntimeCall) -> Result<Self, RuntimeCall> {
+					match call {
+						RuntimeCall::#call_names(call) => Ok(call),
+						call => Err(call),
+					}
+				}
+			}
+
+			impl #framework::support::IsSubType<#call_paths> for RuntimeCall {
+				#[allow(unreachable_patterns)]
+				fn is_sub_type(&self) -> Option<&#call_paths> {
+					match self {
+						RuntimeCall::#call_names(call) => Some(call),
+						_ => None,
+					}
+				}
+			}
+		)*
+
+		// Display a call compactly, prefixed by the pallet it belongs to, like
+		// `balances.transfer(to=bob, amount=30)`.
+		impl ::core::fmt::Display for RuntimeCall {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match *self {
+					#(
+						RuntimeCall::#call_names(ref call) => {
+							write!(f, "{}.{}", stringify!(#call_names), call)
+						},
+					)*
+				}
+			}
+		}
+
+		impl #framework::support::Dispatch for #runtime_struct {
+			type Caller = #system_config::AccountId;
 			type Call = RuntimeCall;
 			// Dispatch a call on behalf of a caller. Increments the caller's nonce.
 			//
@@ -67,25 +563,72 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 				&mut self,
 				caller: Self::Caller,
 				runtime_call: Self::Call,
-			) -> crate::support::DispatchResult {
+			) -> #framework::support::DispatchResult {
+				// Calls rejected by the filter of the runtime are never routed to their pallet.
+				if !#framework::support::CallFilter::contains(self, &runtime_call) {
+					return Err(#framework::support::CALL_FILTERED)
+				}
+
 				// This match statement will allow us to correctly route `RuntimeCall`s
-				// to the appropriate pallet level call.
+				// to the appropriate pallet level call. It has no arms in a runtime without calls,
+				// where `RuntimeCall` can not be constructed.
 				match runtime_call {
 					#(
-						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
-						}
-					),*
+						RuntimeCall::#call_names(call) => {
+							#framework::support::Dispatch::dispatch(&mut self.#call_names, caller, call)
+						},
+					)*
 				}
-				Ok(())
 			}
 		}
 	};
 
+	// This quote block implements the `RuntimeEvent` enum and the `RuntimeGenesisConfig` struct.
+	let parts_impl = quote! {
+		// These are all the events which can be emitted by the runtime.
+		// Note that it is just an accumulation of the events emitted by each pallet.
+		#[allow(non_camel_case_types)]
+		#[derive(Debug)]
+		pub enum RuntimeEvent {
+			#( #event_names(<#event_types as #framework::support::Events>::Event) ),*
+		}
+
+		// The initial state of the runtime, with one field for each pallet providing a genesis.
+		#[derive(#framework::support::serde::Deserialize)]
+		#[serde(crate = #serde_crate)]
+		pub struct RuntimeGenesisConfig {
+			#( pub #genesis_names: <#genesis_types as #framework::support::BuildGenesis>::GenesisConfig ),*
+		}
+	};
+
 	// We combine and return all the generated code.
 	quote! {
 		#dispatch_impl
+		#parts_impl
 		#runtime_impl
 	}
-	.into()
+}
+
+/// Replace every `Self` in `tokens` with the runtime struct, so that pallet types like
+/// `balances::Pallet<Self>` can be used outside of the `impl` blocks of the runtime.
+fn replace_self(
+	tokens: proc_macro2::TokenStream,
+	runtime_struct: &syn::Ident,
+) -> proc_macro2::TokenStream {
+	tokens
+		.into_iter()
+		.map(|token| match token {
+			proc_macro2::TokenTree::Ident(ident) if ident == "Self" =>
+				proc_macro2::TokenTree::Ident(runtime_struct.clone()),
+			proc_macro2::TokenTree::Group(group) => {
+				let mut new_group = proc_macro2::Group::new(
+					group.delimiter(),
+					replace_self(group.stream(), runtime_struct),
+				);
+				new_group.set_span(group.span());
+				proc_macro2::TokenTree::Group(new_group)
+			},
+			token => token,
+		})
+		.collect()
 }
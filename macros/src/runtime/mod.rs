@@ -3,23 +3,27 @@ pub mod parse;
 
 /// See the `fn runtime` docs at the `lib.rs` of this crate for a high level definition.
 pub fn runtime(
-	_attr: proc_macro::TokenStream,
+	attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-	// The final expanded code will be placed here.
-	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
 
-	// First we parse the `Runtime` struct...
-	let generated: proc_macro::TokenStream = match parse::RuntimeDef::try_from(item_mod.clone()) {
+	// First we parse the path to the framework crate from the macro arguments, and the `Runtime`
+	// struct. This also strips the `#[pallet(..)]` attributes from its fields...
+	let framework = crate::framework::parse_framework(attr);
+	let generated = match (framework, parse::RuntimeDef::try_from(&mut item_mod)) {
 		// ..then we generate our new code.
-		Ok(def) => expand::expand_runtime(def).into(),
-		Err(e) => e.to_compile_error().into(),
+		(Ok(framework), Ok(def)) => expand::expand_runtime(framework, def),
+		(Err(e), _) | (_, Err(e)) => {
+			parse::strip_pallet_attrs(&mut item_mod);
+			e.to_compile_error()
+		},
 	};
 
-	// Add our generated code to the end, and return the final result.
-	finished.extend(generated);
-	return finished;
+	// The final product contains all of our old code too, followed by our generated code.
+	quote::quote! {
+		#item_mod
+		#generated
+	}
+	.into()
 }
@@ -1,17 +1,119 @@
 use syn::spanned::Spanned;
 
+// Custom keywords we match to when parsing the `#[pallet(..)]` attribute of a field.
+mod keyword {
+	syn::custom_keyword!(calls);
+	syn::custom_keyword!(events);
+	syn::custom_keyword!(genesis);
+	syn::custom_keyword!(hooks);
+	syn::custom_keyword!(versioned);
+}
+
 /// This object will collect all the information we need to keep while parsing the `Runtime` struct.
 #[derive(Debug)]
 pub struct RuntimeDef {
 	/// This is the name of the struct used by the user. We mostly assume it is `Runtime`.
 	pub runtime_struct: syn::Ident,
+	/// The type of the `system` pallet, like `system::Pallet<Self>`.
+	pub system_type: syn::Type,
 	/// This is the list of pallets included in the `Runtime` struct. We omit `system` from this
 	/// list, but during parsing we check that system exists.
-	pub pallets: Vec<(syn::Ident, syn::Type)>,
+	pub pallets: Vec<PalletDef>,
+}
+
+/// This is the metadata we keep about each pallet of the runtime.
+#[derive(Debug)]
+pub struct PalletDef {
+	/// The name of the field, which is also the name of the pallet module.
+	pub name: syn::Ident,
+	/// The type of the pallet, like `balances::Pallet<Self>`.
+	pub type_: syn::Type,
+	/// The path to the `Call` of the pallet, found next to its type, like `balances::Call<Self>`.
+	pub call_path: syn::Path,
+	/// The parts the pallet provides, from `#[pallet(..)]`.
+	pub parts: PalletParts,
+}
+
+/// The parts a pallet can provide to the runtime.
+#[derive(Debug, Default)]
+pub struct PalletParts {
+	/// The pallet has a `Call<T>` generated by `#[macros::call]`.
+	pub calls: bool,
+	/// The pallet implements `support::Events`.
+	pub events: bool,
+	/// The pallet implements `support::BuildGenesis`.
+	pub genesis: bool,
+	/// The pallet implements `support::Hooks`.
+	pub hooks: bool,
+	/// The pallet implements `support::Versioned`.
+	pub versioned: bool,
+}
+
+/// A single part of the `#[pallet(..)]` attribute.
+pub enum PalletPart {
+	Calls(keyword::calls),
+	Events(keyword::events),
+	Genesis(keyword::genesis),
+	Hooks(keyword::hooks),
+	Versioned(keyword::versioned),
+}
+
+impl syn::parse::Parse for PalletPart {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		let lookahead = input.lookahead1();
+		if lookahead.peek(keyword::calls) {
+			Ok(Self::Calls(input.parse()?))
+		} else if lookahead.peek(keyword::events) {
+			Ok(Self::Events(input.parse()?))
+		} else if lookahead.peek(keyword::genesis) {
+			Ok(Self::Genesis(input.parse()?))
+		} else if lookahead.peek(keyword::hooks) {
+			Ok(Self::Hooks(input.parse()?))
+		} else if lookahead.peek(keyword::versioned) {
+			Ok(Self::Versioned(input.parse()?))
+		} else {
+			Err(lookahead.error())
+		}
+	}
+}
+
+impl PalletParts {
+	/// The parts of a pallet without a `#[pallet(..)]` attribute: calls and hooks.
+	fn without_attr() -> Self {
+		Self { calls: true, hooks: true, ..Default::default() }
+	}
+
+	/// Parse the parts listed in `#[pallet(..)]`.
+	fn try_from(attr: &syn::Attribute) -> syn::Result<Self> {
+		let list = attr.parse_args_with(
+			syn::punctuated::Punctuated::<PalletPart, syn::Token![,]>::parse_terminated,
+		)?;
+
+		let mut parts = Self::default();
+		for part in list {
+			let (flag, span) = match part {
+				PalletPart::Calls(k) => (&mut parts.calls, k.span()),
+				PalletPart::Events(k) => (&mut parts.events, k.span()),
+				PalletPart::Genesis(k) => (&mut parts.genesis, k.span()),
+				PalletPart::Hooks(k) => (&mut parts.hooks, k.span()),
+				PalletPart::Versioned(k) => (&mut parts.versioned, k.span()),
+			};
+			if *flag {
+				return Err(syn::Error::new(span, "Invalid pallet attribute, duplicate part"))
+			}
+			*flag = true;
+		}
+
+		Ok(parts)
+	}
 }
 
 impl RuntimeDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	/// Parse the `Runtime` struct.
+	///
+	/// The `#[pallet(..)]` attributes are removed from `item`, so that it can be emitted again
+	/// without them.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
 		// First we check that we are parsing a `struct`.
 		let item_struct = if let syn::Item::Struct(item) = item {
 			item
@@ -20,23 +122,87 @@ impl RuntimeDef {
 		};
 
 		// We check that the `Runtime` includes the `system` pallet as the first item.
-		check_system(&item_struct)?;
+		check_system(item_struct)?;
 
-		let runtime_struct = item_struct.ident;
+		let runtime_struct = item_struct.ident.clone();
+		let system_type =
+			item_struct.fields.iter().next().expect("checked in `check_system`; qed").ty.clone();
 
 		// Here is where we will store a list of all the pallets.
 		let mut pallets = vec![];
-		// We skip `system`, which we ensure is the first field in `check_system`.
-		for field in item_struct.fields.into_iter().skip(1) {
-			if let Some(ident) = field.ident {
-				pallets.push((ident, field.ty))
+		for (i, field) in item_struct.fields.iter_mut().enumerate() {
+			// Extract our attribute, and leave the others on the field.
+			let (pallet_attrs, kept) = field.attrs.drain(..).partition::<Vec<_>, _>(is_pallet_attr);
+			field.attrs = kept;
+
+			// We skip `system`, which we ensure is the first field in `check_system`. Its parts are
+			// fixed, so it can not be configured.
+			if i == 0 {
+				if let Some(attr) = pallet_attrs.first() {
+					let msg = "Invalid pallet attribute, the parts of system can not be configured";
+					return Err(syn::Error::new(attr.span(), msg))
+				}
+				continue
+			}
+
+			let parts = match pallet_attrs.as_slice() {
+				[] => PalletParts::without_attr(),
+				[attr] => PalletParts::try_from(attr)?,
+				[_, attr, ..] => {
+					let msg = "Invalid pallet attribute, expected a single `#[pallet(..)]`";
+					return Err(syn::Error::new(attr.span(), msg))
+				},
+			};
+
+			if let Some(ident) = &field.ident {
+				let call_path = call_path(&field.ty)?;
+				pallets.push(PalletDef {
+					name: ident.clone(),
+					type_: field.ty.clone(),
+					call_path,
+					parts,
+				})
 			}
 		}
 
-		Ok(Self { runtime_struct, pallets })
+		Ok(Self { runtime_struct, system_type, pallets })
+	}
+}
+
+/// Check if `attr` is the `#[pallet(..)]` attribute.
+fn is_pallet_attr(attr: &syn::Attribute) -> bool {
+	attr.path().is_ident("pallet")
+}
+
+/// Remove the `#[pallet(..)]` attributes from all fields of `item`, without parsing them.
+///
+/// This is used when parsing failed, so the compiler only reports the parsing error and not also
+/// the attributes it does not know about.
+pub fn strip_pallet_attrs(item: &mut syn::Item) {
+	if let syn::Item::Struct(item_struct) = item {
+		for field in item_struct.fields.iter_mut() {
+			field.attrs.retain(|attr| !is_pallet_attr(attr));
+		}
 	}
 }
 
+/// Find the path to the `Call` generated by `#[macros::call]` for a pallet, by replacing the name
+/// of the pallet struct in its type with `Call`. For example `balances::Pallet<Self>` becomes
+/// `balances::Call<Self>`, so pallets can live in any module or crate.
+fn call_path(type_: &syn::Type) -> syn::Result<syn::Path> {
+	let mut path = match type_ {
+		syn::Type::Path(tp) if tp.qself.is_none() => tp.path.clone(),
+		_ => {
+			let msg =
+				"Invalid pallet, expected a path to the pallet struct like `balances::Pallet<Self>`";
+			return Err(syn::Error::new(type_.span(), msg))
+		},
+	};
+	let last = path.segments.last_mut().expect("a path has at least one segment; qed");
+	last.ident = syn::Ident::new("Call", last.ident.span());
+	Ok(path)
+}
+
 /// This function checks that the `system` pallet is the first pallet included in the `Runtime`
 /// struct. We make many assumptions about the `system` pallet in order to keep these macros simple.
 /// For example, we assume that the system pallet has no callable functions, and that it contains
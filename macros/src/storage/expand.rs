@@ -0,0 +1,297 @@
+use super::parse::{StorageDef, StorageItemDef, StorageKind};
+use quote::{format_ident, quote};
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_storage(framework: syn::Path, def: StorageDef) -> proc_macro2::TokenStream {
+	let StorageDef { pallet_struct, generics, items, other_fields } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// This is a vector of all the storage item field names.
+	let field = items.iter().map(|item| &item.field).collect::<Vec<_>>();
+
+	// This is a vector of the expression creating each of the storage items in `field`.
+	let field_init = items.iter().map(expand_init).collect::<Vec<_>>();
+
+	// This is a vector of the functions generated for each of the storage items in `field`.
+	let functions = items.iter().map(expand_functions).collect::<Vec<_>>();
+
+	// The pallet can encode its state when all of its storage items can, which depends on the
+	// types of their keys and values.
+	let field_type = items.iter().map(|item| &item.field_type).collect::<Vec<_>>();
+	let mut state_generics = generics.clone();
+	let state_where_clause = state_generics.make_where_clause();
+	for field_type in &field_type {
+		state_where_clause
+			.predicates
+			.push(syn::parse_quote!(#field_type: #framework::storage::EncodeState));
+	}
+	let (_, _, state_where_clause) = state_generics.split_for_impl();
+
+	// The same goes for decoding its state.
+	let mut decode_generics = generics.clone();
+	let decode_where_clause = decode_generics.make_where_clause();
+	for field_type in &field_type {
+		decode_where_clause
+			.predicates
+			.push(syn::parse_quote!(#field_type: #framework::storage::DecodeState));
+	}
+	let (_, _, decode_where_clause) = decode_generics.split_for_impl();
+
+	// And for tracking the values accessed in its state.
+	let mut track_generics = generics.clone();
+	let track_where_clause = track_generics.make_where_clause();
+	for field_type in &field_type {
+		track_where_clause
+			.predicates
+			.push(syn::parse_quote!(#field_type: #framework::storage::TrackAccesses));
+	}
+	let (_, _, track_where_clause) = track_generics.split_for_impl();
+
+	quote! {
+		// Keys are taken by reference whatever their type, even when it is a `String` or a `Vec`.
+		#[allow(clippy::ptr_arg)]
+		impl #impl_generics #pallet_struct #ty_generics #where_clause {
+			// Create a new instance of the pallet, where every storage item holds its default.
+			pub fn new() -> Self {
+				Self {
+					#( #field: #field_init, )*
+					#( #other_fields: Default::default(), )*
+				}
+			}
+
+			#( #functions )*
+		}
+
+		// Each storage item is encoded under the path `prefix.field`.
+		impl #impl_generics #framework::storage::EncodeState for #pallet_struct #ty_generics
+			#state_where_clause
+		{
+			#[allow(unused_variables)]
+			fn encode_state(&self, prefix: &str, state: &mut #framework::storage::State) {
+				#(
+					#framework::storage::EncodeState::encode_state(
+						&self.#field,
+						&format!("{}.{}", prefix, stringify!(#field)),
+						state,
+					);
+				)*
+			}
+		}
+
+		// Each storage item is decoded from the path `prefix.field`. The other fields are left as
+		// they are.
+		impl #impl_generics #framework::storage::DecodeState for #pallet_struct #ty_generics
+			#decode_where_clause
+		{
+			#[allow(unused_variables)]
+			fn decode_state(
+				&mut self,
+				prefix: &str,
+				state: &#framework::storage::State,
+			) -> Result<(), &'static str> {
+				#(
+					#framework::storage::DecodeState::decode_state(
+						&mut self.#field,
+						&format!("{}.{}", prefix, stringify!(#field)),
+						state,
+					)?;
+				)*
+				Ok(())
+			}
+		}
+
+		// Each storage item tracks the values accessed under the path `prefix.field`.
+		impl #impl_generics #framework::storage::TrackAccesses for #pallet_struct #ty_generics
+			#track_where_clause
+		{
+			#[allow(unused_variables)]
+			fn take_accesses(&self, prefix: &str, accesses: &mut #framework::storage::Accesses) {
+				#(
+					#framework::storage::TrackAccesses::take_accesses(
+						&self.#field,
+						&format!("{}.{}", prefix, stringify!(#field)),
+						accesses,
+					);
+				)*
+			}
+
+			#[allow(unused_variables)]
+			fn encode_keys(
+				&self,
+				prefix: &str,
+				keys: &#framework::storage::Accesses,
+				state: &mut #framework::storage::State,
+			) {
+				#(
+					#framework::storage::TrackAccesses::encode_keys(
+						&self.#field,
+						&format!("{}.{}", prefix, stringify!(#field)),
+						keys,
+						state,
+					);
+				)*
+			}
+
+			#[allow(unused_variables)]
+			fn decode_keys(
+				&mut self,
+				prefix: &str,
+				keys: &#framework::storage::Accesses,
+				state: &#framework::storage::State,
+			) -> Result<(), &'static str> {
+				#(
+					#framework::storage::TrackAccesses::decode_keys(
+						&mut self.#field,
+						&format!("{}.{}", prefix, stringify!(#field)),
+						keys,
+						state,
+					)?;
+				)*
+				Ok(())
+			}
+		}
+	}
+}
+
+/// The expression returned when nothing is stored in `item`.
+fn default_value(item: &StorageItemDef) -> proc_macro2::TokenStream {
+	match &item.default {
+		Some(default) => quote!(#default),
+		None => quote!(Default::default()),
+	}
+}
+
+/// Generate the expression creating an empty storage item.
+fn expand_init(item: &StorageItemDef) -> proc_macro2::TokenStream {
+	let field_type = &item.field_type;
+	match item.kind {
+		StorageKind::Value { .. } => {
+			let default = default_value(item);
+			quote!(<#field_type>::new(#default))
+		},
+		StorageKind::Map { .. } | StorageKind::DoubleMap { .. } => quote!(<#field_type>::new()),
+	}
+}
+
+/// Generate the getter, `set_`, `mutate_` and `take_` functions of a storage item.
+///
+/// A map with a `default` returns that default for the keys where nothing is stored, otherwise it
+/// returns an `Option`.
+fn expand_functions(item: &StorageItemDef) -> proc_macro2::TokenStream {
+	let field = &item.field;
+	let getter = &item.getter;
+	let set = format_ident!("set_{}", getter);
+	let mutate = format_ident!("mutate_{}", getter);
+	let take = format_ident!("take_{}", getter);
+	let default = default_value(item);
+
+	match (&item.kind, item.default.is_some()) {
+		(StorageKind::Value { value }, _) => quote! {
+			pub fn #getter(&self) -> #value {
+				self.#field.get().clone()
+			}
+
+			pub fn #set(&mut self, value: #value) {
+				self.#field.put(value)
+			}
+
+			pub fn #mutate<R>(&mut self, f: impl FnOnce(&mut #value) -> R) -> R {
+				self.#field.mutate(f)
+			}
+
+			pub fn #take(&mut self) -> #value {
+				self.#field.replace(#default)
+			}
+		},
+		(StorageKind::Map { key, value }, true) => quote! {
+			pub fn #getter(&self, key: &#key) -> #value {
+				self.#field.get(key).cloned().unwrap_or_else(|| #default)
+			}
+
+			pub fn #set(&mut self, key: &#key, value: #value) {
+				self.#field.insert(key.clone(), value);
+			}
+
+			pub fn #mutate<R>(&mut self, key: &#key, f: impl FnOnce(&mut #value) -> R) -> R {
+				self.#field.mutate_or_insert(key.clone(), || #default, f)
+			}
+
+			pub fn #take(&mut self, key: &#key) -> #value {
+				self.#field.remove(key).unwrap_or_else(|| #default)
+			}
+		},
+		(StorageKind::Map { key, value }, false) => quote! {
+			pub fn #getter(&self, key: &#key) -> Option<&#value> {
+				self.#field.get(key)
+			}
+
+			pub fn #set(&mut self, key: &#key, value: #value) {
+				self.#field.insert(key.clone(), value);
+			}
+
+			// The value is removed from storage if `f` leaves `None` behind.
+			pub fn #mutate<R>(&mut self, key: &#key, f: impl FnOnce(&mut Option<#value>) -> R) -> R {
+				let mut value = self.#field.remove(key);
+				let result = f(&mut value);
+				if let Some(value) = value {
+					self.#field.insert(key.clone(), value);
+				}
+				result
+			}
+
+			pub fn #take(&mut self, key: &#key) -> Option<#value> {
+				self.#field.remove(key)
+			}
+		},
+		(StorageKind::DoubleMap { key1, key2, value }, true) => quote! {
+			pub fn #getter(&self, key1: &#key1, key2: &#key2) -> #value {
+				self.#field.get(key1, key2).cloned().unwrap_or_else(|| #default)
+			}
+
+			pub fn #set(&mut self, key1: &#key1, key2: &#key2, value: #value) {
+				self.#field.insert(key1.clone(), key2.clone(), value);
+			}
+
+			pub fn #mutate<R>(
+				&mut self,
+				key1: &#key1,
+				key2: &#key2,
+				f: impl FnOnce(&mut #value) -> R,
+			) -> R {
+				self.#field.mutate_or_insert(key1.clone(), key2.clone(), || #default, f)
+			}
+
+			pub fn #take(&mut self, key1: &#key1, key2: &#key2) -> #value {
+				self.#field.remove(key1, key2).unwrap_or_else(|| #default)
+			}
+		},
+		(StorageKind::DoubleMap { key1, key2, value }, false) => quote! {
+			pub fn #getter(&self, key1: &#key1, key2: &#key2) -> Option<&#value> {
+				self.#field.get(key1, key2)
+			}
+
+			pub fn #set(&mut self, key1: &#key1, key2: &#key2, value: #value) {
+				self.#field.insert(key1.clone(), key2.clone(), value);
+			}
+
+			// The value is removed from storage if `f` leaves `None` behind.
+			pub fn #mutate<R>(
+				&mut self,
+				key1: &#key1,
+				key2: &#key2,
+				f: impl FnOnce(&mut Option<#value>) -> R,
+			) -> R {
+				let mut value = self.#field.remove(key1, key2);
+				let result = f(&mut value);
+				if let Some(value) = value {
+					self.#field.insert(key1.clone(), key2.clone(), value);
+				}
+				result
+			}
+
+			pub fn #take(&mut self, key1: &#key1, key2: &#key2) -> Option<#value> {
+				self.#field.remove(key1, key2)
+			}
+		},
+	}
+}
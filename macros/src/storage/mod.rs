@@ -0,0 +1,30 @@
+pub mod expand;
+pub mod parse;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn storage(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
+
+	// First we parse the path to the framework crate from the macro arguments, and the storage
+	// items of the pallet struct. This also strips the `#[storage(..)]` attributes from its
+	// fields...
+	let framework = crate::framework::parse_framework(attr);
+	let generated = match (framework, parse::StorageDef::try_from(&mut item_mod)) {
+		// ..then we generate our new code.
+		(Ok(framework), Ok(def)) => expand::expand_storage(framework, def),
+		(Err(e), _) | (_, Err(e)) => {
+			parse::strip_storage_attrs(&mut item_mod);
+			e.to_compile_error()
+		},
+	};
+
+	// The final product contains all of our old code too, followed by our generated code.
+	quote::quote! {
+		#item_mod
+		#generated
+	}
+	.into()
+}
@@ -0,0 +1,220 @@
+use syn::spanned::Spanned;
+
+// Custom keywords we match to when parsing the `#[storage(..)]` attribute of a field.
+mod keyword {
+	syn::custom_keyword!(getter);
+	syn::custom_keyword!(default);
+}
+
+/// This object will collect all the information we need to keep while parsing the pallet struct.
+#[derive(Debug)]
+pub struct StorageDef {
+	/// This is the name of the pallet struct. We mostly assume it is `Pallet`.
+	pub pallet_struct: syn::Ident,
+	/// The generics of the pallet struct, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is a list of the storage items of the pallet. See `StorageItemDef`.
+	pub items: Vec<StorageItemDef>,
+	/// These are the fields of the pallet struct which are not storage items. They are created
+	/// with `Default::default()`.
+	pub other_fields: Vec<syn::Ident>,
+}
+
+/// The kinds of storage items, and the types of their keys and values.
+#[derive(Debug)]
+pub enum StorageKind {
+	/// `StorageValue<V>`
+	Value { value: Box<syn::Type> },
+	/// `StorageMap<K, V>`
+	Map { key: Box<syn::Type>, value: Box<syn::Type> },
+	/// `StorageDoubleMap<K1, K2, V>`
+	DoubleMap { key1: Box<syn::Type>, key2: Box<syn::Type>, value: Box<syn::Type> },
+}
+
+/// This is the metadata we keep about each storage item of our pallet.
+#[derive(Debug)]
+pub struct StorageItemDef {
+	/// The name of the field.
+	pub field: syn::Ident,
+	/// The full type of the field, like `StorageMap<T::AccountId, T::Balance>`.
+	pub field_type: syn::Type,
+	/// The name of the generated getter, from `#[storage(getter = name)]`. Defaults to the name of
+	/// the field. The other generated functions are named after it.
+	pub getter: syn::Ident,
+	/// The value returned when nothing is stored, from `#[storage(default = expr)]`.
+	pub default: Option<syn::Expr>,
+	/// The kind of storage item.
+	pub kind: StorageKind,
+}
+
+/// A single argument of the `#[storage(..)]` attribute.
+pub enum StorageArg {
+	/// `getter = name`
+	Getter(syn::Ident),
+	/// `default = expr`
+	Default(syn::Expr),
+}
+
+impl syn::parse::Parse for StorageArg {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		let lookahead = input.lookahead1();
+		if lookahead.peek(keyword::getter) {
+			input.parse::<keyword::getter>()?;
+			input.parse::<syn::Token![=]>()?;
+			Ok(Self::Getter(input.parse()?))
+		} else if lookahead.peek(keyword::default) {
+			input.parse::<keyword::default>()?;
+			input.parse::<syn::Token![=]>()?;
+			Ok(Self::Default(input.parse()?))
+		} else {
+			Err(lookahead.error())
+		}
+	}
+}
+
+impl StorageDef {
+	/// Parse the storage items of `item`.
+	///
+	/// The `#[storage(..)]` attributes are removed from `item`, so that it can be emitted again
+	/// without them.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing a `struct`.
+		let item_struct = if let syn::Item::Struct(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::storage, expected item struct"))
+		};
+
+		let pallet_struct = item_struct.ident.clone();
+		let generics = item_struct.generics.clone();
+
+		let fields = if let syn::Fields::Named(fields) = &mut item_struct.fields {
+			fields
+		} else {
+			let msg = "Invalid pallet::storage, expected struct with named fields";
+			return Err(syn::Error::new(item_struct.span(), msg))
+		};
+
+		let mut items = vec![];
+		let mut other_fields = vec![];
+		for field in fields.named.iter_mut() {
+			let field_name = field.ident.clone().expect("fields are named; qed");
+
+			// Extract our attribute, and leave the others on the field.
+			let (storage_attrs, kept) =
+				field.attrs.drain(..).partition::<Vec<_>, _>(is_storage_attr);
+			field.attrs = kept;
+
+			let mut getter = None;
+			let mut default = None;
+			for attr in &storage_attrs {
+				let args = attr.parse_args_with(
+					syn::punctuated::Punctuated::<StorageArg, syn::Token![,]>::parse_terminated,
+				)?;
+				for arg in args {
+					match arg {
+						StorageArg::Getter(ident) if getter.is_none() => getter = Some(ident),
+						StorageArg::Default(expr) if default.is_none() => default = Some(expr),
+						_ => {
+							let msg = "Invalid storage attribute, duplicate argument";
+							return Err(syn::Error::new(attr.span(), msg))
+						},
+					}
+				}
+			}
+
+			let kind = match storage_kind(&field.ty)? {
+				Some(kind) => kind,
+				None => {
+					// Any field which is not a storage item must not be configured as one.
+					if let Some(attr) = storage_attrs.first() {
+						let msg = "Invalid storage attribute, field is not a `StorageValue`, \
+							`StorageMap` or `StorageDoubleMap`";
+						return Err(syn::Error::new(attr.span(), msg))
+					}
+					other_fields.push(field_name);
+					continue
+				},
+			};
+
+			items.push(StorageItemDef {
+				getter: getter.unwrap_or_else(|| field_name.clone()),
+				field: field_name,
+				field_type: field.ty.clone(),
+				default,
+				kind,
+			});
+		}
+
+		Ok(Self { pallet_struct, generics, items, other_fields })
+	}
+}
+
+/// Check if `attr` is the `#[storage(..)]` attribute.
+fn is_storage_attr(attr: &syn::Attribute) -> bool {
+	attr.path().is_ident("storage")
+}
+
+/// Remove the `#[storage(..)]` attributes from all fields of `item`, without parsing them.
+///
+/// This is used when parsing failed, so the compiler only reports the parsing error and not also
+/// the attributes it does not know about.
+pub fn strip_storage_attrs(item: &mut syn::Item) {
+	if let syn::Item::Struct(item_struct) = item {
+		for field in item_struct.fields.iter_mut() {
+			field.attrs.retain(|attr| !is_storage_attr(attr));
+		}
+	}
+}
+
+/// Find the kind of storage item of a field from its type, like `StorageMap<K, V>`. Returns
+/// `None` if the field is not a storage item.
+///
+/// We only look at the last segment of the type path, so `storage::StorageMap<K, V>` also works.
+fn storage_kind(type_: &syn::Type) -> syn::Result<Option<StorageKind>> {
+	let segment = match type_ {
+		syn::Type::Path(tp) => match tp.path.segments.last() {
+			Some(segment) => segment,
+			None => return Ok(None),
+		},
+		_ => return Ok(None),
+	};
+
+	let expected_args = match segment.ident.to_string().as_str() {
+		"StorageValue" => 1,
+		"StorageMap" => 2,
+		"StorageDoubleMap" => 3,
+		_ => return Ok(None),
+	};
+
+	// Collect the generic type arguments, like `K` and `V` in `StorageMap<K, V>`.
+	let args = match &segment.arguments {
+		syn::PathArguments::AngleBracketed(args) => args
+			.args
+			.iter()
+			.filter_map(|arg| match arg {
+				syn::GenericArgument::Type(ty) => Some(Box::new(ty.clone())),
+				_ => None,
+			})
+			.collect::<Vec<_>>(),
+		_ => vec![],
+	};
+
+	if args.len() != expected_args {
+		let msg = format!(
+			"Invalid storage item, `{}` expects {} type arguments",
+			segment.ident, expected_args
+		);
+		return Err(syn::Error::new(segment.span(), msg))
+	}
+
+	let mut args = args.into_iter();
+	let mut next = || args.next().expect("checked the number of args above; qed");
+	let kind = match expected_args {
+		1 => StorageKind::Value { value: next() },
+		2 => StorageKind::Map { key: next(), value: next() },
+		_ => StorageKind::DoubleMap { key1: next(), key2: next(), value: next() },
+	};
+
+	Ok(Some(kind))
+}
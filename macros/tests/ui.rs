@@ -0,0 +1,6 @@
+/// Checks the compile errors of the macros, and that they point at the offending tokens.
+#[test]
+fn ui() {
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/ui/*.rs");
+}
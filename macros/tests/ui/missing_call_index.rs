@@ -0,0 +1,19 @@
+pub trait Config {
+	type AccountId;
+}
+
+pub struct Pallet<T: Config>(core::marker::PhantomData<T>);
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	#[call_index(1)]
+	pub fn first(&mut self, _caller: T::AccountId) -> Result<(), &'static str> {
+		Ok(())
+	}
+
+	pub fn second(&mut self, _caller: T::AccountId) -> Result<(), &'static str> {
+		Ok(())
+	}
+}
+
+fn main() {}
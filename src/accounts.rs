@@ -0,0 +1,158 @@
+use crate::crypto::{Pair, PublicKey};
+use crate::storage::StorageMap;
+use crate::support::Get;
+
+/// The configuration trait for the Accounts Module.
+pub trait Config: crate::system::Config {
+    /// The account allowed to set the key of any account, to register new accounts.
+    /// It stands in for the root origin of a real blockchain, like a governance body.
+    type Root: Get<Self::AccountId>;
+}
+
+/// The errors which can be returned by the calls of the Accounts Module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The caller is neither the account whose key is set, nor the root account.
+    NotAllowed,
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotAllowed => "caller can not set the key of this account",
+        }
+    }
+}
+
+/// This is the Accounts Module.
+/// It keeps the public key of each account, so the runtime can check that the extrinsics of an
+/// account are signed with its key in its `support::AccountKeys`.
+#[macros::storage]
+#[derive(Debug, Clone)]
+pub struct Pallet<T: Config> {
+    /// A map from an account to its public key.
+    /// An account without a key can not sign extrinsics.
+    #[storage(getter = account_key)]
+    keys: StorageMap<T::AccountId, PublicKey>,
+    /// The events emitted by this pallet, until the runtime takes them.
+    events: Vec<Event<T::AccountId>>,
+}
+
+/// The events emitted by the Accounts Module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<AccountId> {
+    /// The extrinsics of `who` are signed with `key` from now on.
+    KeySet { who: AccountId, key: PublicKey },
+}
+
+/// The initial state of the Accounts Module.
+#[derive(serde::Deserialize)]
+pub struct GenesisConfig<AccountId> {
+    /// The accounts which can sign extrinsics when the chain starts, and their public key.
+    pub keys: Vec<(AccountId, PublicKey)>,
+}
+
+impl<AccountId: From<&'static str>> GenesisConfig<AccountId> {
+    /// The accounts of a development chain, named `names`, where each account signs with the key
+    /// pair of its own name as seed, see `Pair::from_seed`. Anyone can sign for these accounts.
+    pub fn dev(names: &[&'static str]) -> Self {
+        let keys = names
+            .iter()
+            .map(|name| (AccountId::from(*name), Pair::from_seed(name).public()))
+            .collect();
+        GenesisConfig { keys }
+    }
+}
+
+impl<T: Config> crate::support::Events for Pallet<T> {
+    type Event = Event<T::AccountId>;
+
+    fn take_events(&mut self) -> Vec<Self::Event> {
+        core::mem::take(&mut self.events)
+    }
+}
+
+impl<T: Config> crate::support::BuildGenesis for Pallet<T> {
+    type GenesisConfig = GenesisConfig<T::AccountId>;
+
+    fn build_genesis(&mut self, config: &Self::GenesisConfig) {
+        for (who, key) in &config.keys {
+            self.set_account_key(who, *key);
+        }
+    }
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+    /// Set the public key of `who`, which signs their extrinsics from now on.
+    /// An account can change its own key, and the root account can set the key of any account,
+    /// like a new one.
+    #[call_index(0)]
+    #[weight(5_000)]
+    pub fn set_key(
+        &mut self,
+        caller: T::AccountId,
+        who: T::AccountId,
+        key: PublicKey,
+    ) -> crate::support::DispatchResult {
+        if caller != who && caller != T::Root::get() {
+            return Err(Error::NotAllowed.into());
+        }
+        self.set_account_key(&who, key);
+        self.events.push(Event::KeySet { who, key });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto::Pair;
+
+    struct TestConfig;
+
+    struct Root;
+
+    impl crate::support::Get<&'static str> for Root {
+        fn get() -> &'static str {
+            "root"
+        }
+    }
+
+    impl crate::system::Config for TestConfig {
+        type AccountId = &'static str;
+        type BlockNumber = u32;
+        type Nonce = u32;
+    }
+
+    impl super::Config for TestConfig {
+        type Root = Root;
+    }
+
+    /// This test checks the following:
+    /// - The genesis of a development chain gives each account the key of its name.
+    /// - An account can change its own key, and root can set the key of any account.
+    /// - No other account can set a key.
+    #[test]
+    fn set_keys() {
+        use crate::support::BuildGenesis;
+
+        let mut accounts = super::Pallet::<TestConfig>::new();
+        accounts.build_genesis(&super::GenesisConfig::dev(&["alice"]));
+        let (alice, bob) = (Pair::from_seed("alice"), Pair::from_seed("bob"));
+        assert_eq!(accounts.account_key(&"alice"), Some(&alice.public()));
+        assert_eq!(accounts.account_key(&"bob"), None);
+
+        let new_key = Pair::from_seed("alice new seed").public();
+        assert_eq!(
+            accounts.set_key("bob", "alice", bob.public()),
+            Err("caller can not set the key of this account")
+        );
+        assert_eq!(accounts.set_key("alice", "alice", new_key), Ok(()));
+        assert_eq!(accounts.set_key("root", "bob", bob.public()), Ok(()));
+        assert_eq!(accounts.account_key(&"alice"), Some(&new_key));
+        assert_eq!(accounts.account_key(&"bob"), Some(&bob.public()));
+
+        let events = crate::support::Events::take_events(&mut accounts);
+        assert_eq!(events.len(), 2);
+    }
+}
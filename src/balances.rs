@@ -1,46 +1,104 @@
+use crate::storage::StorageMap;
 use num::traits::{CheckedAdd, CheckedSub, Zero};
-use std::collections::BTreeMap;
 
 /// The configuration trait for the Balances Module.
 /// Contains the basic types needed for handling balances.
-pub trait Config: crate::system::Config {
+///
+/// The module is instantiable: a runtime can include several independent ledgers, each with its own
+/// instance type `I` and its own `Config<I>`. The default instance is `()`.
+pub trait Config<I: 'static = ()>: crate::system::Config {
     /// A type which can represent the balance of an account.
     /// Usually this is a large unsigned integer.
     type Balance: Zero + CheckedSub + CheckedAdd + Copy;
 }
 
-#[derive(Debug)]
-pub struct Pallet<T: Config> {
-    balances: BTreeMap<T::AccountId, T::Balance>,
+/// This is the Balances Module.
+/// It keeps track of how much balance each account has in this state machine.
+#[macros::storage]
+#[derive(Debug, Clone)]
+pub struct Pallet<T: Config<I>, I: 'static = ()> {
+    /// A map from an account to their balance.
+    /// If the account has no stored balance, it has a balance of zero.
+    #[storage(getter = balance, default = T::Balance::zero())]
+    balances: StorageMap<T::AccountId, T::Balance>,
+    /// The events emitted by this pallet, until the runtime takes them.
+    events: Vec<Event<T::AccountId, T::Balance>>,
+    _instance: core::marker::PhantomData<I>,
 }
 
-impl<T: Config> Pallet<T> {
-    /// Create a new instance of the balances module
-    pub fn new() -> Self {
-        Self {
-            balances: BTreeMap::new(),
+/// The events emitted by the Balances Module.
+#[derive(Debug, Clone)]
+pub enum Event<AccountId, Balance> {
+    /// `amount` was transferred from `from` to `to`.
+    Transfer {
+        from: AccountId,
+        to: AccountId,
+        amount: Balance,
+    },
+}
+
+/// The initial state of the Balances Module.
+#[derive(serde::Deserialize)]
+pub struct GenesisConfig<AccountId, Balance> {
+    /// The accounts which have a balance when the chain starts, and their balance.
+    pub balances: Vec<(AccountId, Balance)>,
+}
+
+impl<T: Config<I>, I: 'static> crate::support::Events for Pallet<T, I> {
+    type Event = Event<T::AccountId, T::Balance>;
+
+    fn take_events(&mut self) -> Vec<Self::Event> {
+        core::mem::take(&mut self.events)
+    }
+}
+
+impl<T: Config<I>, I: 'static> crate::support::BuildGenesis for Pallet<T, I> {
+    type GenesisConfig = GenesisConfig<T::AccountId, T::Balance>;
+
+    fn build_genesis(&mut self, config: &Self::GenesisConfig) {
+        for (who, amount) in &config.balances {
+            self.set_balance(who, *amount);
         }
     }
+}
 
-    /// Set the balance of an account `who` to some `amount`
-    pub fn set_balance(&mut self, who: &T::AccountId, amount: T::Balance) {
-        self.balances.insert(who.clone(), amount);
+impl<T: Config<I>, I: 'static> crate::support::Hooks<T::BlockNumber> for Pallet<T, I> {}
+
+impl<T: Config<I>, I: 'static> Pallet<T, I> {
+    /// Check that `amount` can be withdrawn from the balance of `who`.
+    pub fn ensure_can_withdraw(
+        &self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> crate::support::DispatchResult {
+        self.balance(who)
+            .checked_sub(&amount)
+            .map(|_| ())
+            .ok_or("insufficient balance")
     }
 
-    /// Get the balance of an account `who`.
-    /// If the account has no stored balance, we return zero.
-    pub fn balance(&self, who: &T::AccountId) -> T::Balance {
-        // dereferencing with `*` as result will be &value or &0
-        // note, get will return Option<&V> hence the dereferencing
-        *self.balances.get(who).unwrap_or(&T::Balance::zero())
+    /// Withdraw `amount` from the balance of `who`, and burn it, like the fees of an extrinsic.
+    pub fn withdraw(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> crate::support::DispatchResult {
+        let new_balance = self
+            .balance(who)
+            .checked_sub(&amount)
+            .ok_or("insufficient balance")?;
+        self.set_balance(who, new_balance);
+        Ok(())
     }
 }
 
 #[macros::call]
-impl<T: Config> Pallet<T> {
+impl<T: Config<I>, I: 'static> Pallet<T, I> {
     /// Transfer `amount` from one account to another.
     /// This function verifies that `from` has at least `amount` balance to transfer,
     /// and that no mathematical overflows occur.
+    #[call_index(0)]
+    #[weight(10_000)]
     pub fn transfer(
         &mut self,
         caller: T::AccountId,
@@ -61,6 +119,12 @@ impl<T: Config> Pallet<T> {
         self.set_balance(&caller, new_caller_balance);
         self.set_balance(&to, new_to_balance);
 
+        self.events.push(Event::Transfer {
+            from: caller,
+            to,
+            amount,
+        });
+
         Ok(())
     }
 }
@@ -112,5 +176,61 @@ mod tests {
 
         assert_eq!(balances.balance(&alice), 5);
         assert_eq!(balances.balance(&bob), 10);
+
+        let events = crate::support::Events::take_events(&mut balances);
+        assert!(matches!(
+            events.as_slice(),
+            [super::Event::Transfer { from, to, amount: 10 }] if *from == alice && *to == bob
+        ));
+        assert!(crate::support::Events::take_events(&mut balances).is_empty());
+    }
+
+    /// Checks the genesis config sets the initial balances.
+    #[test]
+    fn genesis_balances() {
+        let mut balances = super::Pallet::<TestConfig>::new();
+        let config = super::GenesisConfig {
+            balances: vec![(String::from("alice"), 100), (String::from("bob"), 50)],
+        };
+
+        crate::support::BuildGenesis::build_genesis(&mut balances, &config);
+
+        assert_eq!(balances.balance(&"alice".to_string()), 100);
+        assert_eq!(balances.balance(&"bob".to_string()), 50);
+    }
+
+    /// Checks the index and weight declared on `transfer` are exposed on the generated `Call`.
+    #[test]
+    fn call_index_and_weight() {
+        let call = super::Call::<TestConfig>::transfer {
+            to: String::from("bob"),
+            amount: 10,
+        };
+
+        assert_eq!(call.call_index(), 0);
+        assert_eq!(call.weight(), 10_000);
+    }
+
+    /// This test checks the following:
+    /// - The generated `Call` can be cloned and compared, although `TestConfig` implements neither.
+    /// - It has a `Debug` and a compact `Display` format.
+    #[test]
+    fn call_traits() {
+        let call = super::Call::<TestConfig>::transfer {
+            to: String::from("bob"),
+            amount: 30,
+        };
+        let other = super::Call::<TestConfig>::transfer {
+            to: String::from("bob"),
+            amount: 31,
+        };
+
+        assert_eq!(call.clone(), call);
+        assert_ne!(call, other);
+        assert_eq!(
+            format!("{:?}", call),
+            r#"transfer { to: "bob", amount: 30 }"#
+        );
+        assert_eq!(call.to_string(), "transfer(to=bob, amount=30)");
     }
 }
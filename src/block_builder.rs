@@ -0,0 +1,213 @@
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime, RuntimeEvent};
+use crate::support::{self, BlockReceipt, ExtrinsicReceipt, Weight};
+
+/// The errors which can be returned when applying an extrinsic to a block being built. The
+/// extrinsic is not included in the block, and the state of the runtime is not changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The weight of the extrinsic does not fit in what is left of the block.
+    ExhaustsResources,
+    /// The extrinsic can not be executed, like when its nonce is wrong or its fees can not be paid.
+    Invalid(&'static str),
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::ExhaustsResources => "extrinsic does not fit in the block",
+            Error::Invalid(error) => error,
+        }
+    }
+}
+
+/// Builds a block on a `Runtime`, one extrinsic at a time.
+///
+/// The block is executed while it is built, so the runtime holds the state after the block once it
+/// is finalized. Executing the finalized block with `Runtime::execute_block` on the state the
+/// builder started from gives the same state.
+#[derive(Debug)]
+pub struct BlockBuilder<'a> {
+    runtime: &'a mut Runtime,
+    /// The header of the block, whose roots are filled in when it is finalized.
+    header: types::Header,
+    /// The maximum weight of the block, including the hooks.
+    max_weight: Weight,
+    /// The extrinsics included in the block so far.
+    extrinsics: Vec<types::Extrinsic>,
+    /// The receipt of each extrinsic included in the block so far.
+    receipts: Vec<ExtrinsicReceipt<RuntimeEvent>>,
+    /// The events emitted by the `on_initialize` hooks.
+    initialize_events: Vec<RuntimeEvent>,
+}
+
+impl<'a> BlockBuilder<'a> {
+    /// Start building the block following the current block of `runtime`, whose parent has the
+    /// hash `parent_hash`, and whose weight can not exceed `max_weight`. The `on_initialize` hooks
+    /// are executed right away.
+    pub fn new(runtime: &'a mut Runtime, parent_hash: Hash, max_weight: Weight) -> Self {
+        let block_number = runtime.system.block_number() + 1;
+        let initialize_events = runtime
+            .initialize_block(block_number)
+            .expect("the block number follows the current one; qed");
+        Self {
+            runtime,
+            header: types::Header {
+                parent_hash,
+                block_number,
+                state_root: Hash::default(),
+                extrinsics_root: Hash::default(),
+            },
+            max_weight,
+            extrinsics: Vec::new(),
+            receipts: Vec::new(),
+            initialize_events,
+        }
+    }
+
+    /// The weight of the block so far.
+    pub fn weight(&self) -> Weight {
+        self.runtime.system.block_weight()
+    }
+
+    /// Execute `extrinsic` and include it in the block.
+    ///
+    /// Returns an error if the extrinsic does not fit in the block or can not be executed, in which
+    /// case it is not included. Otherwise, returns its receipt, whose result tells if its call
+    /// succeeded.
+    pub fn apply_extrinsic(
+        &mut self,
+        extrinsic: types::Extrinsic,
+    ) -> Result<&ExtrinsicReceipt<RuntimeEvent>, Error> {
+        let weight = extrinsic.call.weight();
+        if self.weight().saturating_add(weight) > self.max_weight {
+            return Err(Error::ExhaustsResources);
+        }
+        let result = self
+            .runtime
+            .apply_extrinsic(extrinsic.clone())
+            .map_err(Error::Invalid)?;
+
+        self.receipts.push(ExtrinsicReceipt {
+            index: self.extrinsics.len(),
+            result,
+            weight,
+            events: self.runtime.take_events(),
+        });
+        self.extrinsics.push(extrinsic);
+        Ok(self.receipts.last().expect("just pushed; qed"))
+    }
+
+    /// Execute the `on_finalize` hooks, and return the complete block with its receipt. The header
+    /// commits to the extrinsics of the block and to the state after it.
+    pub fn finalize(self) -> (types::Block, BlockReceipt<RuntimeEvent>) {
+        let finalize_events = self.runtime.finalize_block(self.header.block_number);
+        let state_root = self.runtime.state_root();
+        let header = types::Header {
+            state_root,
+            extrinsics_root: support::extrinsics_root(&self.extrinsics),
+            ..self.header
+        };
+        let receipt = BlockReceipt {
+            extrinsics: self.receipts,
+            initialize_events: self.initialize_events,
+            finalize_events,
+            weight: self.runtime.system.block_weight(),
+            state_root,
+        };
+        (
+            types::Block {
+                header,
+                extrinsics: self.extrinsics,
+            },
+            receipt,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockBuilder, Error};
+    use crate::crypto::Pair;
+    use crate::merkle::Hash;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::support::{self, INVALID_NONCE};
+    use crate::{accounts, balances, proof_of_existence};
+
+    fn genesis() -> Runtime {
+        Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(String::from("alice"), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice"]),
+        })
+    }
+
+    fn transfer(nonce: u32, amount: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount,
+        };
+        types::Extrinsic::new(String::from("alice"), nonce, call).sign(&Pair::from_seed("alice"))
+    }
+
+    /// This test checks the following:
+    /// - Extrinsics which can not be executed, or do not fit in the block, are not included.
+    /// - Included extrinsics report whether their call succeeded.
+    /// - The finalized block has its roots filled in, and executing it again on the initial state
+    ///   gives the same state and receipt.
+    #[test]
+    fn build_and_execute() {
+        let mut runtime = genesis();
+        let mut builder = BlockBuilder::new(&mut runtime, Hash::default(), 30_000);
+
+        let receipt = builder.apply_extrinsic(transfer(0, 30)).unwrap();
+        assert_eq!((receipt.index, receipt.result), (0, Ok(())));
+        assert_eq!(
+            builder.apply_extrinsic(transfer(0, 30)).err(),
+            Some(Error::Invalid(INVALID_NONCE))
+        );
+        let receipt = builder.apply_extrinsic(transfer(1, 500)).unwrap();
+        assert_eq!(
+            (receipt.index, receipt.result),
+            (1, Err("error subtracting balance"))
+        );
+        assert_eq!(builder.weight(), 20_000);
+        let claim = proof_of_existence::Call::create_claim {
+            claim: String::from("document"),
+        };
+        let claim =
+            types::Extrinsic::new(String::from("alice"), 2, claim).sign(&Pair::from_seed("alice"));
+        assert!(builder.apply_extrinsic(claim).is_ok());
+        assert_eq!(
+            builder.apply_extrinsic(transfer(3, 10)).err(),
+            Some(Error::ExhaustsResources)
+        );
+
+        let (block, receipt) = builder.finalize();
+        assert_eq!(block.header.block_number, 1);
+        assert_eq!(block.extrinsics.len(), 3);
+        assert_eq!(
+            block.header.extrinsics_root,
+            support::extrinsics_root(&block.extrinsics)
+        );
+        assert_eq!(block.header.state_root, runtime.state_root());
+        assert_eq!(receipt.extrinsics.len(), 3);
+
+        let mut replayed = genesis();
+        let replayed_receipt = replayed.execute_block(block.clone()).unwrap();
+        assert_eq!(replayed_receipt.weight, receipt.weight);
+        assert_eq!(replayed_receipt.state_root, block.header.state_root);
+        let results = |receipt: &support::BlockReceipt<_>| {
+            receipt
+                .extrinsics
+                .iter()
+                .map(|extrinsic| extrinsic.result)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(results(&replayed_receipt), results(&receipt));
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", runtime));
+        assert_eq!(runtime.balances.balance(&String::from("alice")), 70);
+    }
+}
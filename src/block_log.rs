@@ -0,0 +1,225 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime, RuntimeEvent};
+use crate::support::BlockReceipt;
+
+/// The outcome of executing a block: the state root after it, or the error it failed with.
+pub type Outcome = Result<Hash, String>;
+
+/// A single entry of the log, which is a line of JSON in the log file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// The executed block.
+    pub block: types::Block,
+    /// The outcome of executing the block.
+    pub outcome: Outcome,
+}
+
+/// The errors which can be returned when writing or replaying a block log.
+#[derive(Debug)]
+pub enum Error {
+    /// The log file can not be read or written.
+    Io(std::io::Error),
+    /// The entry at `line` of the log file, counting from one, can not be decoded.
+    Corrupt { line: usize },
+    /// The block failed to execute. It was still recorded, with its error.
+    InvalidBlock(&'static str),
+    /// Replaying the block at `line` of the log file gave another outcome than the recorded one.
+    Diverged {
+        line: usize,
+        recorded: Outcome,
+        replayed: Outcome,
+    },
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(_) => "block log can not be read or written",
+            Error::Corrupt { .. } => "block log entry can not be decoded",
+            Error::InvalidBlock(error) => error,
+            Error::Diverged { .. } => "replayed block does not match the block log",
+        }
+    }
+}
+
+/// An append-only log of the blocks executed on a `Runtime`, with the outcome of each of them.
+///
+/// Execution is deterministic, so replaying the log from the same genesis with `replay` goes
+/// through the exact same states, including the failures of the blocks which did not execute.
+#[derive(Debug)]
+pub struct BlockLog {
+    file: File,
+}
+
+impl BlockLog {
+    /// Open the log at `path` for appending, creating it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Append `entry` to the log. The entry is on disk once this returns.
+    pub fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+        let mut line = serde_json::to_string(entry).expect("blocks always serialize");
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Execute `block` on `runtime`, and append it to the log with its outcome.
+    ///
+    /// A block which fails to execute is recorded too, and returns `Error::InvalidBlock`.
+    pub fn execute_block(
+        &mut self,
+        runtime: &mut Runtime,
+        block: types::Block,
+    ) -> Result<BlockReceipt<RuntimeEvent>, Error> {
+        let result = runtime.execute_block(block.clone());
+        let outcome = match &result {
+            Ok(receipt) => Ok(receipt.state_root),
+            Err(error) => Err(error.to_string()),
+        };
+        self.append(&Entry { block, outcome })?;
+        result.map_err(Error::InvalidBlock)
+    }
+}
+
+/// Read every entry of the log at `path`, in the order they were appended.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Entry>, Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let entry = serde_json::from_str(&line?).map_err(|_| Error::Corrupt { line: index + 1 })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Replay the log at `path` on `runtime`, which must hold the genesis the log was recorded from,
+/// checking that every block has its recorded outcome.
+///
+/// Returns the number of replayed blocks. If a block diverges from the log, the replay stops there,
+/// and `runtime` holds the state after that block.
+pub fn replay(path: impl AsRef<Path>, runtime: &mut Runtime) -> Result<usize, Error> {
+    let entries = read(path)?;
+    for (index, entry) in entries.iter().enumerate() {
+        let replayed = match runtime.execute_block(entry.block.clone()) {
+            Ok(receipt) => Ok(receipt.state_root),
+            Err(error) => Err(error.to_string()),
+        };
+        if replayed != entry.outcome {
+            return Err(Error::Diverged {
+                line: index + 1,
+                recorded: entry.outcome.clone(),
+                replayed,
+            });
+        }
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockLog, Error};
+    use crate::crypto::Pair;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::{accounts, balances, support};
+
+    fn genesis(balance: u128) -> Runtime {
+        Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(String::from("alice"), balance)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice"]),
+        })
+    }
+
+    fn block(block_number: u32, extrinsics: Vec<types::Extrinsic>) -> types::Block {
+        types::Block {
+            header: support::Header {
+                block_number,
+                ..Default::default()
+            },
+            extrinsics,
+        }
+    }
+
+    fn transfer(nonce: u32, amount: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount,
+        };
+        types::Extrinsic::new(String::from("alice"), nonce, call).sign(&Pair::from_seed("alice"))
+    }
+
+    /// This test checks the following:
+    /// - Executed blocks are appended to the log with their state root, and failed blocks with
+    ///   their error, without moving the runtime to the next block number.
+    /// - Replaying the log on the same genesis reaches the same state, and the same failure.
+    /// - Replaying it on another genesis, or replaying a tampered log, stops at the first block
+    ///   which diverges, and a line which can not be decoded is reported.
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("block_log_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut runtime = genesis(100);
+        let mut log = BlockLog::open(&path).unwrap();
+        log.execute_block(&mut runtime, block(1, vec![transfer(0, 30)]))
+            .unwrap();
+        log.execute_block(&mut runtime, block(2, vec![transfer(1, 20)]))
+            .unwrap();
+        assert!(matches!(
+            log.execute_block(&mut runtime, block(4, vec![])),
+            Err(Error::InvalidBlock(
+                "block number does not match what is expected"
+            ))
+        ));
+        assert_eq!(runtime.system.block_number(), 2);
+        let entries = super::read(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].block, block(1, vec![transfer(0, 30)]));
+        assert!(entries[2].outcome.is_err());
+
+        let mut replayed = genesis(100);
+        assert_eq!(super::replay(&path, &mut replayed).unwrap(), 3);
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", runtime));
+        assert_eq!(replayed.balances.balance(&String::from("bob")), 50);
+
+        let mut other_genesis = genesis(1_000);
+        match super::replay(&path, &mut other_genesis) {
+            Err(Error::Diverged { line, replayed, .. }) => {
+                assert_eq!(line, 1);
+                assert_eq!(replayed, Ok(other_genesis.state_root()));
+            }
+            result => panic!("unexpected replay result {:?}", result),
+        }
+
+        let log_file = std::fs::read_to_string(&path).unwrap();
+        let tampered = log_file.replacen(r#""amount":20"#, r#""amount":21"#, 1);
+        std::fs::write(&path, tampered).unwrap();
+        assert!(matches!(
+            super::replay(&path, &mut genesis(100)),
+            Err(Error::Diverged { line: 2, .. })
+        ));
+
+        std::fs::write(&path, format!("{}{{", log_file)).unwrap();
+        assert!(matches!(
+            super::replay(&path, &mut genesis(100)),
+            Err(Error::Corrupt { line: 4 })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use crate::storage::{StorageDoubleMap, StorageMap};
+use crate::support::{Get, Optional};
+
+/// The configuration trait for the Call Filter Module.
+pub trait Config: crate::system::Config {
+    /// The account allowed to disable and enable calls.
+    /// It stands in for the root origin of a real blockchain, like a governance body.
+    type Root: Get<Self::AccountId>;
+}
+
+/// The errors which can be returned by the calls of the Call Filter Module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The caller is not the root account.
+    NotRoot,
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotRoot => "caller is not the root account",
+        }
+    }
+}
+
+/// This is the Call Filter Module.
+/// It keeps track of the pallets and calls disabled by root, so the runtime can reject them in its
+/// `support::CallFilter`.
+#[macros::storage]
+#[derive(Debug, Clone)]
+pub struct Pallet<T: Config> {
+    /// The names of the pallets whose calls are all disabled.
+    disabled_pallets: StorageMap<String, ()>,
+    /// The names of the pallets and of their calls which are disabled.
+    disabled_calls: StorageDoubleMap<String, String, ()>,
+    /// The events emitted by this pallet, until the runtime takes them.
+    events: Vec<Event>,
+    _config: core::marker::PhantomData<T>,
+}
+
+/// The events emitted by the Call Filter Module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The calls of `pallet` were disabled, or only `call` if there is one.
+    Disabled {
+        pallet: String,
+        call: Option<String>,
+    },
+    /// The calls of `pallet` were enabled, or only `call` if there is one.
+    Enabled {
+        pallet: String,
+        call: Option<String>,
+    },
+}
+
+impl<T: Config> crate::support::Events for Pallet<T> {
+    type Event = Event;
+
+    fn take_events(&mut self) -> Vec<Event> {
+        core::mem::take(&mut self.events)
+    }
+}
+
+impl<T: Config> Pallet<T> {
+    /// Check if the call named `call` of the pallet named `pallet` is disabled, either on its own
+    /// or because the whole pallet is.
+    pub fn is_disabled(&self, pallet: &str, call: &str) -> bool {
+        let pallet = pallet.to_string();
+        self.disabled_pallets.contains_key(&pallet)
+            || self.disabled_calls.contains_key(&pallet, &call.to_string())
+    }
+
+    /// Ensure that `caller` is the root account.
+    fn ensure_root(caller: &T::AccountId) -> Result<(), Error> {
+        if *caller != T::Root::get() {
+            return Err(Error::NotRoot);
+        }
+        Ok(())
+    }
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+    /// Disable all the calls of `pallet`, or only `call` if there is one.
+    /// Only the root account can disable calls.
+    #[call_index(0)]
+    #[weight(5_000)]
+    pub fn disable(
+        &mut self,
+        caller: T::AccountId,
+        pallet: String,
+        call: Optional<String>,
+    ) -> crate::support::DispatchResult {
+        Self::ensure_root(&caller)?;
+        let call = call.0;
+        match &call {
+            Some(call) => self.disabled_calls.insert(pallet.clone(), call.clone(), ()),
+            None => self.disabled_pallets.insert(pallet.clone(), ()),
+        };
+        self.events.push(Event::Disabled { pallet, call });
+        Ok(())
+    }
+
+    /// Enable again all the calls of `pallet`, or only `call` if there is one.
+    /// A call stays disabled while its whole pallet is disabled.
+    /// Only the root account can enable calls.
+    #[call_index(1)]
+    #[weight(5_000)]
+    pub fn enable(
+        &mut self,
+        caller: T::AccountId,
+        pallet: String,
+        call: Optional<String>,
+    ) -> crate::support::DispatchResult {
+        Self::ensure_root(&caller)?;
+        let call = call.0;
+        match &call {
+            Some(call) => self.disabled_calls.remove(&pallet, call),
+            None => self.disabled_pallets.remove(&pallet),
+        };
+        self.events.push(Event::Enabled { pallet, call });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    struct TestConfig;
+
+    struct Root;
+
+    impl crate::support::Get<&'static str> for Root {
+        fn get() -> &'static str {
+            "root"
+        }
+    }
+
+    impl crate::system::Config for TestConfig {
+        type AccountId = &'static str;
+        type BlockNumber = u32;
+        type Nonce = u32;
+    }
+
+    impl super::Config for TestConfig {
+        type Root = Root;
+    }
+
+    /// This test checks the following:
+    /// - Only root can disable and enable calls.
+    /// - Disabling a pallet disables all of its calls, and a single call stays disabled while its
+    ///   pallet is.
+    /// - A call to disable a whole pallet is displayed with `call=none`.
+    #[test]
+    fn disable_and_enable() {
+        let mut call_filter = super::Pallet::<TestConfig>::new();
+        let transfer = || Some(String::from("transfer")).into();
+
+        assert_eq!(
+            call_filter.disable("alice", "balances".into(), None.into()),
+            Err("caller is not the root account")
+        );
+        assert!(!call_filter.is_disabled("balances", "transfer"));
+
+        assert_eq!(
+            call_filter.disable("root", "balances".into(), transfer()),
+            Ok(())
+        );
+        assert!(call_filter.is_disabled("balances", "transfer"));
+        assert!(!call_filter.is_disabled("balances", "mint"));
+
+        assert_eq!(
+            call_filter.disable("root", "balances".into(), None.into()),
+            Ok(())
+        );
+        assert_eq!(
+            call_filter.enable("root", "balances".into(), transfer()),
+            Ok(())
+        );
+        assert!(call_filter.is_disabled("balances", "transfer"));
+        assert!(call_filter.is_disabled("balances", "mint"));
+
+        assert_eq!(
+            call_filter.enable("alice", "balances".into(), None.into()),
+            Err("caller is not the root account")
+        );
+        assert_eq!(
+            call_filter.enable("root", "balances".into(), None.into()),
+            Ok(())
+        );
+        assert!(!call_filter.is_disabled("balances", "transfer"));
+
+        let events = crate::support::Events::take_events(&mut call_filter);
+        assert_eq!(events.len(), 4);
+
+        let disable = |call| super::Call::<TestConfig>::disable {
+            pallet: String::from("balances"),
+            call,
+        };
+        assert_eq!(
+            disable(transfer()).to_string(),
+            "disable(pallet=balances, call=transfer)"
+        );
+        assert_eq!(
+            disable(None.into()).to_string(),
+            "disable(pallet=balances, call=none)"
+        );
+    }
+}
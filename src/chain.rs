@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime, RuntimeEvent};
+use crate::support::{self, BlockReceipt};
+
+/// The errors which can be returned when importing a block into the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The parent of the block is not the best block of the chain.
+    InvalidParent,
+    /// The number of the block does not follow the number of its parent.
+    InvalidBlockNumber,
+    /// The extrinsics root of the header does not match the extrinsics of the block.
+    InvalidExtrinsicsRoot,
+    /// The state root of the header does not match the state after executing the block.
+    InvalidStateRoot,
+    /// The block is valid for the chain, but the runtime rejected it.
+    Execution(&'static str),
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidParent => "block does not build on the best block",
+            Error::InvalidBlockNumber => "block number does not follow its parent",
+            Error::InvalidExtrinsicsRoot => "extrinsics root does not match the extrinsics",
+            Error::InvalidStateRoot => "state root does not match the state after the block",
+            Error::Execution(error) => error,
+        }
+    }
+}
+
+/// A blockchain: the history of the blocks executed on a `Runtime`, linked by the hashes of their
+/// headers, starting from a genesis block.
+///
+/// Blocks are only imported on top of the best block, so the chain never forks.
+#[derive(Debug)]
+pub struct Chain {
+    /// The runtime, holding the state after the best block.
+    runtime: Runtime,
+    /// Every imported block, by the hash of its header.
+    blocks: HashMap<Hash, types::Block>,
+    /// The receipt of executing every imported block, by the hash of its header. The genesis
+    /// block has none.
+    receipts: HashMap<Hash, BlockReceipt<RuntimeEvent>>,
+    /// The hash of every imported block, by block number, from the genesis block to the best block.
+    hashes: Vec<Hash>,
+}
+
+impl Chain {
+    /// Create a new chain on top of the current state of `runtime`, usually built from genesis.
+    ///
+    /// The genesis block has no extrinsics, and the current block number and state root of
+    /// `runtime`.
+    pub fn new(runtime: Runtime) -> Self {
+        let genesis = types::Block {
+            header: types::Header {
+                parent_hash: Hash::default(),
+                block_number: runtime.system.block_number(),
+                state_root: runtime.state_root(),
+                extrinsics_root: support::extrinsics_root::<types::Extrinsic>(&[]),
+            },
+            extrinsics: vec![],
+        };
+        let hash = genesis.header.hash();
+
+        Self {
+            runtime,
+            blocks: HashMap::from([(hash, genesis)]),
+            receipts: HashMap::new(),
+            hashes: vec![hash],
+        }
+    }
+
+    /// The runtime, holding the state after the best block.
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// The hash of the best block, which is the last imported one.
+    pub fn best_hash(&self) -> Hash {
+        *self
+            .hashes
+            .last()
+            .expect("the chain always has a genesis block; qed")
+    }
+
+    /// The best block, which is the last imported one.
+    pub fn best_block(&self) -> &types::Block {
+        &self.blocks[&self.best_hash()]
+    }
+
+    /// The block with the given hash, if it was imported.
+    pub fn block_by_hash(&self, hash: &Hash) -> Option<&types::Block> {
+        self.blocks.get(hash)
+    }
+
+    /// The block with the given number, if it was imported.
+    pub fn block_by_number(&self, block_number: types::BlockNumber) -> Option<&types::Block> {
+        let genesis_number = self.blocks[&self.hashes[0]].header.block_number;
+        let index = block_number.checked_sub(genesis_number)?;
+        self.hashes
+            .get(index as usize)
+            .and_then(|hash| self.block_by_hash(hash))
+    }
+
+    /// The receipt of executing the block with the given hash, if it was imported.
+    pub fn receipt_by_hash(&self, hash: &Hash) -> Option<&BlockReceipt<RuntimeEvent>> {
+        self.receipts.get(hash)
+    }
+
+    /// Create a block with `extrinsics` on top of the best block, ready to be imported.
+    ///
+    /// The block is simulated on the state of the best block, to fill in its state root.
+    pub fn new_block(&self, extrinsics: Vec<types::Extrinsic>) -> types::Block {
+        let mut block = types::Block {
+            header: types::Header {
+                parent_hash: self.best_hash(),
+                block_number: self.best_block().header.block_number + 1,
+                state_root: Hash::default(),
+                extrinsics_root: support::extrinsics_root(&extrinsics),
+            },
+            extrinsics,
+        };
+        block.header.state_root = self
+            .runtime
+            .simulate_block(block.clone())
+            .expect("the block number follows the best block; qed")
+            .receipt
+            .state_root;
+        block
+    }
+
+    /// Import `block` on top of the best block: check that it is linked to the best block, execute
+    /// it on the runtime, check the state root of its header, and store it.
+    ///
+    /// Returns the receipt of executing the block. If an error is returned, the block is not
+    /// imported, and the state of the runtime is not changed.
+    pub fn import_block(
+        &mut self,
+        block: types::Block,
+    ) -> Result<&BlockReceipt<RuntimeEvent>, Error> {
+        Ok(self.prepare_import(block)?.commit())
+    }
+
+    /// Check and execute `block` like `import_block`, without storing it yet, so the caller can
+    /// persist it first. The block is imported when the returned `PendingImport` is committed,
+    /// and dropping it leaves the chain unchanged.
+    pub fn prepare_import(&mut self, block: types::Block) -> Result<PendingImport<'_>, Error> {
+        let best = &self.best_block().header;
+        if block.header.parent_hash != self.best_hash() {
+            return Err(Error::InvalidParent);
+        }
+        if block.header.block_number != best.block_number + 1 {
+            return Err(Error::InvalidBlockNumber);
+        }
+        if block.header.extrinsics_root != support::extrinsics_root(&block.extrinsics) {
+            return Err(Error::InvalidExtrinsicsRoot);
+        }
+
+        // The block is executed on a copy of the runtime, which replaces it only if the state
+        // after the block matches the header.
+        let mut runtime = self.runtime.clone();
+        let receipt = runtime
+            .execute_block(block.clone())
+            .map_err(Error::Execution)?;
+        if receipt.state_root != block.header.state_root {
+            return Err(Error::InvalidStateRoot);
+        }
+
+        Ok(PendingImport {
+            chain: self,
+            block,
+            runtime,
+            receipt,
+        })
+    }
+}
+
+/// A block checked and executed on top of the best block of a `Chain`, waiting to be imported.
+///
+/// It borrows the chain, so no other block can be imported in the meantime.
+#[derive(Debug)]
+pub struct PendingImport<'a> {
+    /// The chain the block is imported into.
+    chain: &'a mut Chain,
+    /// The block to import.
+    block: types::Block,
+    /// The runtime, holding the state after the block.
+    runtime: Runtime,
+    /// The receipt of executing the block.
+    receipt: BlockReceipt<RuntimeEvent>,
+}
+
+impl<'a> PendingImport<'a> {
+    /// The block to import.
+    pub fn block(&self) -> &types::Block {
+        &self.block
+    }
+
+    /// The receipt of executing the block.
+    pub fn receipt(&self) -> &BlockReceipt<RuntimeEvent> {
+        &self.receipt
+    }
+
+    /// Store the block on top of the best block of the chain, and return its receipt.
+    pub fn commit(self) -> &'a BlockReceipt<RuntimeEvent> {
+        let chain = self.chain;
+        let hash = self.block.header.hash();
+        chain.runtime = self.runtime;
+        chain.blocks.insert(hash, self.block);
+        chain.hashes.push(hash);
+        chain.receipts.entry(hash).or_insert(self.receipt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chain, Error};
+    use crate::crypto::Pair;
+    use crate::merkle::Hash;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::{accounts, balances};
+
+    fn new_chain() -> Chain {
+        Chain::new(Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(String::from("alice"), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice"]),
+        }))
+    }
+
+    fn transfer(nonce: u32, amount: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount,
+        };
+        types::Extrinsic::new(String::from("alice"), nonce, call).sign(&Pair::from_seed("alice"))
+    }
+
+    /// This test checks the following:
+    /// - Blocks built on the best block are imported, executed, and linked to their parent.
+    /// - A prepared import leaves the chain unchanged until it is committed.
+    /// - Blocks and receipts can be queried by hash and by number.
+    #[test]
+    fn import_blocks() {
+        let mut chain = new_chain();
+        let genesis_hash = chain.best_hash();
+        assert_eq!(chain.best_block().header.block_number, 0);
+        assert!(chain.receipt_by_hash(&genesis_hash).is_none());
+
+        let block_1 = chain.new_block(vec![transfer(0, 30)]);
+        let receipt = chain.import_block(block_1.clone()).unwrap();
+        assert_eq!(receipt.extrinsics.len(), 1);
+        assert_eq!(receipt.extrinsics[0].result, Ok(()));
+        let block_2 = chain.new_block(vec![transfer(1, 20), transfer(2, 100)]);
+        let import = chain.prepare_import(block_2.clone()).unwrap();
+        assert_eq!(import.receipt().state_root, block_2.header.state_root);
+        drop(import);
+        let hash_1 = block_1.header.hash();
+        assert_eq!(chain.best_hash(), hash_1);
+        assert_eq!(chain.runtime().system.block_number(), 1);
+        chain.prepare_import(block_2.clone()).unwrap().commit();
+
+        let hash_2 = block_2.header.hash();
+        assert_eq!(block_1.header.parent_hash, genesis_hash);
+        assert_eq!(block_2.header.parent_hash, hash_1);
+        assert_eq!(chain.best_hash(), hash_2);
+        assert_eq!(chain.best_block(), &block_2);
+        assert_eq!(chain.block_by_hash(&hash_1), Some(&block_1));
+        assert_eq!(chain.block_by_number(1), Some(&block_1));
+        assert_eq!(chain.block_by_number(2), Some(&block_2));
+        assert_eq!(chain.block_by_number(3), None);
+        assert_eq!(chain.block_by_hash(&Hash::default()), None);
+
+        let receipt = chain.receipt_by_hash(&hash_2).unwrap();
+        assert_eq!(receipt.extrinsics[0].result, Ok(()));
+        assert!(receipt.extrinsics[1].result.is_err());
+        assert_eq!(chain.runtime().system.block_number(), 2);
+        assert_eq!(block_2.header.state_root, chain.runtime().state_root());
+        assert_eq!(receipt.state_root, block_2.header.state_root);
+        assert_eq!(chain.runtime().balances.balance(&String::from("alice")), 50);
+    }
+
+    /// This test checks the following:
+    /// - Blocks with a wrong parent hash, block number, extrinsics root or state root are rejected.
+    /// - Rejected blocks are not imported, and do not change the state of the runtime.
+    #[test]
+    fn reject_invalid_blocks() {
+        let mut chain = new_chain();
+        let genesis_hash = chain.best_hash();
+        let block = chain.new_block(vec![transfer(0, 30)]);
+
+        let mut wrong_parent = block.clone();
+        wrong_parent.header.parent_hash = Hash::default();
+        assert_eq!(
+            chain.import_block(wrong_parent).err(),
+            Some(Error::InvalidParent)
+        );
+
+        let mut wrong_number = block.clone();
+        wrong_number.header.block_number = 2;
+        assert_eq!(
+            chain.import_block(wrong_number).err(),
+            Some(Error::InvalidBlockNumber)
+        );
+
+        let mut wrong_root = block.clone();
+        wrong_root.extrinsics.push(transfer(1, 10));
+        assert_eq!(
+            chain.import_block(wrong_root).err(),
+            Some(Error::InvalidExtrinsicsRoot)
+        );
+
+        let mut wrong_state = block.clone();
+        wrong_state.header.state_root = Hash::default();
+        assert_eq!(
+            chain.import_block(wrong_state).err(),
+            Some(Error::InvalidStateRoot)
+        );
+
+        assert_eq!(chain.best_hash(), genesis_hash);
+        assert_eq!(chain.runtime().system.block_number(), 0);
+        assert_eq!(
+            chain.runtime().balances.balance(&String::from("alice")),
+            100
+        );
+
+        chain.import_block(block).unwrap();
+        assert_eq!(chain.runtime().balances.balance(&String::from("alice")), 70);
+    }
+}
@@ -0,0 +1,165 @@
+use core::fmt;
+use core::str::FromStr;
+
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+
+use crate::merkle;
+
+/// The public key of an account, which verifies the signatures of its extrinsics.
+///
+/// It is encoded as a string of 64 hexadecimal digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PublicKey(pub [u8; 32]);
+
+/// An Ed25519 signature, made with the secret key of an account.
+///
+/// It is encoded as a string of 128 hexadecimal digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature(pub [u8; 64]);
+
+/// The key pair of an account, which signs its extrinsics.
+#[derive(Debug, Clone)]
+pub struct Pair(SigningKey);
+
+impl Pair {
+    /// The key pair whose secret key is the hash of `seed`.
+    ///
+    /// Anyone who knows the seed can sign on behalf of the account, so it must be kept as secret
+    /// as the key itself. Well-known seeds like `alice` are only fit for development chains.
+    pub fn from_seed(seed: &str) -> Self {
+        Pair(SigningKey::from_bytes(&merkle::hash(seed.as_bytes())))
+    }
+
+    /// The public key of the pair, which verifies its signatures.
+    pub fn public(&self) -> PublicKey {
+        PublicKey(self.0.verifying_key().to_bytes())
+    }
+
+    /// Sign `message` with the secret key of the pair.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(self.0.sign(message).to_bytes())
+    }
+}
+
+impl PublicKey {
+    /// Check that `signature` was made over `message` by the secret key of this public key.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let signature = ed25519_dalek::Signature::from_bytes(&signature.0);
+        VerifyingKey::from_bytes(&self.0)
+            .is_ok_and(|key| key.verify_strict(message, &signature).is_ok())
+    }
+}
+
+// A signature of zeros, which no key ever verifies. It is the signature of an extrinsic until it is
+// signed.
+impl Default for Signature {
+    fn default() -> Self {
+        Signature([0; 64])
+    }
+}
+
+/// Decode `N` bytes from `2 * N` hexadecimal digits.
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&merkle::to_hex(&self.0))
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = &'static str;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        from_hex(hex)
+            .map(PublicKey)
+            .ok_or("public key is not 32 bytes of hex")
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&merkle::to_hex(&self.0))
+    }
+}
+
+impl FromStr for Signature {
+    type Err = &'static str;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        from_hex(hex)
+            .map(Signature)
+            .ok_or("signature is not 64 bytes of hex")
+    }
+}
+
+impl serde::Serialize for PublicKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for Signature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Signature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Pair, PublicKey, Signature};
+
+    /// This test checks the following:
+    /// - A signature verifies against the public key of the pair which made it, and only over the
+    ///   signed message.
+    /// - Keys and signatures are encoded as hex, and decode back.
+    #[test]
+    fn sign_and_verify() {
+        let alice = Pair::from_seed("alice");
+        let bob = Pair::from_seed("bob");
+        let signature = alice.sign(b"message");
+
+        assert!(alice.public().verify(b"message", &signature));
+        assert!(!alice.public().verify(b"other message", &signature));
+        assert!(!bob.public().verify(b"message", &signature));
+        assert!(!alice.public().verify(b"message", &Signature::default()));
+
+        let encoded = serde_json::to_string(&alice.public()).unwrap();
+        assert_eq!(encoded.len(), 2 + 64);
+        assert_eq!(
+            serde_json::from_str::<PublicKey>(&encoded).unwrap(),
+            alice.public()
+        );
+        let encoded = serde_json::to_string(&signature).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Signature>(&encoded).unwrap(),
+            signature
+        );
+        assert!("zz".parse::<PublicKey>().is_err());
+    }
+}
@@ -0,0 +1,19 @@
+pub mod accounts;
+pub mod balances;
+pub mod block_builder;
+pub mod block_log;
+pub mod call_filter;
+pub mod chain;
+pub mod crypto;
+pub mod light_client;
+pub mod merkle;
+pub mod node;
+pub mod parallel_executor;
+pub mod proof_of_existence;
+pub mod rpc;
+pub mod runtime;
+pub mod sparse_merkle;
+pub mod storage;
+pub mod support;
+pub mod system;
+pub mod transaction_pool;
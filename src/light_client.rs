@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use crate::merkle::Hash;
+use crate::runtime::types;
+use crate::sparse_merkle::{self, StorageProof};
+use crate::storage;
+
+/// The errors which can be returned by the light client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The parent of the header is not the best header of the light client.
+    InvalidParent,
+    /// The number of the header does not follow the number of its parent.
+    InvalidBlockNumber,
+    /// The light client does not know the header with the given hash.
+    UnknownHeader,
+    /// The storage proof does not match the state root of the header.
+    InvalidProof,
+    /// The proven value can not be decoded to the expected type.
+    InvalidValue,
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidParent => "header does not build on the best header",
+            Error::InvalidBlockNumber => "header number does not follow its parent",
+            Error::UnknownHeader => "header is unknown",
+            Error::InvalidProof => "storage proof does not match the state root",
+            Error::InvalidValue => "proven value can not be decoded",
+        }
+    }
+}
+
+/// A light client, following the headers of a chain without executing its blocks.
+///
+/// It starts from a trusted header, usually the genesis, and only accepts headers linked to it by
+/// their parent hashes. The state of the chain at any of these headers can then be checked with
+/// storage proofs against its state root, provided by a full node.
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    /// Every known header, by hash.
+    headers: HashMap<Hash, types::Header>,
+    /// The hash of every known header, from the trusted header to the best header.
+    hashes: Vec<Hash>,
+}
+
+impl LightClient {
+    /// Create a light client trusting `header`.
+    pub fn new(header: types::Header) -> Self {
+        let hash = header.hash();
+        Self {
+            headers: HashMap::from([(hash, header)]),
+            hashes: vec![hash],
+        }
+    }
+
+    /// The best header, which is the last imported one.
+    pub fn best_header(&self) -> &types::Header {
+        let hash = self
+            .hashes
+            .last()
+            .expect("there is always a trusted header; qed");
+        &self.headers[hash]
+    }
+
+    /// The header with the given hash, if it was imported.
+    pub fn header_by_hash(&self, hash: &Hash) -> Option<&types::Header> {
+        self.headers.get(hash)
+    }
+
+    /// The header with the given number, if it was imported.
+    pub fn header_by_number(&self, block_number: types::BlockNumber) -> Option<&types::Header> {
+        let trusted_number = self.headers[&self.hashes[0]].block_number;
+        let index = block_number.checked_sub(trusted_number)?;
+        self.hashes
+            .get(index as usize)
+            .and_then(|hash| self.header_by_hash(hash))
+    }
+
+    /// Import `header` on top of the best header, after checking that it is linked to it.
+    ///
+    /// Returns the hash of the header.
+    pub fn import_header(&mut self, header: types::Header) -> Result<Hash, Error> {
+        let best = self.best_header();
+        if header.parent_hash != best.hash() {
+            return Err(Error::InvalidParent);
+        }
+        if header.block_number != best.block_number + 1 {
+            return Err(Error::InvalidBlockNumber);
+        }
+
+        let hash = header.hash();
+        self.headers.insert(hash, header);
+        self.hashes.push(hash);
+        Ok(hash)
+    }
+
+    /// Check that `key` has the encoded `value` in the state at the header with hash `at`, or no
+    /// value if `value` is `None`.
+    pub fn verify_storage(
+        &self,
+        at: &Hash,
+        key: &str,
+        value: Option<&str>,
+        proof: &StorageProof,
+    ) -> Result<(), Error> {
+        let header = self.header_by_hash(at).ok_or(Error::UnknownHeader)?;
+        if !sparse_merkle::verify(&header.state_root, key, value, proof) {
+            return Err(Error::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Check the balance of `who` in the state at the header with hash `at`, from the encoded
+    /// `value` and `proof` provided by a full node, and return it.
+    pub fn verify_balance(
+        &self,
+        at: &Hash,
+        who: &types::AccountId,
+        value: Option<&str>,
+        proof: &StorageProof,
+    ) -> Result<u128, Error> {
+        let key = storage::map_key("balances.balances", who);
+        self.verify_storage(at, &key, value, proof)?;
+        value
+            .map(|value| serde_json::from_str(value).map_err(|_| Error::InvalidValue))
+            .unwrap_or(Ok(0))
+    }
+
+    /// Check the claim on `claim` in the state at the header with hash `at`, from the encoded
+    /// `value` and `proof` provided by a full node, and return it.
+    pub fn verify_claim(
+        &self,
+        at: &Hash,
+        claim: &types::Content,
+        value: Option<&str>,
+        proof: &StorageProof,
+    ) -> Result<Option<types::ClaimInfo>, Error> {
+        let key = storage::map_key("proof_of_existence.claims", claim);
+        self.verify_storage(at, &key, value, proof)?;
+        value
+            .map(|value| serde_json::from_str(value).map_err(|_| Error::InvalidValue))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, LightClient};
+    use crate::chain::Chain;
+    use crate::crypto::Pair;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::storage::map_key;
+    use crate::{accounts, balances, proof_of_existence};
+
+    /// This test checks the following:
+    /// - The light client follows the headers of a chain, and rejects headers which are not linked
+    ///   to its best header.
+    /// - Balances and claims proven by the full chain are checked against the state root of the
+    ///   chosen header, and proofs for a different header or value are rejected.
+    #[test]
+    fn follow_chain() {
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        let mut chain = Chain::new(Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice"]),
+        }));
+        let mut light_client = LightClient::new(chain.best_block().header.clone());
+
+        let transfer = BalancesCall::transfer {
+            to: bob.clone(),
+            amount: 30,
+        };
+        let claim = proof_of_existence::Call::create_claim {
+            claim: String::from("document"),
+        };
+        let block_1 = chain.new_block(vec![
+            types::Extrinsic::new(alice.clone(), 0, transfer).sign(&Pair::from_seed("alice")),
+            types::Extrinsic::new(alice.clone(), 1, claim).sign(&Pair::from_seed("alice")),
+        ]);
+        chain.import_block(block_1.clone()).unwrap();
+
+        let mut orphan = block_1.header.clone();
+        orphan.parent_hash = orphan.hash();
+        assert_eq!(
+            light_client.import_header(orphan),
+            Err(Error::InvalidParent)
+        );
+        let mut wrong_number = block_1.header.clone();
+        wrong_number.block_number = 2;
+        assert_eq!(
+            light_client.import_header(wrong_number),
+            Err(Error::InvalidBlockNumber)
+        );
+        let hash_1 = light_client.import_header(block_1.header.clone()).unwrap();
+        assert_eq!(light_client.header_by_number(1), Some(&block_1.header));
+        let genesis_hash = chain.block_by_number(0).unwrap().header.hash();
+
+        let (balance, proof) = chain
+            .runtime()
+            .read_proof(&map_key("balances.balances", &bob));
+        assert_eq!(
+            light_client.verify_balance(&hash_1, &bob, balance.as_deref(), &proof),
+            Ok(30)
+        );
+        assert_eq!(
+            light_client.verify_balance(&hash_1, &bob, Some("1000"), &proof),
+            Err(Error::InvalidProof)
+        );
+        assert_eq!(
+            light_client.verify_balance(&hash_1, &alice, balance.as_deref(), &proof),
+            Err(Error::InvalidProof)
+        );
+        assert_eq!(
+            light_client.verify_balance(&genesis_hash, &bob, balance.as_deref(), &proof),
+            Err(Error::InvalidProof)
+        );
+        assert_eq!(
+            light_client.verify_balance(&[1; 32], &bob, balance.as_deref(), &proof),
+            Err(Error::UnknownHeader)
+        );
+
+        let (claim, proof) = chain
+            .runtime()
+            .read_proof(&map_key("proof_of_existence.claims", "document"));
+        assert_eq!(
+            light_client.verify_claim(&hash_1, &String::from("document"), claim.as_deref(), &proof),
+            Ok(Some(types::ClaimInfo {
+                owner: alice,
+                created_at: 1
+            }))
+        );
+        let (claim, proof) = chain
+            .runtime()
+            .read_proof(&map_key("proof_of_existence.claims", "missing"));
+        assert_eq!(
+            light_client.verify_claim(&hash_1, &String::from("missing"), claim.as_deref(), &proof),
+            Ok(None)
+        );
+    }
+}
@@ -1,111 +1,629 @@
-use support::Dispatch;
-
-mod balances;
-mod proof_of_existence;
-mod support;
-mod system;
-
-mod types {
-    pub type AccountId = String;
-    pub type BlockNumber = u32;
-    pub type Extrinsic = crate::support::Extrinsic<AccountId, crate::RuntimeCall>;
-    pub type Header = crate::support::Header<BlockNumber>;
-    pub type Block = crate::support::Block<Header, Extrinsic>;
-    pub type Content = &'static str;
-}
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rust_state_machine::chain::Chain;
+use rust_state_machine::crypto::Pair;
+use rust_state_machine::node::Node;
+use rust_state_machine::rpc::RpcServer;
+use rust_state_machine::runtime::{
+    types, BalancesCall, Runtime, RuntimeCall, RuntimeEvent, RuntimeGenesisConfig,
+};
+use rust_state_machine::transaction_pool::TransactionPool;
+use rust_state_machine::{
+    accounts, balances, merkle, proof_of_existence, sparse_merkle, storage, support,
+};
+
+const USAGE: &str = "usage: rust-state-machine [--dir <state dir>] <command>
 
-// This is our main Runtime.
-// It accumulates all of the different pallets we want to use.
-#[derive(Debug)]
-#[macros::runtime]
-pub struct Runtime {
-    system: system::Pallet<Self>,
-    balances: balances::Pallet<Self>,
-    proof_of_existence: proof_of_existence::Pallet<Self>,
+commands:
+  init <genesis file>      start a chain from a JSON genesis config
+  key <seed>               print the public key of the account signing with a secret seed
+  submit <pallet> <call> --from <account> --seed <seed> [--nonce <nonce>] [--tip <tip>]
+         [--<arg> <value>]...
+                           submit an extrinsic signed with the key of a secret seed, like
+                           `submit balances transfer --from alice --seed alice --to bob --amount 30`
+  produce-block            import a block with the ready extrinsics of the pool
+  query balance <account>  print the balance of an account
+  query claim <content>    print the claim on some content
+  query nonce <account>    print the nonce of an account
+  export-state             print the state of the best block as JSON
+  serve [--address <address>] [--block-time <seconds>]
+                           serve JSON-RPC on a local address, producing a block every
+                           `--block-time` seconds
+  demo                     run an example scenario on a chain in memory";
+
+/// The state directory used when `--dir` is not given.
+const DEFAULT_DIR: &str = "chain-data";
+/// The address `serve` listens on when `--address` is not given.
+const DEFAULT_ADDRESS: &str = "127.0.0.1:9933";
+/// The seconds between two blocks produced by `serve` when `--block-time` is not given.
+const DEFAULT_BLOCK_TIME: u64 = 6;
+
+fn main() {
+    let args = std::env::args().skip(1).collect();
+    if let Err(error) = run(args) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
 }
 
-impl system::Config for Runtime {
-    type AccountId = String;
-    type BlockNumber = u32;
-    type Nonce = u32;
+/// Run the command given by `args`, on the chain kept in the state directory.
+fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let (command, mut options) = parse_args(args)?;
+    let dir = options
+        .remove("dir")
+        .unwrap_or_else(|| String::from(DEFAULT_DIR));
+    let command = command.iter().map(String::as_str).collect::<Vec<_>>();
+
+    match command.as_slice() {
+        ["init", genesis] => {
+            let genesis = std::fs::read_to_string(genesis)?;
+            let node = Node::init(&dir, &genesis).map_err(<&str>::from)?;
+            println!("genesis {}", merkle::to_hex(&node.chain().best_hash()));
+        }
+        ["key", seed] => println!("{}", Pair::from_seed(seed).public()),
+        ["submit", pallet, call] => {
+            let mut node = Node::open(&dir).map_err(<&str>::from)?;
+            let from = options.remove("from").ok_or("missing --from <account>")?;
+            let seed = options.remove("seed").ok_or("missing --seed <seed>")?;
+            let nonce = match options.remove("nonce") {
+                Some(nonce) => nonce.parse()?,
+                None => node.next_nonce(&from),
+            };
+            let tip = match options.remove("tip") {
+                Some(tip) => tip.parse()?,
+                None => 0,
+            };
+            let call = parse_call(pallet, call, options)?;
+            let extrinsic = types::Extrinsic::new(from, nonce, call)
+                .with_tip(tip)
+                .sign(&Pair::from_seed(&seed));
+            let hash = node.submit(extrinsic).map_err(<&str>::from)?;
+            println!("submitted {} with nonce {}", merkle::to_hex(&hash), nonce);
+        }
+        ["produce-block"] => {
+            let mut node = Node::open(&dir).map_err(<&str>::from)?;
+            let (block, receipt) = node.produce_block().map_err(<&str>::from)?;
+            let block_number = block.header.block_number;
+            println!(
+                "block {}: {}",
+                block_number,
+                merkle::to_hex(&block.header.hash())
+            );
+            print_receipt(block_number, receipt);
+        }
+        ["query", "balance", account] => {
+            let node = Node::open(&dir).map_err(<&str>::from)?;
+            let balance = node
+                .chain()
+                .runtime()
+                .balances
+                .balance(&account.to_string());
+            println!("{}", balance);
+        }
+        ["query", "claim", content] => {
+            let node = Node::open(&dir).map_err(<&str>::from)?;
+            let runtime = node.chain().runtime();
+            match runtime
+                .proof_of_existence
+                .get_claim_info(&content.to_string())
+            {
+                Some(claim) => {
+                    println!("owned by {} since block {}", claim.owner, claim.created_at)
+                }
+                None => println!("no claim"),
+            }
+        }
+        ["query", "nonce", account] => {
+            let node = Node::open(&dir).map_err(<&str>::from)?;
+            println!(
+                "{}",
+                node.chain().runtime().system.nonce(&account.to_string())
+            );
+        }
+        ["export-state"] => {
+            let node = Node::open(&dir).map_err(<&str>::from)?;
+            let state = node.chain().runtime().state();
+            println!("{}", serde_json::to_string_pretty(&state)?);
+        }
+        ["serve"] => {
+            let node = Node::open(&dir).map_err(<&str>::from)?;
+            let node = Arc::new(Mutex::new(node));
+            let address = options
+                .remove("address")
+                .unwrap_or_else(|| String::from(DEFAULT_ADDRESS));
+            let block_time = match options.remove("block-time") {
+                Some(block_time) => block_time.parse()?,
+                None => DEFAULT_BLOCK_TIME,
+            };
+            let server = RpcServer::bind(address, node.clone())?;
+            println!("listening on {}", server.local_addr()?);
+            std::thread::spawn(move || server.run());
+            loop {
+                std::thread::sleep(Duration::from_secs(block_time));
+                let mut node = node
+                    .lock()
+                    .expect("no thread panics while holding the node");
+                let (block, receipt) = node.produce_block().map_err(<&str>::from)?;
+                print_receipt(block.header.block_number, receipt);
+            }
+        }
+        ["demo"] => demo(),
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
 }
 
-impl balances::Config for Runtime {
-    type Balance = u128;
+/// Split `args` into the words of the command and its `--name value` options.
+fn parse_args(args: Vec<String>) -> Result<(Vec<String>, BTreeMap<String, String>), String> {
+    let mut command = Vec::new();
+    let mut options = BTreeMap::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for --{}", name))?;
+                options.insert(name.to_string(), value);
+            }
+            None => command.push(arg),
+        }
+    }
+    Ok((command, options))
 }
 
-impl proof_of_existence::Config for Runtime {
-    type Content = types::Content;
+/// The most arguments of a call whose values are tried both decoded and as strings, which takes a
+/// number of tries exponential in them.
+const MAX_AMBIGUOUS_ARGS: usize = 8;
+
+/// Build the call named `call` of `pallet` from its arguments, given as `--name value` options.
+///
+/// Values are decoded as JSON, or taken as strings when they are not valid JSON, so
+/// `--to bob --amount 30` gives the arguments `{"to": "bob", "amount": 30}`. A value which is
+/// valid JSON but not a string, like the account in `--to 42` or `--to null`, is also tried as a
+/// string if the call does not accept it. Arguments the call does not take, according to the
+/// metadata of the runtime, are rejected.
+fn parse_call(
+    pallet: &str,
+    call: &str,
+    args: BTreeMap<String, String>,
+) -> Result<RuntimeCall, String> {
+    let metadata = Runtime::metadata()
+        .pallets
+        .into_iter()
+        .find(|metadata| metadata.name == pallet)
+        .and_then(|metadata| {
+            metadata
+                .calls
+                .into_iter()
+                .find(|metadata| metadata.name == call)
+        })
+        .ok_or_else(|| format!("unknown call {}.{}", pallet, call))?;
+    if let Some(name) = args
+        .keys()
+        .find(|name| !metadata.args.iter().any(|arg| arg.name == name.as_str()))
+    {
+        return Err(format!(
+            "unknown argument --{} of {}.{}",
+            name, pallet, call
+        ));
+    }
+
+    // The values each argument may stand for, the decoded one first.
+    let candidates = args
+        .into_iter()
+        .map(|(name, value)| {
+            let values = match serde_json::from_str(&value) {
+                Ok(serde_json::Value::String(decoded)) => vec![serde_json::Value::String(decoded)],
+                Ok(decoded) => vec![decoded, serde_json::Value::String(value)],
+                Err(_) => vec![serde_json::Value::String(value)],
+            };
+            (name, values)
+        })
+        .collect::<Vec<_>>();
+    let ambiguous = candidates
+        .iter()
+        .filter(|(_, values)| values.len() > 1)
+        .count();
+    if ambiguous > MAX_AMBIGUOUS_ARGS {
+        return Err(format!(
+            "too many arguments of {}.{} may be strings, quote the strings as JSON",
+            pallet, call
+        ));
+    }
+
+    // Each bit of `choice` takes an ambiguous value as a string, starting with none of them.
+    let mut first_error = None;
+    for choice in 0..1usize << ambiguous {
+        let mut bit = 0;
+        let args = candidates
+            .iter()
+            .map(|(name, values)| {
+                let mut index = 0;
+                if values.len() > 1 {
+                    index = choice >> bit & 1;
+                    bit += 1;
+                }
+                (name.clone(), values[index].clone())
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let encoded = serde_json::json!({ pallet: { call: args } });
+        match serde_json::from_value(encoded) {
+            Ok(decoded) => return Ok(decoded),
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    let error = first_error.expect("at least one choice of values is tried; qed");
+    Err(format!("invalid call {}.{}: {}", pallet, call, error))
 }
 
-fn main() {
-    // Create a new instance of the Runtime.
-    // It will instantiate with it all the modules it uses.
-    let mut runtime = Runtime::new();
+/// Run an example scenario on a chain kept in memory, printing what happens.
+fn demo() {
     let alice = String::from("alice");
     let bob = String::from("bob");
     let charlie = String::from("charlie");
 
-    runtime.balances.set_balance(&alice, 100);
-
-    let block_1 = types::Block {
-        header: support::Header {
-            block_number: runtime.system.block_number() + 1,
+    // Create a new instance of the Runtime.
+    // It will instantiate with it all the modules it uses, give `alice` an initial balance, and
+    // `bob` some reward points. The three accounts sign with the development keys of their names.
+    let runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+        balances: balances::GenesisConfig {
+            balances: vec![(alice.clone(), 100)],
+        },
+        reward_points: balances::GenesisConfig {
+            balances: vec![(bob.clone(), 10)],
         },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer {
-                    to: bob.clone(),
-                    amount: 30,
-                }),
+        accounts: accounts::GenesisConfig::dev(&["alice", "bob", "charlie"]),
+    });
+
+    // Start a chain from the genesis state, which keeps the history of the blocks we import.
+    let mut chain = Chain::new(runtime);
+    // Extrinsics are submitted to the pool, which provides the ones ready for each new block.
+    let mut pool = TransactionPool::new(100);
+
+    let extrinsics = vec![
+        types::Extrinsic::new(
+            alice.clone(),
+            0,
+            BalancesCall::transfer {
+                to: bob.clone(),
+                amount: 30,
             },
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer {
-                    to: charlie.clone(),
-                    amount: 20,
-                }),
+        ),
+        types::Extrinsic::new(
+            alice.clone(),
+            1,
+            BalancesCall::transfer {
+                to: charlie.clone(),
+                amount: 20,
             },
-        ],
-    };
+        ),
+    ];
+    // Before submitting her first transfer, Alice checks what it would do.
+    let dry_run = chain.runtime().dry_run(sign(extrinsics[0].clone()));
+    println!("dry run: {:?}, weight {}", dry_run.result, dry_run.weight);
+    for (key, change) in &dry_run.state_diff {
+        println!("  {}: {:?} -> {:?}", key, change.before, change.after);
+    }
 
-    runtime.execute_block(block_1).expect("invalid block");
+    submit(&chain, &mut pool, extrinsics);
+    let receipt = produce_block(&mut chain, &mut pool);
+    print_receipt(1, receipt);
 
-    let block_2 = types::Block {
-        header: support::Header {
-            block_number: runtime.system.block_number() + 1,
-        },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
-                    claim: "claim content",
-                }),
+    let extrinsics = vec![
+        types::Extrinsic::new(
+            alice.clone(),
+            2,
+            proof_of_existence::Call::create_claim {
+                claim: String::from("claim content"),
+            },
+        ),
+        types::Extrinsic::new(
+            bob.clone(),
+            0,
+            proof_of_existence::Call::revoke_claim {
+                claim: String::from("claim content"),
+            },
+        ),
+        types::Extrinsic::new(
+            alice.clone(),
+            3,
+            proof_of_existence::Call::revoke_claim {
+                claim: String::from("claim content"),
+            },
+        ),
+        // Charlie tips to get his claim included first.
+        types::Extrinsic::new(
+            charlie.clone(),
+            0,
+            proof_of_existence::Call::create_claim {
+                claim: String::from("charlie claim content"),
+            },
+        )
+        .with_tip(5),
+    ];
+    submit(&chain, &mut pool, extrinsics);
+    let receipt = produce_block(&mut chain, &mut pool);
+    print_receipt(2, receipt);
+
+    // Bob claims a whole batch of documents at once, using the Merkle root of their hashes.
+    let documents = ["contract", "invoice", "receipt"].map(|doc| merkle::hash(doc.as_bytes()));
+    let batch = merkle::MerkleTree::new(&documents).expect("batch is not empty");
+
+    let extrinsics = vec![
+        types::Extrinsic::new(
+            bob.clone(),
+            1,
+            proof_of_existence::Call::create_batch_claim {
+                root: batch.root().into(),
             },
-            support::Extrinsic {
-                caller: bob.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
-                    claim: "claim content",
-                }),
+        ),
+        // Alice, Bob and Charlie jointly own a claim, any two of them can revoke it.
+        types::Extrinsic::new(
+            alice.clone(),
+            4,
+            proof_of_existence::Call::create_joint_claim {
+                claim: String::from("joint claim content"),
+                co_owners: vec![bob.clone(), charlie.clone()].into(),
+                threshold: 2,
             },
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
-                    claim: "claim content",
-                }),
+        ),
+        // Charlie's approval waits in the pool until the joint claim was created.
+        types::Extrinsic::new(
+            charlie.clone(),
+            2,
+            proof_of_existence::Call::approve_joint_action {
+                claim: String::from("joint claim content"),
+                action: proof_of_existence::JointAction::Revoke,
             },
-            support::Extrinsic {
-                caller: charlie.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
-                    claim: "charlie claim content",
-                }),
+        ),
+    ];
+    submit(&chain, &mut pool, extrinsics);
+    let receipt = produce_block(&mut chain, &mut pool);
+    print_receipt(3, receipt);
+    println!("pool after block 3: {:?}", pool.status(chain.runtime()));
+
+    let extrinsics = vec![
+        types::Extrinsic::new(
+            alice.clone(),
+            5,
+            proof_of_existence::Call::approve_joint_action {
+                claim: String::from("joint claim content"),
+                action: proof_of_existence::JointAction::Revoke,
+            },
+        ),
+        types::Extrinsic::new(
+            charlie.clone(),
+            1,
+            BalancesCall::transfer {
+                to: bob.clone(),
+                amount: 5,
             },
-        ],
-    };
+        ),
+    ];
+    submit(&chain, &mut pool, extrinsics);
+    let receipt = produce_block(&mut chain, &mut pool);
+    print_receipt(4, receipt);
+    println!("block 4 weight: {}", receipt.weight);
+    assert!(chain
+        .runtime()
+        .proof_of_existence
+        .get_joint_claim(&String::from("joint claim content"))
+        .is_none());
+
+    // Any single document of the batch can now be proven to be owned by `bob`.
+    let invoice_proof = batch.proof(1).expect("invoice is in the batch");
+    assert!(merkle::verify(&batch.root(), &documents[1], &invoice_proof));
+    println!(
+        "invoice owner: {:?}",
+        chain
+            .runtime()
+            .proof_of_existence
+            .verify_inclusion(&documents[1], &invoice_proof)
+    );
+
+    // Print the claims owned by `charlie`, using the owner index of the Proof of Existence pallet.
+    println!(
+        "charlie claims: {:?}",
+        chain
+            .runtime()
+            .proof_of_existence
+            .claims_of(&charlie, 0, 10)
+    );
+
+    // Print the description of the runtime, so clients can discover its pallets and calls.
+    println!("{}", Runtime::metadata().to_json());
+
+    // Print the history of the chain, from genesis to the best block.
+    for block_number in 0..=chain.best_block().header.block_number {
+        let header = &chain
+            .block_by_number(block_number)
+            .expect("imported")
+            .header;
+        println!(
+            "block {}: {} (state root {})",
+            block_number,
+            merkle::to_hex(&header.hash()),
+            merkle::to_hex(&header.state_root)
+        );
+    }
 
-    runtime.execute_block(block_2).expect("invalid block");
+    // Prove the balance of `bob` against the state root of the best block.
+    let key = storage::map_key("balances.balances", &bob);
+    let (balance, proof) = chain.runtime().read_proof(&key);
+    let state_root = chain.best_block().header.state_root;
+    println!(
+        "bob balance: {:?}, proven: {}",
+        balance,
+        sparse_merkle::verify(&state_root, &key, balance.as_deref(), &proof)
+    );
 
     // Print the debug format of runtime state
-    print!("{:#?}", runtime)
+    print!("{:#?}", chain.runtime())
+}
+
+/// Sign `extrinsic` with the development key of its caller, whose name is its seed.
+fn sign(extrinsic: types::Extrinsic) -> types::Extrinsic {
+    let pair = Pair::from_seed(&extrinsic.caller);
+    extrinsic.sign(&pair)
+}
+
+/// Sign `extrinsics` and submit them to the pool, validated against the state of the best block of
+/// `chain`.
+fn submit(chain: &Chain, pool: &mut TransactionPool, extrinsics: Vec<types::Extrinsic>) {
+    for extrinsic in extrinsics {
+        pool.submit(chain.runtime(), sign(extrinsic))
+            .expect("invalid extrinsic");
+    }
+}
+
+/// Import a block with the ready extrinsics of the pool on top of the best block of `chain`, and
+/// remove them from the pool.
+fn produce_block<'a>(
+    chain: &'a mut Chain,
+    pool: &mut TransactionPool,
+) -> &'a support::BlockReceipt<RuntimeEvent> {
+    let block = chain.new_block(pool.ready(chain.runtime()));
+    let hash = block.header.hash();
+    chain.import_block(block).expect("invalid block");
+    pool.prune(chain.runtime());
+    chain.receipt_by_hash(&hash).expect("block was imported")
+}
+
+/// Print the outcome of each extrinsic of a block, and the events it emitted.
+fn print_receipt(block_number: types::BlockNumber, receipt: &support::BlockReceipt<RuntimeEvent>) {
+    for extrinsic in &receipt.extrinsics {
+        match &extrinsic.result {
+            Ok(()) => println!(
+                "block {} extrinsic {}: {:?}",
+                block_number, extrinsic.index, extrinsic.events
+            ),
+            Err(e) => println!(
+                "block {} extrinsic {} failed: {}",
+                block_number, extrinsic.index, e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use rust_state_machine::runtime::{BalancesCall, RuntimeCall};
+    use rust_state_machine::{call_filter, proof_of_existence};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn options(options: &[(&str, &str)]) -> BTreeMap<String, String> {
+        options
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// This test checks the following:
+    /// - The words of the command are kept in order, and `--name value` options are collected by
+    ///   name, wherever they appear.
+    /// - An option without a value is rejected.
+    #[test]
+    fn parse_args() {
+        let (command, parsed) = super::parse_args(args(&[
+            "submit", "balances", "--from", "alice", "transfer", "--to", "bob",
+        ]))
+        .unwrap();
+        assert_eq!(command, args(&["submit", "balances", "transfer"]));
+        assert_eq!(parsed, options(&[("from", "alice"), ("to", "bob")]));
+
+        assert_eq!(
+            super::parse_args(args(&["submit", "--seed"])),
+            Err(String::from("missing value for --seed"))
+        );
+    }
+
+    /// This test checks the following:
+    /// - Values are decoded as JSON, or taken as strings when they are not valid JSON.
+    /// - Values which are valid JSON of another type than the argument, like numbers, booleans or
+    ///   `null` for accounts and claims, are taken as strings, while the other arguments of the
+    ///   call keep their decoded values.
+    /// - Unknown calls, unknown arguments and values which fit neither way are rejected.
+    #[test]
+    fn parse_call() {
+        let transfer = |to: &str| {
+            super::parse_call(
+                "balances",
+                "transfer",
+                options(&[("to", to), ("amount", "30")]),
+            )
+        };
+        let expected = |to: &str| {
+            Ok(RuntimeCall::from(BalancesCall::transfer {
+                to: to.to_string(),
+                amount: 30,
+            }))
+        };
+        assert_eq!(transfer("bob"), expected("bob"));
+        assert_eq!(transfer(r#""bob""#), expected("bob"));
+        assert_eq!(transfer("42"), expected("42"));
+        assert_eq!(transfer("true"), expected("true"));
+        assert_eq!(transfer("null"), expected("null"));
+
+        let joint_claim = super::parse_call(
+            "proof_of_existence",
+            "create_joint_claim",
+            options(&[
+                ("claim", "42"),
+                ("co_owners", r#"["bob", "charlie"]"#),
+                ("threshold", "2"),
+            ]),
+        );
+        assert_eq!(
+            joint_claim,
+            Ok(RuntimeCall::from(
+                proof_of_existence::Call::create_joint_claim {
+                    claim: String::from("42"),
+                    co_owners: vec![String::from("bob"), String::from("charlie")].into(),
+                    threshold: 2,
+                }
+            ))
+        );
+        let disable = super::parse_call(
+            "call_filter",
+            "disable",
+            options(&[("pallet", "balances"), ("call", "null")]),
+        );
+        assert_eq!(
+            disable,
+            Ok(RuntimeCall::from(call_filter::Call::disable {
+                pallet: String::from("balances"),
+                call: None.into(),
+            }))
+        );
+
+        assert_eq!(
+            super::parse_call("balances", "burn", options(&[])),
+            Err(String::from("unknown call balances.burn"))
+        );
+        assert_eq!(
+            super::parse_call(
+                "balances",
+                "transfer",
+                options(&[("to", "bob"), ("amount", "30"), ("memo", "42")])
+            ),
+            Err(String::from("unknown argument --memo of balances.transfer"))
+        );
+        assert!(super::parse_call(
+            "balances",
+            "transfer",
+            options(&[("to", "bob"), ("amount", "thirty")])
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use sha2::{Digest, Sha256};
+
+/// The hash type used for the leaves and nodes of a Merkle tree.
+pub type Hash = [u8; 32];
+
+/// Domain separation prefixes, so that a leaf can never be confused with an inner node.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hash some arbitrary bytes, for example the contents of a document.
+pub fn hash(data: &[u8]) -> Hash {
+    Sha256::digest(data).into()
+}
+
+/// Render a hash, or any other bytes, as lowercase hexadecimal, the way hashes are usually shown to
+/// users.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parse a hash rendered by `to_hex`, or return `None` if `hex` is not 64 hexadecimal digits.
+pub fn from_hex(hex: &str) -> Option<Hash> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = Hash::default();
+    for (byte, digits) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// A hash passed as the argument of a call. Calls display their arguments, which a `Hash` can not
+/// do, so it is displayed in hexadecimal instead. It is encoded like the `Hash` it wraps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct HexHash(pub Hash);
+
+impl From<Hash> for HexHash {
+    fn from(hash: Hash) -> Self {
+        HexHash(hash)
+    }
+}
+
+impl core::fmt::Display for HexHash {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+fn hash_leaf(leaf: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// A single step of an inclusion proof: the sibling hash, and which side of the path it is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofStep {
+    /// The sibling is on the left, so the current hash is the right child.
+    Left(Hash),
+    /// The sibling is on the right, so the current hash is the left child.
+    Right(Hash),
+}
+
+/// An inclusion proof for a single leaf, ordered from the leaf up to the root.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Proof {
+    pub steps: Vec<ProofStep>,
+}
+
+/// A binary Merkle tree built over a list of leaves (usually document hashes).
+///
+/// When a layer has an odd number of nodes, the last node is promoted to the next layer as is,
+/// rather than being paired with a copy of itself.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    /// All the layers of the tree, starting from the hashed leaves and ending with the root.
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build a new tree over `leaves`. Returns `None` if there are no leaves.
+    pub fn new(leaves: &[Hash]) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut layers = vec![leaves.iter().map(hash_leaf).collect::<Vec<_>>()];
+        while layers.last().is_some_and(|layer| layer.len() > 1) {
+            let next = layers
+                .last()
+                .expect("checked above; qed")
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!("chunks are never empty or larger than 2"),
+                })
+                .collect();
+            layers.push(next);
+        }
+
+        Some(Self { layers })
+    }
+
+    /// The root of the tree, which commits to all of the leaves.
+    pub fn root(&self) -> Hash {
+        self.layers.last().expect("a tree always has a root layer")[0]
+    }
+
+    /// Generate the inclusion proof for the leaf at `index`, or `None` if out of bounds.
+    pub fn proof(&self, mut index: usize) -> Option<Proof> {
+        if index >= self.layers[0].len() {
+            return None;
+        }
+
+        let mut proof = Proof::default();
+        // Every layer except the root contributes at most one sibling to the proof.
+        for layer in &self.layers[..self.layers.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = layer.get(sibling) {
+                let step = if sibling < index {
+                    ProofStep::Left(*hash)
+                } else {
+                    ProofStep::Right(*hash)
+                };
+                proof.steps.push(step);
+            }
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
+
+/// Compute the root implied by a `leaf` and its inclusion `proof`.
+pub fn compute_root(leaf: &Hash, proof: &Proof) -> Hash {
+    proof
+        .steps
+        .iter()
+        .fold(hash_leaf(leaf), |current, step| match step {
+            ProofStep::Left(sibling) => hash_node(sibling, &current),
+            ProofStep::Right(sibling) => hash_node(&current, sibling),
+        })
+}
+
+/// Check that `leaf` is included in the tree with the given `root`.
+pub fn verify(root: &Hash, leaf: &Hash, proof: &Proof) -> bool {
+    compute_root(leaf, proof) == *root
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Checks the following:
+    /// - A tree cannot be built without leaves.
+    /// - Every leaf of trees of various sizes can be proven against the root.
+    /// - A proof does not verify for a different leaf or a different root.
+    #[test]
+    fn merkle_proofs() {
+        assert!(MerkleTree::new(&[]).is_none());
+
+        for size in 1..=9 {
+            let leaves = (0..size)
+                .map(|i| hash(format!("document {}", i).as_bytes()))
+                .collect::<Vec<_>>();
+            let tree = MerkleTree::new(&leaves).unwrap();
+
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify(&tree.root(), leaf, &proof));
+                assert!(!verify(&tree.root(), &hash(b"not a document"), &proof));
+                assert!(!verify(&hash(b"not a root"), leaf, &proof));
+            }
+
+            assert_eq!(tree.proof(size), None);
+        }
+    }
+
+    /// Checks that a single leaf tree has an empty proof, and its root is the hashed leaf.
+    #[test]
+    fn single_leaf() {
+        let leaf = hash(b"only document");
+        let tree = MerkleTree::new(&[leaf]).unwrap();
+
+        assert_eq!(tree.proof(0), Some(Proof::default()));
+        assert_eq!(tree.root(), compute_root(&leaf, &Proof::default()));
+    }
+
+    /// Checks that a hash rendered as hex parses back, and that malformed hex is rejected.
+    #[test]
+    fn hex_round_trip() {
+        let hash = hash(b"document");
+
+        assert_eq!(from_hex(&to_hex(&hash)), Some(hash));
+        assert_eq!(from_hex(&to_hex(&hash)[1..]), None);
+        assert_eq!(from_hex(&"zz".repeat(32)), None);
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::block_log::{self, BlockLog, Entry};
+use crate::chain::{self, Chain};
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime, RuntimeEvent, RuntimeGenesisConfig};
+use crate::support::BlockReceipt;
+use crate::transaction_pool::{self, TransactionPool};
+
+/// The file of the state directory holding the genesis config of the chain, as JSON.
+pub const GENESIS_FILE: &str = "genesis.json";
+/// The file of the state directory holding the log of the imported blocks.
+pub const BLOCKS_FILE: &str = "blocks.jsonl";
+/// The file of the state directory holding the extrinsics of the pool, as a JSON array.
+pub const POOL_FILE: &str = "pool.json";
+
+/// The maximum number of extrinsics in the pool of a node.
+const POOL_SIZE: usize = 1_000;
+
+/// The errors which can be returned when opening or updating the state directory of a node.
+#[derive(Debug)]
+pub enum Error {
+    /// A file of the state directory can not be read or written.
+    Io(std::io::Error),
+    /// The state directory already holds a chain.
+    AlreadyInitialized,
+    /// The state directory does not hold a chain.
+    NotInitialized,
+    /// The genesis config can not be decoded.
+    InvalidGenesis,
+    /// The extrinsics of the pool can not be decoded.
+    CorruptPool,
+    /// The block log can not be read or written, or does not match the chain.
+    BlockLog(block_log::Error),
+    /// The block at `line` of the block log can not be imported into the chain.
+    InvalidBlock { line: usize, error: chain::Error },
+    /// The extrinsic is rejected by the pool.
+    Pool(transaction_pool::Error),
+    /// The produced block can not be imported into the chain.
+    Import(chain::Error),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<block_log::Error> for Error {
+    fn from(error: block_log::Error) -> Self {
+        Error::BlockLog(error)
+    }
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(_) => "state directory can not be read or written",
+            Error::AlreadyInitialized => "state directory already holds a chain",
+            Error::NotInitialized => "state directory does not hold a chain",
+            Error::InvalidGenesis => "genesis config can not be decoded",
+            Error::CorruptPool => "transaction pool can not be decoded",
+            Error::BlockLog(error) => error.into(),
+            Error::InvalidBlock { error, .. } => error.into(),
+            Error::Pool(error) => error.into(),
+            Error::Import(error) => error.into(),
+        }
+    }
+}
+
+/// A node keeping a `Chain` and its `TransactionPool` in a state directory, so they outlive the
+/// process.
+///
+/// The directory holds the genesis config, the log of the imported blocks, and the extrinsics of
+/// the pool. Opening it rebuilds the chain from genesis by importing the logged blocks again, and
+/// submits the extrinsics to a new pool.
+#[derive(Debug)]
+pub struct Node {
+    /// The state directory.
+    dir: PathBuf,
+    /// The chain, holding the state after the best block.
+    chain: Chain,
+    /// The extrinsics waiting to be included in a block.
+    pool: TransactionPool,
+    /// The log every imported block is appended to.
+    log: BlockLog,
+}
+
+impl Node {
+    /// Start a new chain in the state directory `dir` from `genesis`, the JSON encoding of a
+    /// `RuntimeGenesisConfig`. The directory is created if it does not exist.
+    pub fn init(dir: impl AsRef<Path>, genesis: &str) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        serde_json::from_str::<RuntimeGenesisConfig>(genesis).map_err(|_| Error::InvalidGenesis)?;
+        if dir.join(GENESIS_FILE).exists() {
+            return Err(Error::AlreadyInitialized);
+        }
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(GENESIS_FILE), genesis)?;
+        Self::open(dir)
+    }
+
+    /// Open the chain in the state directory `dir`, started with `init`.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let genesis = match fs::read_to_string(dir.join(GENESIS_FILE)) {
+            Ok(genesis) => genesis,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::NotInitialized)
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let genesis = serde_json::from_str(&genesis).map_err(|_| Error::InvalidGenesis)?;
+        let mut chain = Chain::new(Runtime::from_genesis(genesis));
+
+        let blocks = dir.join(BLOCKS_FILE);
+        let entries = match blocks.exists() {
+            true => block_log::read(&blocks)?,
+            false => Vec::new(),
+        };
+        for (index, entry) in entries.into_iter().enumerate() {
+            let line = index + 1;
+            let receipt = chain
+                .import_block(entry.block)
+                .map_err(|error| Error::InvalidBlock { line, error })?;
+            let replayed = Ok(receipt.state_root);
+            if replayed != entry.outcome {
+                let recorded = entry.outcome;
+                let error = block_log::Error::Diverged {
+                    line,
+                    recorded,
+                    replayed,
+                };
+                return Err(error.into());
+            }
+        }
+
+        let mut pool = TransactionPool::new(POOL_SIZE);
+        let extrinsics = match fs::read_to_string(dir.join(POOL_FILE)) {
+            Ok(pool) => serde_json::from_str::<Vec<types::Extrinsic>>(&pool)
+                .map_err(|_| Error::CorruptPool)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        for extrinsic in extrinsics {
+            // The pool is pruned whenever it is saved, so its extrinsics are still valid. An
+            // extrinsic rejected anyway is dropped, like `prune` would.
+            let _ = pool.submit(chain.runtime(), extrinsic);
+        }
+
+        let log = BlockLog::open(blocks)?;
+        Ok(Self {
+            dir,
+            chain,
+            pool,
+            log,
+        })
+    }
+
+    /// The chain, holding the state after the best block.
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    /// The extrinsics waiting to be included in a block.
+    pub fn pool(&self) -> &TransactionPool {
+        &self.pool
+    }
+
+    /// The nonce of the next extrinsic of `who`, after their extrinsics in the chain and the pool.
+    pub fn next_nonce(&self, who: &types::AccountId) -> types::Nonce {
+        self.pool.next_nonce(self.chain.runtime(), who)
+    }
+
+    /// Submit `extrinsic` to the pool, validated against the state of the best block.
+    ///
+    /// Returns the hash of the extrinsic.
+    pub fn submit(&mut self, extrinsic: types::Extrinsic) -> Result<Hash, Error> {
+        let hash = self
+            .pool
+            .submit(self.chain.runtime(), extrinsic)
+            .map_err(Error::Pool)?;
+        self.save_pool()?;
+        Ok(hash)
+    }
+
+    /// Import a block with the ready extrinsics of the pool on top of the best block, append it to
+    /// the block log, and remove its extrinsics from the pool. The block is appended to the log
+    /// before it is imported, so the chain is left unchanged if the log can not be written.
+    ///
+    /// Returns the imported block and its receipt.
+    pub fn produce_block(&mut self) -> Result<(&types::Block, &BlockReceipt<RuntimeEvent>), Error> {
+        let block = self.chain.new_block(self.pool.ready(self.chain.runtime()));
+        let hash = block.header.hash();
+        let import = self.chain.prepare_import(block).map_err(Error::Import)?;
+        self.log.append(&Entry {
+            block: import.block().clone(),
+            outcome: Ok(import.receipt().state_root),
+        })?;
+        import.commit();
+        self.pool.prune(self.chain.runtime());
+        self.save_pool()?;
+
+        let block = self.chain.block_by_hash(&hash).expect("block was imported");
+        let receipt = self
+            .chain
+            .receipt_by_hash(&hash)
+            .expect("block was imported");
+        Ok((block, receipt))
+    }
+
+    /// Write the extrinsics of the pool to the state directory.
+    fn save_pool(&self) -> Result<(), Error> {
+        let extrinsics =
+            serde_json::to_string(&self.pool.extrinsics()).expect("extrinsics always serialize");
+        fs::write(self.dir.join(POOL_FILE), extrinsics)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Node};
+    use crate::crypto::Pair;
+    use crate::runtime::{types, BalancesCall};
+    use crate::transaction_pool;
+
+    fn genesis() -> String {
+        let alice = Pair::from_seed("alice").public();
+        format!(
+            r#"{{
+                "balances": {{ "balances": [["alice", 100]] }},
+                "reward_points": {{ "balances": [] }},
+                "accounts": {{ "keys": [["alice", "{}"]] }}
+            }}"#,
+            alice
+        )
+    }
+
+    fn transfer(nonce: u32, amount: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount,
+        };
+        types::Extrinsic::new(String::from("alice"), nonce, call).sign(&Pair::from_seed("alice"))
+    }
+
+    /// This test checks the following:
+    /// - A node starts from a genesis file, and a state directory can only be initialized once.
+    /// - Submitted extrinsics and produced blocks are kept in the state directory, so reopening it
+    ///   gives the same chain and pool.
+    /// - A block log which does not match the chain is rejected.
+    #[test]
+    fn persist_chain_and_pool() {
+        let dir = std::env::temp_dir().join(format!("node_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(matches!(Node::open(&dir), Err(Error::NotInitialized)));
+        assert!(matches!(Node::init(&dir, "{}"), Err(Error::InvalidGenesis)));
+        let mut node = Node::init(&dir, &genesis()).unwrap();
+        assert!(matches!(
+            Node::init(&dir, &genesis()),
+            Err(Error::AlreadyInitialized)
+        ));
+
+        node.submit(transfer(0, 30)).unwrap();
+        node.submit(transfer(1, 20)).unwrap();
+        assert_eq!(node.next_nonce(&String::from("alice")), 2);
+        let (block, receipt) = node.produce_block().unwrap();
+        assert_eq!(block.header.block_number, 1);
+        assert_eq!(receipt.extrinsics.len(), 2);
+        node.submit(transfer(3, 10)).unwrap();
+        assert!(matches!(
+            node.submit(transfer(0, 10)),
+            Err(Error::Pool(transaction_pool::Error::Stale))
+        ));
+        let best_hash = node.chain().best_hash();
+        drop(node);
+
+        let mut node = Node::open(&dir).unwrap();
+        assert_eq!(node.chain().best_hash(), best_hash);
+        let balances = &node.chain().runtime().balances;
+        assert_eq!(balances.balance(&String::from("bob")), 50);
+        assert_eq!(node.pool().extrinsics(), vec![transfer(3, 10)]);
+        assert_eq!(node.next_nonce(&String::from("alice")), 2);
+
+        node.submit(transfer(2, 5)).unwrap();
+        let (block, receipt) = node.produce_block().unwrap();
+        assert_eq!(block.header.block_number, 2);
+        assert_eq!(receipt.extrinsics.len(), 2);
+        assert!(node.pool().is_empty());
+        drop(node);
+
+        let blocks = dir.join(super::BLOCKS_FILE);
+        let log = std::fs::read_to_string(&blocks).unwrap();
+        std::fs::write(&blocks, log.replacen(r#""amount":20"#, r#""amount":21"#, 1)).unwrap();
+        assert!(matches!(
+            Node::open(&dir),
+            Err(Error::InvalidBlock { line: 1, .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
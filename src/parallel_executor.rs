@@ -0,0 +1,498 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime, RuntimeCall, RuntimeEvent};
+use crate::storage;
+use crate::support::{BlockReceipt, ExtrinsicReceipt, Weight};
+use crate::{balances, proof_of_existence};
+
+/// A part of the state of the `Runtime` which an extrinsic may read or write.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    /// Everything stored for an account: its nonce, its balances, and the index of its claims,
+    /// including the joint claims it co-owns.
+    Account(types::AccountId),
+    /// A single or joint claim on some content.
+    Claim(types::Content),
+    /// A claim on a batch of documents, by Merkle root.
+    BatchClaim(Hash),
+}
+
+/// The resources `extrinsic` may read or write, derived from its caller and the arguments of its
+/// call, or `None` if it may access any part of the state.
+///
+/// Every extrinsic accesses the account of its caller, since it uses their nonce and pays its
+/// tip from their balance.
+pub fn access_set(extrinsic: &types::Extrinsic) -> Option<BTreeSet<Resource>> {
+    use balances::Call as BalancesCall;
+    use proof_of_existence::Call as PoeCall;
+
+    let mut resources = BTreeSet::from([Resource::Account(extrinsic.caller.clone())]);
+    match &extrinsic.call {
+        RuntimeCall::balances(BalancesCall::transfer { to, .. })
+        | RuntimeCall::reward_points(BalancesCall::transfer { to, .. }) => {
+            resources.insert(Resource::Account(to.clone()));
+        }
+        RuntimeCall::proof_of_existence(
+            PoeCall::create_claim { claim } | PoeCall::revoke_claim { claim },
+        ) => {
+            resources.insert(Resource::Claim(claim.clone()));
+        }
+        RuntimeCall::proof_of_existence(PoeCall::create_joint_claim {
+            claim, co_owners, ..
+        }) => {
+            resources.insert(Resource::Claim(claim.clone()));
+            resources.extend(co_owners.iter().cloned().map(Resource::Account));
+        }
+        RuntimeCall::proof_of_existence(
+            PoeCall::create_batch_claim { root } | PoeCall::revoke_batch_claim { root },
+        ) => {
+            resources.insert(Resource::BatchClaim(root.0));
+        }
+        // The calls of the Call Filter pallet change which calls every other extrinsic may
+        // dispatch, and approving a joint action may change the claims of co-owners which are
+        // only known from the state. Any call not listed above may access anything.
+        _ => return None,
+    }
+    Some(resources)
+}
+
+/// Check if the value at `key` in the state of the `Runtime` is among `accesses`, by its own key or
+/// by a prefix of it.
+fn is_accessed(accesses: &storage::Accesses, key: &str) -> bool {
+    accesses.contains(key)
+        || key
+            .match_indices('/')
+            .any(|(position, _)| accesses.contains(&key[..=position]))
+}
+
+/// The key of the weight of the block in the state, which every extrinsic changes. The weights of
+/// extrinsics executed in parallel are added up instead of being merged like the other values.
+const BLOCK_WEIGHT_KEY: &str = "system.block_weight";
+
+/// Executes blocks on a `Runtime` like `Runtime::execute_block`, but runs the extrinsics which do
+/// not access the same resources on several threads.
+///
+/// The extrinsics of a block are split by the ones whose access set is unknown, which are executed
+/// alone. Between them, extrinsics sharing a resource, directly or through other extrinsics, are
+/// executed in block order on the same thread. Every thread records the values its extrinsics
+/// read or write, and if none of the values written by a thread were accessed by another one,
+/// executing them in any order reaches the state sequential execution reaches, so the changes of
+/// every thread are merged together. Otherwise, for example when the access set of an extrinsic
+/// missed some of its accesses, their work is discarded and the extrinsics are executed
+/// sequentially.
+#[derive(Debug, Clone)]
+pub struct ParallelExecutor {
+    /// The maximum number of threads executing extrinsics at the same time.
+    threads: usize,
+}
+
+impl ParallelExecutor {
+    /// Create an executor running extrinsics on at most `threads` threads.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+        }
+    }
+
+    /// Execute `block` on `runtime`, with the same outcome as `Runtime::execute_block`.
+    pub fn execute_block(
+        &self,
+        runtime: &mut Runtime,
+        block: types::Block,
+    ) -> Result<BlockReceipt<RuntimeEvent>, &'static str> {
+        let block_number = block.header.block_number;
+        let initialize_events = runtime.initialize_block(block_number)?;
+
+        let mut extrinsics = Vec::with_capacity(block.extrinsics.len());
+        let mut segment = Vec::new();
+        for (index, extrinsic) in block.extrinsics.into_iter().enumerate() {
+            match access_set(&extrinsic) {
+                Some(resources) => segment.push((index, extrinsic, resources)),
+                None => {
+                    extrinsics.extend(self.execute_segment(runtime, std::mem::take(&mut segment)));
+                    extrinsics.push(apply_extrinsic(runtime, index, extrinsic));
+                }
+            }
+        }
+        extrinsics.extend(self.execute_segment(runtime, segment));
+
+        let finalize_events = runtime.finalize_block(block_number);
+        Ok(BlockReceipt {
+            extrinsics,
+            initialize_events,
+            finalize_events,
+            weight: runtime.system.block_weight(),
+            state_root: runtime.state_root(),
+        })
+    }
+
+    /// Split the extrinsics of `segment` into at most `threads` groups, such that extrinsics
+    /// sharing a resource are in the same group. Each group lists positions in `segment`, in
+    /// order.
+    fn schedule(
+        &self,
+        segment: &[(usize, types::Extrinsic, BTreeSet<Resource>)],
+    ) -> Vec<Vec<usize>> {
+        // Union-find over the positions of the extrinsics, joining the ones sharing a resource.
+        let mut parent = (0..segment.len()).collect::<Vec<_>>();
+        fn find(parent: &mut [usize], mut position: usize) -> usize {
+            while parent[position] != position {
+                parent[position] = parent[parent[position]];
+                position = parent[position];
+            }
+            position
+        }
+        let mut first_access = BTreeMap::<&Resource, usize>::new();
+        for (position, (_, _, resources)) in segment.iter().enumerate() {
+            for resource in resources {
+                let other = *first_access.entry(resource).or_insert(position);
+                let (root, other_root) = (find(&mut parent, position), find(&mut parent, other));
+                parent[root] = other_root;
+            }
+        }
+
+        let mut components = BTreeMap::<usize, Vec<usize>>::new();
+        for position in 0..segment.len() {
+            let root = find(&mut parent, position);
+            components.entry(root).or_default().push(position);
+        }
+
+        // The largest components go first, each to the group with the fewest extrinsics so far.
+        let mut components = components.into_values().collect::<Vec<_>>();
+        components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+        let mut groups = vec![Vec::new(); self.threads.min(components.len())];
+        for component in components {
+            let group = groups
+                .iter_mut()
+                .min_by_key(|group| group.len())
+                .expect("there is a group for every component, up to `threads`; qed");
+            group.extend(component);
+        }
+        for group in &mut groups {
+            group.sort();
+        }
+        groups
+    }
+
+    /// Execute the extrinsics of `segment`, whose access sets are all known, and return their
+    /// receipts in order.
+    fn execute_segment(
+        &self,
+        runtime: &mut Runtime,
+        segment: Vec<(usize, types::Extrinsic, BTreeSet<Resource>)>,
+    ) -> Vec<ExtrinsicReceipt<RuntimeEvent>> {
+        let groups = self.schedule(&segment);
+        if groups.len() <= 1 {
+            return execute_sequentially(runtime, segment);
+        }
+        match execute_groups(runtime, &segment, &groups) {
+            Some(receipts) => receipts,
+            None => execute_sequentially(runtime, segment),
+        }
+    }
+}
+
+/// Execute each group of extrinsics of `segment` on its own thread, and merge their changes into
+/// `runtime`. Returns their receipts in order, or `None`, leaving `runtime` untouched, if a group
+/// wrote a value another group accessed.
+///
+/// Each group runs on a clone of `runtime`, which is the only step taking time linear in the size
+/// of the state: the changes of a group are found among the values it accessed, and only those
+/// are written back into `runtime`.
+fn execute_groups(
+    runtime: &mut Runtime,
+    segment: &[(usize, types::Extrinsic, BTreeSet<Resource>)],
+    groups: &[Vec<usize>],
+) -> Option<Vec<ExtrinsicReceipt<RuntimeEvent>>> {
+    let executed = std::thread::scope(|scope| {
+        let handles = groups
+            .iter()
+            .map(|group| {
+                let mut copy = runtime.clone();
+                scope.spawn(move || {
+                    let receipts = storage::record_accesses(|| {
+                        group
+                            .iter()
+                            .map(|&position| {
+                                let (index, extrinsic, _) = &segment[position];
+                                apply_extrinsic(&mut copy, *index, extrinsic.clone())
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                    let accesses = copy.take_accesses();
+                    (copy, accesses, receipts)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("executing extrinsics does not panic"))
+            .collect::<Vec<_>>()
+    });
+
+    // The values each group changed, with their new values. The weight of the block is added up
+    // instead, since every extrinsic adds to it without otherwise reading it.
+    let changes = executed
+        .iter()
+        .map(|(copy, accesses, _)| {
+            let after = copy.encode_keys(accesses);
+            let written = storage::diff(&runtime.encode_keys(accesses), &after)
+                .into_keys()
+                .filter(|key| key != BLOCK_WEIGHT_KEY)
+                .collect::<storage::Accesses>();
+            (written, after)
+        })
+        .collect::<Vec<_>>();
+    for (group, (written, _)) in changes.iter().enumerate() {
+        let conflict = executed
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != group)
+            .any(|(_, (_, accesses, _))| written.iter().any(|key| is_accessed(accesses, key)));
+        if conflict {
+            return None;
+        }
+    }
+
+    let block_weight = runtime.system.block_weight();
+    let weight = executed
+        .iter()
+        .map(|(copy, _, _)| copy.system.block_weight() - block_weight)
+        .sum::<Weight>();
+    let mut receipts = executed
+        .into_iter()
+        .flat_map(|(_, _, receipts)| receipts)
+        .collect::<Vec<_>>();
+    receipts.sort_by_key(|receipt| receipt.index);
+
+    for (written, after) in &changes {
+        runtime
+            .decode_keys(written, after)
+            .expect("values encoded by a clone of the runtime decode to its stored types; qed");
+    }
+    runtime.system.register_weight(weight);
+    Some(receipts)
+}
+
+/// Execute the extrinsics of `segment` one after the other, and return their receipts.
+fn execute_sequentially(
+    runtime: &mut Runtime,
+    segment: Vec<(usize, types::Extrinsic, BTreeSet<Resource>)>,
+) -> Vec<ExtrinsicReceipt<RuntimeEvent>> {
+    segment
+        .into_iter()
+        .map(|(index, extrinsic, _)| apply_extrinsic(runtime, index, extrinsic))
+        .collect()
+}
+
+/// Apply the extrinsic at `index` of the block, like `Runtime::execute_block` does.
+fn apply_extrinsic(
+    runtime: &mut Runtime,
+    index: usize,
+    extrinsic: types::Extrinsic,
+) -> ExtrinsicReceipt<RuntimeEvent> {
+    let weight = extrinsic.call.weight();
+    let (weight, result) = match runtime.apply_extrinsic(extrinsic) {
+        Ok(result) => (weight, result),
+        Err(e) => (0, Err(e)),
+    };
+    ExtrinsicReceipt {
+        index,
+        result,
+        weight,
+        events: runtime.take_events(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{access_set, apply_extrinsic, execute_groups, execute_sequentially};
+    use super::{ParallelExecutor, Resource};
+    use crate::crypto::Pair;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::support::{BlockReceipt, Header};
+    use crate::{accounts, balances, call_filter, proof_of_existence, storage};
+
+    fn genesis() -> Runtime {
+        let accounts = ["alice", "bob", "charlie", "dave", "eve", "ferdie"];
+        Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: accounts
+                    .iter()
+                    .map(|account| (account.to_string(), 100))
+                    .collect(),
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&[
+                "alice", "bob", "charlie", "dave", "eve", "ferdie", "root",
+            ]),
+        })
+    }
+
+    fn transfer(from: &str, nonce: u32, to: &str, amount: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: to.to_string(),
+            amount,
+        };
+        types::Extrinsic::new(from.to_string(), nonce, call).sign(&Pair::from_seed(from))
+    }
+
+    fn claim(from: &str, nonce: u32, claim: &str) -> types::Extrinsic {
+        let call = proof_of_existence::Call::create_claim {
+            claim: claim.to_string(),
+        };
+        types::Extrinsic::new(from.to_string(), nonce, call).sign(&Pair::from_seed(from))
+    }
+
+    /// The outcome of a block, with its events rendered, since events can not be compared.
+    fn outcome(receipt: &BlockReceipt<crate::runtime::RuntimeEvent>) -> String {
+        format!("{:?}", receipt)
+    }
+
+    /// This test checks the following:
+    /// - Extrinsics sharing an account or a claim are scheduled together, and independent ones are
+    ///   spread over the threads.
+    /// - Executing a block in parallel gives the same receipt and state as executing it
+    ///   sequentially, including for failing calls, invalid nonces, tips, and calls of the Call
+    ///   Filter pallet which are executed alone.
+    #[test]
+    fn same_outcome_as_sequential() {
+        let executor = ParallelExecutor::new(3);
+        let extrinsics = vec![
+            transfer("alice", 0, "bob", 10),
+            transfer("charlie", 0, "dave", 20),
+            transfer("bob", 0, "eve", 5),
+            claim("ferdie", 0, "document"),
+            claim("dave", 0, "document"),
+            transfer("eve", 0, "alice", 1_000),
+        ];
+        let segment = extrinsics
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, extrinsic)| {
+                let resources = access_set(&extrinsic).unwrap();
+                (index, extrinsic, resources)
+            })
+            .collect::<Vec<_>>();
+        let groups = executor.schedule(&segment);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.contains(&vec![0, 2, 5]) && groups.contains(&vec![1, 3, 4]));
+
+        let disable = call_filter::Call::disable {
+            pallet: String::from("proof_of_existence"),
+            call: None.into(),
+        };
+        let disable =
+            types::Extrinsic::new(String::from("root"), 0, disable).sign(&Pair::from_seed("root"));
+        assert_eq!(access_set(&disable), None);
+
+        let mut block_1 = extrinsics;
+        block_1.extend([
+            transfer("alice", 0, "charlie", 1),
+            transfer("alice", 1, "dave", 1)
+                .with_tip(3)
+                .sign(&Pair::from_seed("alice")),
+            claim("charlie", 1, "other"),
+            disable,
+            claim("bob", 1, "third"),
+            transfer("ferdie", 0, "alice", 7),
+        ]);
+        let block_2 = vec![
+            transfer("alice", 2, "bob", 1),
+            transfer("eve", 1, "dave", 2),
+            transfer("dave", 1, "alice", 3),
+        ];
+
+        let mut sequential = genesis();
+        let mut parallel = genesis();
+        for (block_number, extrinsics) in [(1, block_1), (2, block_2)] {
+            let block = types::Block {
+                header: Header {
+                    block_number,
+                    ..Default::default()
+                },
+                extrinsics,
+            };
+            let expected = sequential.execute_block(block.clone()).unwrap();
+            let receipt = executor.execute_block(&mut parallel, block).unwrap();
+            assert_eq!(outcome(&receipt), outcome(&expected));
+            assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+        }
+        assert_eq!(parallel.balances.balance(&String::from("bob")), 106);
+    }
+
+    /// This test checks the following:
+    /// - The values read and written while recording accesses are recorded, including the ones
+    ///   which are only read, and the others are not.
+    /// - Groups which do not access the values written by each other are merged.
+    /// - A group reading a value written by another group makes the segment run sequentially, and
+    ///   leaves the runtime untouched, even when it writes nothing the other group accesses.
+    #[test]
+    fn conflicting_access() {
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        let runtime = genesis();
+        storage::record_accesses(|| runtime.balances.balance(&bob));
+        runtime.balances.balance(&alice);
+        assert_eq!(
+            runtime.take_accesses(),
+            [r#"balances.balances/"bob""#.to_string()].into()
+        );
+
+        let independent = [
+            transfer("alice", 0, "bob", 10),
+            claim("charlie", 0, "document"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(index, extrinsic)| {
+            let resources = access_set(&extrinsic).unwrap();
+            (index, extrinsic, resources)
+        })
+        .collect::<Vec<_>>();
+        let mut sequential = genesis();
+        let mut parallel = genesis();
+        let expected = execute_sequentially(&mut sequential, independent.clone());
+        let receipts = execute_groups(&mut parallel, &independent, &[vec![0], vec![1]]).unwrap();
+        assert_eq!(format!("{:?}", receipts), format!("{:?}", expected));
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+
+        // The transfer of `alice` declares only her account, so it looks independent of the
+        // transfer of `bob`, which can only be paid with what `alice` sends them first.
+        let segment = vec![
+            (
+                0,
+                transfer("alice", 0, "bob", 10),
+                [Resource::Account(alice.clone())].into(),
+            ),
+            (
+                1,
+                transfer("bob", 0, "charlie", 105),
+                [Resource::Account(bob.clone())].into(),
+            ),
+        ];
+        let executor = ParallelExecutor::new(2);
+        assert_eq!(executor.schedule(&segment).len(), 2);
+
+        // Alone, the transfer of `bob` fails, so it only reads their balance.
+        let mut alone = genesis();
+        let receipt =
+            storage::record_accesses(|| apply_extrinsic(&mut alone, 1, segment[1].1.clone()));
+        assert!(receipt.result.is_err());
+        assert!(alone.take_accesses().contains(r#"balances.balances/"bob""#));
+        assert_eq!(alone.balances.balance(&bob), 100);
+
+        let mut parallel = genesis();
+        assert!(execute_groups(&mut parallel, &segment, &[vec![0], vec![1]]).is_none());
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", genesis()));
+
+        let mut sequential = genesis();
+        let expected = execute_sequentially(&mut sequential, segment.clone());
+        let receipts = executor.execute_segment(&mut parallel, segment);
+        assert_eq!(format!("{:?}", receipts), format!("{:?}", expected));
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+        assert_eq!(parallel.balances.balance(&String::from("charlie")), 205);
+    }
+}
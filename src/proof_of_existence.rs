@@ -1,50 +1,513 @@
+use crate::merkle;
+use crate::storage::{
+    self, DecodeState, EncodeState, State, StorageDoubleMap, StorageMap, StorageValue,
+};
+use crate::support::{
+    DispatchResult, Get, Hooks, List, OnRuntimeUpgrade, StorageVersion, Versioned, Weight,
+};
 use core::fmt::Debug;
-use std::collections::BTreeMap;
+use core::marker::PhantomData;
+use num::{CheckedAdd, Zero};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeSet;
 
 pub trait Config: crate::system::Config {
     /// The type which represents the content that can be claimed using this pallet.
     /// Could be the content directly as bytes, or better yet the hash of that content.
     /// We leave that decision to the runtime developer.
-    type Content: Debug + Ord;
+    type Content: Debug + Ord + Clone;
+
+    /// The maximum number of claims a single account is allowed to own at any time.
+    type MaxClaimsPerAccount: Get<u32>;
+
+    /// The number of blocks pending approvals on a joint claim stay valid for.
+    type ApprovalPeriod: Get<Self::BlockNumber>;
+}
+
+/// A claim on some content by a single owner.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ClaimInfo<AccountId, BlockNumber> {
+    /// The owner of the claim.
+    pub owner: AccountId,
+    /// The block at which the claim was created.
+    pub created_at: BlockNumber,
+}
+
+/// An action on a joint claim which needs the approval of `threshold` co-owners.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum JointAction<AccountId> {
+    /// Remove the claim entirely.
+    Revoke,
+    /// Hand the claim over to a new set of co-owners, with a new approval threshold.
+    Transfer {
+        owners: Vec<AccountId>,
+        threshold: u32,
+    },
+}
+
+// Display an action like the calls taking it, like `transfer(owners=[bob, charlie], threshold=2)`.
+impl<AccountId: core::fmt::Display> core::fmt::Display for JointAction<AccountId> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            JointAction::Revoke => write!(f, "revoke"),
+            JointAction::Transfer { owners, threshold } => {
+                let owners = owners.iter().map(ToString::to_string).collect::<Vec<_>>();
+                let owners = owners.join(", ");
+                write!(f, "transfer(owners=[{}], threshold={})", owners, threshold)
+            }
+        }
+    }
+}
+
+/// The approvals collected so far for an action on a joint claim.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(bound(deserialize = "AccountId: Ord + serde::Deserialize<'de>, \
+    BlockNumber: serde::Deserialize<'de>"))]
+pub struct PendingApproval<AccountId, BlockNumber> {
+    /// The action being approved.
+    pub action: JointAction<AccountId>,
+    /// The distinct co-owners who approved the action.
+    pub approvals: BTreeSet<AccountId>,
+    /// The block at which these approvals are discarded.
+    pub expires_at: BlockNumber,
+}
+
+/// A claim owned jointly by a set of co-owners.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(bound(deserialize = "AccountId: Ord + serde::Deserialize<'de>, \
+    BlockNumber: serde::Deserialize<'de>"))]
+pub struct JointClaim<AccountId, BlockNumber> {
+    /// The co-owners of the claim.
+    pub owners: BTreeSet<AccountId>,
+    /// The number of distinct co-owners which must approve an action on the claim.
+    pub threshold: u32,
+    /// The actions collecting approvals. A co-owner approves one action at a time, so there are
+    /// never more pending actions than co-owners.
+    pub pending: Vec<PendingApproval<AccountId, BlockNumber>>,
+}
+
+/// The layout of the storage of version 2 and older.
+mod v2 {
+    use super::PendingApproval;
+    use std::collections::BTreeSet;
+
+    /// A claim owned jointly by a set of co-owners, collecting approvals for a single action at a
+    /// time.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[serde(bound(deserialize = "AccountId: Ord + serde::Deserialize<'de>, \
+        BlockNumber: serde::Deserialize<'de>"))]
+    pub struct JointClaim<AccountId, BlockNumber> {
+        pub owners: BTreeSet<AccountId>,
+        pub threshold: u32,
+        pub pending: Option<PendingApproval<AccountId, BlockNumber>>,
+    }
+}
+
+/// The joint claims of the runtime `T`, in the layout of version 2.
+type JointClaimV2Of<T> = v2::JointClaim<
+    <T as crate::system::Config>::AccountId,
+    <T as crate::system::Config>::BlockNumber,
+>;
+
+/// The errors which can be returned by the calls of this pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The content has already been claimed by someone.
+    AlreadyClaimed,
+    /// There is no claim for the content.
+    ClaimNotFound,
+    /// The caller is not the owner of the claim.
+    NotClaimOwner,
+    /// The caller already owns `MaxClaimsPerAccount` claims.
+    TooManyClaims,
+    /// One of the co-owners of a joint claim already owns `MaxClaimsPerAccount` claims.
+    CoOwnerHasTooManyClaims,
+    /// The caller is not one of the co-owners of the joint claim.
+    NotCoOwner,
+    /// The threshold of a joint claim must be between one and the number of co-owners.
+    InvalidThreshold,
+    /// The block at which new approvals expire does not fit in a block number.
+    ApprovalExpiryOverflow,
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::AlreadyClaimed => "this content is already claimed",
+            Error::ClaimNotFound => "revoking claim but it does not exist",
+            Error::NotClaimOwner => "revoking claim but caller does not match owner",
+            Error::TooManyClaims => "caller has reached the maximum number of claims",
+            Error::CoOwnerHasTooManyClaims => "a co-owner has reached the maximum number of claims",
+            Error::NotCoOwner => "caller is not a co-owner of the joint claim",
+            Error::InvalidThreshold => "threshold must be between one and the number of co-owners",
+            Error::ApprovalExpiryOverflow => "approval expiry overflows the block number",
+        }
+    }
 }
 
 /// This is the Proof of Existence Module.
 /// It is a simple module that allows accounts to claim existence of some data.
-#[derive(Debug)]
+#[macros::storage]
+#[derive(Debug, Clone)]
 pub struct Pallet<T: Config> {
-    /// A simple storage map from content to the owner of that content.
+    /// A simple storage map from content to the owner of that content, and when it was claimed.
     /// Accounts can make multiple different claims, but each claim can only have one owner.
-    claims: BTreeMap<T::Content, T::AccountId>,
+    #[storage(getter = get_claim_info)]
+    claims: StorageMap<T::Content, ClaimInfo<T::AccountId, T::BlockNumber>>,
+    /// A reverse index from an owner to all the content they have claimed, alone or jointly.
+    /// This is kept in sync with `claims` and `joint_claims` by the calls of this pallet.
+    owners: StorageDoubleMap<T::AccountId, T::Content, ()>,
+    /// A storage map from the Merkle root of a batch of document hashes to the owner of the batch.
+    /// Any single document of the batch can be proven with an inclusion proof against the root.
+    #[storage(getter = get_batch_claim)]
+    batch_claims: StorageMap<merkle::Hash, T::AccountId>,
+    /// A storage map from content to the co-owners of that content, for jointly owned claims.
+    /// Content is either claimed by a single owner in `claims`, or jointly here, never both.
+    #[storage(getter = get_joint_claim)]
+    joint_claims: StorageMap<T::Content, JointClaim<T::AccountId, T::BlockNumber>>,
+    /// The current block number, as last reported to `on_initialize`.
+    /// Used to compute when pending approvals expire.
+    #[storage(default = T::BlockNumber::zero())]
+    block_number: StorageValue<T::BlockNumber>,
+    /// The events emitted by this pallet, until the runtime takes them.
+    events: Vec<Event<T>>,
+}
+
+/// The events emitted by the Proof of Existence Module.
+#[derive(Debug, Clone)]
+pub enum Event<T: Config> {
+    /// `owner` claimed `claim`.
+    ClaimCreated {
+        owner: T::AccountId,
+        claim: T::Content,
+    },
+    /// `owner` revoked their claim on `claim`.
+    ClaimRevoked {
+        owner: T::AccountId,
+        claim: T::Content,
+    },
+    /// `owner` claimed the batch of documents with the Merkle root `root`.
+    BatchClaimCreated {
+        owner: T::AccountId,
+        root: merkle::Hash,
+    },
+    /// `owner` revoked their claim on the batch with the Merkle root `root`.
+    BatchClaimRevoked {
+        owner: T::AccountId,
+        root: merkle::Hash,
+    },
+    /// `claim` is now jointly owned by `owners`.
+    JointClaimCreated {
+        claim: T::Content,
+        owners: Vec<T::AccountId>,
+    },
+    /// Enough co-owners approved `action` on `claim`, and it was executed.
+    JointActionExecuted {
+        claim: T::Content,
+        action: JointAction<T::AccountId>,
+    },
+}
+
+impl<T: Config> crate::support::Events for Pallet<T> {
+    type Event = Event<T>;
+
+    fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
+    }
 }
 
 impl<T: Config> Pallet<T> {
-    /// Create a new instance of the Proof of Existence Module.
-    pub fn new() -> Self {
-        Self {
-            claims: BTreeMap::new(),
+    /// Get the owner of the claim on `claim`, if any.
+    pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
+        self.get_claim_info(claim).map(|info| &info.owner)
+    }
+
+    /// Get the number of claims owned by `owner`, including the joint claims they co-own.
+    pub fn claim_count(&self, owner: &T::AccountId) -> u32 {
+        Self::count_claims(&self.owners, owner)
+    }
+
+    /// Count the claims of `owner` in the owner index, which can be borrowed while a joint claim
+    /// is being changed.
+    fn count_claims(
+        index: &StorageDoubleMap<T::AccountId, T::Content, ()>,
+        owner: &T::AccountId,
+    ) -> u32 {
+        index.iter_prefix(owner).count() as u32
+    }
+
+    /// Get a page of the claims owned by `owner`, alone or jointly, in content order.
+    /// Skips the first `offset` claims and returns at most `limit` of them.
+    pub fn claims_of(&self, owner: &T::AccountId, offset: usize, limit: usize) -> Vec<&T::Content> {
+        self.owners
+            .iter_prefix(owner)
+            .skip(offset)
+            .take(limit)
+            .map(|(claim, ())| claim)
+            .collect()
+    }
+
+    /// Verify that the document hash `leaf` is part of a claimed batch, using its inclusion
+    /// `proof`. Returns the owner of the batch if the proof leads to a claimed root.
+    pub fn verify_inclusion(
+        &self,
+        leaf: &merkle::Hash,
+        proof: &merkle::Proof,
+    ) -> Option<&T::AccountId> {
+        self.get_batch_claim(&merkle::compute_root(leaf, proof))
+    }
+}
+
+/// Build the set of co-owners of a joint claim, checking `threshold` is valid for that set.
+fn co_owners<AccountId: Ord>(
+    owners: Vec<AccountId>,
+    threshold: u32,
+) -> Result<BTreeSet<AccountId>, Error> {
+    let owners = owners.into_iter().collect::<BTreeSet<_>>();
+    if threshold == 0 || threshold as usize > owners.len() {
+        return Err(Error::InvalidThreshold);
+    }
+    Ok(owners)
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {
+    /// Keep track of the current block number, and discard the pending approvals which expired.
+    fn on_initialize(&mut self, block_number: T::BlockNumber) -> Weight {
+        self.set_block_number(block_number);
+        for joint_claim in self.joint_claims.values_mut() {
+            joint_claim
+                .pending
+                .retain(|pending| pending.expires_at > block_number);
         }
+
+        // Every joint claim is read, and possibly written.
+        self.joint_claims.len() as Weight * 100
     }
+}
 
-    /// Get the owner (if any) of a claim.
-    pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
-        self.claims.get(claim)
+/// Version 1 stores when each claim was created, alongside its owner. Version 2 indexes joint
+/// claims for each of their co-owners, in `owners`. Version 3 collects approvals for several
+/// actions on a joint claim at once.
+impl<T: Config> Versioned for Pallet<T>
+where
+    MigrateToV1<T>: OnRuntimeUpgrade,
+    MigrateToV2<T>: OnRuntimeUpgrade,
+    MigrateToV3<T>: OnRuntimeUpgrade,
+{
+    const STORAGE_VERSION: StorageVersion = StorageVersion(3);
+
+    type Migrations = (MigrateToV1<T>, MigrateToV2<T>, MigrateToV3<T>);
+}
+
+/// Migrate the claims from version 0, which only stored their owner, to `ClaimInfo`.
+///
+/// The block at which the older claims were created was never stored, so they are recorded as
+/// created at the block of the upgrade.
+pub struct MigrateToV1<T>(PhantomData<T>);
+
+impl<T: Config> MigrateToV1<T>
+where
+    T::Content: Serialize + DeserializeOwned,
+    T::AccountId: Serialize + DeserializeOwned,
+{
+    /// The claims in the layout of version 0, from content to owner.
+    fn old_claims(
+        prefix: &str,
+        state: &State,
+    ) -> Result<StorageMap<T::Content, T::AccountId>, &'static str> {
+        let mut claims = StorageMap::new();
+        claims.decode_state(&format!("{}.claims", prefix), state)?;
+        Ok(claims)
+    }
+}
+
+impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T>
+where
+    T::Content: Serialize + DeserializeOwned,
+    T::AccountId: Serialize + DeserializeOwned,
+    T::BlockNumber: Serialize + DeserializeOwned,
+{
+    const FROM: StorageVersion = StorageVersion(0);
+
+    fn on_runtime_upgrade(prefix: &str, state: &mut State) -> DispatchResult {
+        let mut block_number = StorageValue::new(T::BlockNumber::zero());
+        block_number.decode_state(&format!("{}.block_number", prefix), state)?;
+        let old_claims = Self::old_claims(prefix, state)?;
+
+        let mut claims = StorageMap::new();
+        for (claim, owner) in old_claims.iter() {
+            let info = ClaimInfo {
+                owner: owner.clone(),
+                created_at: *block_number.get(),
+            };
+            claims.insert(claim.clone(), info);
+        }
+
+        let claims_prefix = format!("{}.claims", prefix);
+        let old_keys = format!("{}/", claims_prefix);
+        state.retain(|key, _| !key.starts_with(&old_keys));
+        claims.encode_state(&claims_prefix, state);
+        Ok(())
+    }
+
+    /// Remember the owner of every claim.
+    fn pre_upgrade(prefix: &str, state: &State) -> Result<Vec<u8>, &'static str> {
+        let old_claims = Self::old_claims(prefix, state)?;
+        let owners = old_claims.iter().collect::<Vec<_>>();
+        Ok(storage::encode(&owners).into_bytes())
+    }
+
+    /// Check that every claim kept its owner, and that no claim was added.
+    fn post_upgrade(prefix: &str, state: &State, data: Vec<u8>) -> DispatchResult {
+        let owners = String::from_utf8(data).map_err(|_| storage::UNDECODABLE_STATE)?;
+        let owners = storage::decode::<Vec<(T::Content, T::AccountId)>>(&owners)?;
+        let mut claims = StorageMap::<T::Content, ClaimInfo<T::AccountId, T::BlockNumber>>::new();
+        claims.decode_state(&format!("{}.claims", prefix), state)?;
+
+        let kept_owners = owners
+            .iter()
+            .all(|(claim, owner)| claims.get(claim).is_some_and(|info| info.owner == *owner));
+        if !kept_owners || claims.len() != owners.len() {
+            return Err("claims changed owner during the migration");
+        }
+        Ok(())
+    }
+}
+
+/// Migrate the owner index from version 1, which only held single claims, to also hold the joint
+/// claims of each co-owner.
+///
+/// Co-owners may end up with more than `MaxClaimsPerAccount` claims, which they keep, but they can
+/// not claim more until they are under the limit again.
+pub struct MigrateToV2<T>(PhantomData<T>);
+
+impl<T: Config> MigrateToV2<T>
+where
+    T::Content: Serialize + DeserializeOwned,
+    T::AccountId: Serialize + DeserializeOwned,
+    T::BlockNumber: Serialize + DeserializeOwned,
+{
+    /// The joint claims stored under `prefix`.
+    fn joint_claims(
+        prefix: &str,
+        state: &State,
+    ) -> Result<StorageMap<T::Content, JointClaimV2Of<T>>, &'static str> {
+        let mut joint_claims = StorageMap::new();
+        joint_claims.decode_state(&format!("{}.joint_claims", prefix), state)?;
+        Ok(joint_claims)
+    }
+
+    /// The owner index stored under `prefix`.
+    fn owner_index(
+        prefix: &str,
+        state: &State,
+    ) -> Result<StorageDoubleMap<T::AccountId, T::Content, ()>, &'static str> {
+        let mut owners = StorageDoubleMap::new();
+        owners.decode_state(&format!("{}.owners", prefix), state)?;
+        Ok(owners)
+    }
+}
+
+impl<T: Config> OnRuntimeUpgrade for MigrateToV2<T>
+where
+    T::Content: Serialize + DeserializeOwned,
+    T::AccountId: Serialize + DeserializeOwned,
+    T::BlockNumber: Serialize + DeserializeOwned,
+{
+    const FROM: StorageVersion = StorageVersion(1);
+
+    fn on_runtime_upgrade(prefix: &str, state: &mut State) -> DispatchResult {
+        let mut owners = Self::owner_index(prefix, state)?;
+        for (claim, joint_claim) in Self::joint_claims(prefix, state)?.iter() {
+            for owner in &joint_claim.owners {
+                owners.insert(owner.clone(), claim.clone(), ());
+            }
+        }
+        owners.encode_state(&format!("{}.owners", prefix), state);
+        Ok(())
+    }
+
+    /// Check that every co-owner of a joint claim has it in their index.
+    fn post_upgrade(prefix: &str, state: &State, _data: Vec<u8>) -> DispatchResult {
+        let owners = Self::owner_index(prefix, state)?;
+        let indexed = Self::joint_claims(prefix, state)?
+            .iter()
+            .all(|(claim, joint_claim)| {
+                joint_claim
+                    .owners
+                    .iter()
+                    .all(|owner| owners.contains_key(owner, claim))
+            });
+        if !indexed {
+            return Err("joint claims are missing from the owner index");
+        }
+        Ok(())
+    }
+}
+
+/// Migrate the joint claims from version 2, which collected approvals for a single action, to a
+/// list of pending actions, keeping the approvals collected so far.
+pub struct MigrateToV3<T>(PhantomData<T>);
+
+impl<T: Config> OnRuntimeUpgrade for MigrateToV3<T>
+where
+    T::Content: Serialize + DeserializeOwned,
+    T::AccountId: Serialize + DeserializeOwned,
+    T::BlockNumber: Serialize + DeserializeOwned,
+{
+    const FROM: StorageVersion = StorageVersion(2);
+
+    fn on_runtime_upgrade(prefix: &str, state: &mut State) -> DispatchResult {
+        let old_joint_claims = MigrateToV2::<T>::joint_claims(prefix, state)?;
+        let mut joint_claims = StorageMap::new();
+        for (claim, joint_claim) in old_joint_claims.iter() {
+            let joint_claim = JointClaim {
+                owners: joint_claim.owners.clone(),
+                threshold: joint_claim.threshold,
+                pending: joint_claim.pending.clone().into_iter().collect(),
+            };
+            joint_claims.insert(claim.clone(), joint_claim);
+        }
+
+        let joint_claims_prefix = format!("{}.joint_claims", prefix);
+        let old_keys = format!("{}/", joint_claims_prefix);
+        state.retain(|key, _| !key.starts_with(&old_keys));
+        joint_claims.encode_state(&joint_claims_prefix, state);
+        Ok(())
     }
 }
 
 #[macros::call]
 impl<T: Config> Pallet<T> {
     /// Create a new claim on behalf of the `caller`.
-    /// This function will return an error if someone already has claimed that content.
+    /// This function will return an error if someone already has claimed that content,
+    /// or if the `caller` already owns `MaxClaimsPerAccount` claims.
+    #[call_index(0)]
+    #[weight(10_000)]
     pub fn create_claim(
         &mut self,
         caller: T::AccountId,
         claim: T::Content,
     ) -> crate::support::DispatchResult {
-        if self.claims.contains_key(&claim) {
-            return Err(&"this content is already claimed");
+        if self.claims.contains_key(&claim) || self.joint_claims.contains_key(&claim) {
+            return Err(Error::AlreadyClaimed.into());
+        }
+
+        if self.claim_count(&caller) >= T::MaxClaimsPerAccount::get() {
+            return Err(Error::TooManyClaims.into());
         }
 
-        self.claims.insert(claim, caller);
+        self.owners.insert(caller.clone(), claim.clone(), ());
+        let info = ClaimInfo {
+            owner: caller.clone(),
+            created_at: self.block_number(),
+        };
+        self.claims.insert(claim.clone(), info);
+
+        self.events.push(Event::ClaimCreated {
+            owner: caller,
+            claim,
+        });
 
         Ok(())
     }
@@ -52,20 +515,247 @@ impl<T: Config> Pallet<T> {
     /// Revoke an existing claim on some content.
     /// This function should only succeed if the caller is the owner of an existing claim.
     /// It will return an error if the claim does not exist, or if the caller is not the owner.
+    #[call_index(1)]
+    #[weight(10_000)]
     pub fn revoke_claim(
         &mut self,
         caller: T::AccountId,
         claim: T::Content,
     ) -> crate::support::DispatchResult {
-        let owner = self
-            .get_claim(&claim)
-            .ok_or("revoking claim but it does not exist")?;
+        let owner = self.get_claim(&claim).ok_or(Error::ClaimNotFound)?;
 
         if *owner != caller {
-            return Err(&"revoking claim but caller does not match owner");
+            return Err(Error::NotClaimOwner.into());
         }
 
         self.claims.remove(&claim);
+        self.owners.remove(&caller, &claim);
+
+        self.events.push(Event::ClaimRevoked {
+            owner: caller,
+            claim,
+        });
+
+        Ok(())
+    }
+
+    /// Create a new batch claim on behalf of the `caller`, where `root` is the Merkle root of
+    /// many document hashes built with `merkle::MerkleTree`.
+    /// This function will return an error if someone already has claimed that root.
+    /// Batch claims are tracked separately from single claims, and do not count towards
+    /// `MaxClaimsPerAccount`.
+    #[call_index(2)]
+    #[weight(10_000)]
+    pub fn create_batch_claim(
+        &mut self,
+        caller: T::AccountId,
+        root: merkle::HexHash,
+    ) -> crate::support::DispatchResult {
+        let root = root.0;
+        if self.batch_claims.contains_key(&root) {
+            return Err(Error::AlreadyClaimed.into());
+        }
+
+        self.batch_claims.insert(root, caller.clone());
+
+        self.events.push(Event::BatchClaimCreated {
+            owner: caller,
+            root,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke an existing batch claim.
+    /// It will return an error if the batch claim does not exist, or if the caller is not the owner.
+    #[call_index(3)]
+    #[weight(10_000)]
+    pub fn revoke_batch_claim(
+        &mut self,
+        caller: T::AccountId,
+        root: merkle::HexHash,
+    ) -> crate::support::DispatchResult {
+        let root = root.0;
+        let owner = self.get_batch_claim(&root).ok_or(Error::ClaimNotFound)?;
+
+        if *owner != caller {
+            return Err(Error::NotClaimOwner.into());
+        }
+
+        self.batch_claims.remove(&root);
+
+        self.events.push(Event::BatchClaimRevoked {
+            owner: caller,
+            root,
+        });
+
+        Ok(())
+    }
+
+    /// Create a new claim owned jointly by the `caller` and `co_owners`.
+    /// Revoking or transferring the claim later needs the approval of `threshold` distinct
+    /// co-owners, see `approve_joint_action`.
+    /// This function will return an error if someone already has claimed that content, or if one
+    /// of the co-owners already owns `MaxClaimsPerAccount` claims, since a joint claim counts
+    /// towards the limit of each co-owner.
+    #[call_index(4)]
+    #[weight(20_000)]
+    pub fn create_joint_claim(
+        &mut self,
+        caller: T::AccountId,
+        claim: T::Content,
+        co_owners: List<T::AccountId>,
+        threshold: u32,
+    ) -> crate::support::DispatchResult {
+        if self.claims.contains_key(&claim) || self.joint_claims.contains_key(&claim) {
+            return Err(Error::AlreadyClaimed.into());
+        }
+
+        let mut owners = co_owners.0;
+        owners.push(caller);
+        let owners = self::co_owners(owners, threshold)?;
+        if owners
+            .iter()
+            .any(|owner| self.claim_count(owner) >= T::MaxClaimsPerAccount::get())
+        {
+            return Err(Error::CoOwnerHasTooManyClaims.into());
+        }
+
+        for owner in &owners {
+            self.owners.insert(owner.clone(), claim.clone(), ());
+        }
+        self.events.push(Event::JointClaimCreated {
+            claim: claim.clone(),
+            owners: owners.iter().cloned().collect(),
+        });
+
+        self.joint_claims.insert(
+            claim,
+            JointClaim {
+                owners,
+                threshold,
+                pending: Vec::new(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Approve an `action` on a joint claim on behalf of the `caller`, who must be a co-owner.
+    /// The action is executed once `threshold` distinct co-owners approved it.
+    ///
+    /// Several actions may collect approvals at once, but each co-owner approves only one of them
+    /// at a time: approving another action moves the approval of the `caller` to it, and leaves
+    /// the approvals of the other co-owners alone. Pending approvals expire `ApprovalPeriod`
+    /// blocks after the first approval of their action.
+    ///
+    /// The approval which executes a transfer fails, and is not recorded, if one of the new
+    /// co-owners already owns `MaxClaimsPerAccount` claims.
+    #[call_index(5)]
+    #[weight(20_000)]
+    pub fn approve_joint_action(
+        &mut self,
+        caller: T::AccountId,
+        claim: T::Content,
+        action: JointAction<T::AccountId>,
+    ) -> crate::support::DispatchResult {
+        // New approvals are valid for `ApprovalPeriod` blocks from now.
+        let expires_at = self
+            .block_number()
+            .checked_add(&T::ApprovalPeriod::get())
+            .ok_or(Error::ApprovalExpiryOverflow)?;
+
+        let joint_claim = self
+            .joint_claims
+            .get_mut(&claim)
+            .ok_or(Error::ClaimNotFound)?;
+
+        if !joint_claim.owners.contains(&caller) {
+            return Err(Error::NotCoOwner.into());
+        }
+
+        // Check the new co-owners before collecting any approval for a transfer.
+        let new_owners = match &action {
+            JointAction::Transfer { owners, threshold } => co_owners(owners.clone(), *threshold)?,
+            JointAction::Revoke => BTreeSet::new(),
+        };
+
+        // If this approval executes a transfer, the co-owners joining the claim must have room
+        // for it, which is checked before anything is recorded.
+        let pending = joint_claim
+            .pending
+            .iter()
+            .find(|pending| pending.action == action);
+        let approvals = match pending {
+            Some(pending) => {
+                pending.approvals.len() + usize::from(!pending.approvals.contains(&caller))
+            }
+            None => 1,
+        };
+        if approvals as u32 >= joint_claim.threshold
+            && new_owners.iter().any(|owner| {
+                !joint_claim.owners.contains(owner)
+                    && Self::count_claims(&self.owners, owner) >= T::MaxClaimsPerAccount::get()
+            })
+        {
+            return Err(Error::CoOwnerHasTooManyClaims.into());
+        }
+
+        // Move the approval of the caller from any other action, dropping actions left without
+        // approvals.
+        for pending in joint_claim.pending.iter_mut() {
+            if pending.action != action {
+                pending.approvals.remove(&caller);
+            }
+        }
+        joint_claim
+            .pending
+            .retain(|pending| !pending.approvals.is_empty() || pending.action == action);
+        let pending = match joint_claim
+            .pending
+            .iter()
+            .position(|pending| pending.action == action)
+        {
+            Some(position) => &mut joint_claim.pending[position],
+            None => {
+                joint_claim.pending.push(PendingApproval {
+                    action,
+                    approvals: BTreeSet::new(),
+                    expires_at,
+                });
+                joint_claim
+                    .pending
+                    .last_mut()
+                    .expect("an action was just pushed; qed")
+            }
+        };
+        pending.approvals.insert(caller);
+
+        if (pending.approvals.len() as u32) < joint_claim.threshold {
+            return Ok(());
+        }
+
+        let action = pending.action.clone();
+        for owner in &joint_claim.owners {
+            self.owners.remove(owner, &claim);
+        }
+        match &action {
+            JointAction::Revoke => {
+                self.joint_claims.remove(&claim);
+            }
+            JointAction::Transfer { threshold, .. } => {
+                for owner in &new_owners {
+                    self.owners.insert(owner.clone(), claim.clone(), ());
+                }
+                joint_claim.owners = new_owners;
+                joint_claim.threshold = *threshold;
+                // The approvals were given by the previous co-owners.
+                joint_claim.pending.clear();
+            }
+        }
+
+        self.events
+            .push(Event::JointActionExecuted { claim, action });
 
         Ok(())
     }
@@ -75,8 +765,26 @@ impl<T: Config> Pallet<T> {
 mod test {
     struct TestConfig;
 
+    struct MaxClaimsPerAccount;
+
+    impl crate::support::Get<u32> for MaxClaimsPerAccount {
+        fn get() -> u32 {
+            2
+        }
+    }
+
+    struct ApprovalPeriod;
+
+    impl crate::support::Get<u32> for ApprovalPeriod {
+        fn get() -> u32 {
+            2
+        }
+    }
+
     impl super::Config for TestConfig {
         type Content = &'static str;
+        type MaxClaimsPerAccount = MaxClaimsPerAccount;
+        type ApprovalPeriod = ApprovalPeriod;
     }
 
     impl crate::system::Config for TestConfig {
@@ -85,6 +793,21 @@ mod test {
         type Nonce = u32;
     }
 
+    /// A config whose claims can be decoded from a `State`, which is needed by the migrations.
+    struct DecodableConfig;
+
+    impl super::Config for DecodableConfig {
+        type Content = String;
+        type MaxClaimsPerAccount = MaxClaimsPerAccount;
+        type ApprovalPeriod = ApprovalPeriod;
+    }
+
+    impl crate::system::Config for DecodableConfig {
+        type AccountId = String;
+        type BlockNumber = u32;
+        type Nonce = u32;
+    }
+
     #[test]
     fn basic_proof_of_existence() {
         let mut proof_of_existence = super::Pallet::<TestConfig>::new();
@@ -112,5 +835,527 @@ mod test {
         assert_eq!(proof_of_existence.revoke_claim(alice, content_1), Ok(()));
 
         assert_eq!(proof_of_existence.get_claim(&content_1), None);
+
+        let events = crate::support::Events::take_events(&mut proof_of_existence);
+        assert!(matches!(
+            events.as_slice(),
+            [
+                super::Event::ClaimCreated {
+                    owner: "alice",
+                    claim: "content 1"
+                },
+                super::Event::ClaimRevoked {
+                    owner: "alice",
+                    claim: "content 1"
+                },
+            ]
+        ));
+    }
+
+    /// This test checks the following:
+    /// - That the owner index follows `create_claim` and `revoke_claim`.
+    /// - That `claims_of` pages through the claims of an owner.
+    /// - That an account cannot own more than `MaxClaimsPerAccount` claims.
+    #[test]
+    fn owner_index_and_claim_limit() {
+        let mut proof_of_existence = super::Pallet::<TestConfig>::new();
+        let alice = "alice";
+        let bob = "bob";
+
+        assert_eq!(proof_of_existence.create_claim(alice, "b"), Ok(()));
+        assert_eq!(proof_of_existence.create_claim(alice, "a"), Ok(()));
+        assert_eq!(proof_of_existence.create_claim(bob, "c"), Ok(()));
+
+        assert_eq!(proof_of_existence.claim_count(&alice), 2);
+        assert_eq!(
+            proof_of_existence.claims_of(&alice, 0, 10),
+            vec![&"a", &"b"]
+        );
+        assert_eq!(proof_of_existence.claims_of(&alice, 1, 10), vec![&"b"]);
+        assert_eq!(proof_of_existence.claims_of(&alice, 0, 1), vec![&"a"]);
+        assert!(proof_of_existence.claims_of(&alice, 2, 10).is_empty());
+
+        assert_eq!(
+            proof_of_existence.create_claim(alice, "d"),
+            Err(super::Error::TooManyClaims.into())
+        );
+
+        assert_eq!(proof_of_existence.revoke_claim(alice, "a"), Ok(()));
+        assert_eq!(proof_of_existence.claims_of(&alice, 0, 10), vec![&"b"]);
+        assert_eq!(proof_of_existence.create_claim(alice, "d"), Ok(()));
+
+        assert_eq!(proof_of_existence.revoke_claim(bob, "c"), Ok(()));
+        assert_eq!(proof_of_existence.claim_count(&bob), 0);
+        assert!(proof_of_existence.claims_of(&bob, 0, 10).is_empty());
+    }
+
+    /// This test checks the following:
+    /// - That a batch of documents can be claimed with a single Merkle root.
+    /// - That each document of the batch can be proven to the owner of the batch.
+    /// - That a document outside of the batch cannot be proven.
+    /// - That only the owner can revoke the batch claim, after which proofs no longer verify.
+    #[test]
+    fn batch_claims() {
+        use crate::merkle::{hash, MerkleTree};
+
+        let mut proof_of_existence = super::Pallet::<TestConfig>::new();
+        let alice = "alice";
+        let bob = "bob";
+
+        let documents = (0..5)
+            .map(|i| hash(format!("document {}", i).as_bytes()))
+            .collect::<Vec<_>>();
+        let tree = MerkleTree::new(&documents).unwrap();
+        let root = tree.root();
+
+        assert_eq!(
+            proof_of_existence.create_batch_claim(alice, root.into()),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.create_batch_claim(bob, root.into()),
+            Err("this content is already claimed")
+        );
+
+        for (i, document) in documents.iter().enumerate() {
+            let proof = tree.proof(i).unwrap();
+            assert_eq!(
+                proof_of_existence.verify_inclusion(document, &proof),
+                Some(&alice)
+            );
+        }
+
+        let proof = tree.proof(0).unwrap();
+        assert_eq!(
+            proof_of_existence.verify_inclusion(&hash(b"unknown document"), &proof),
+            None
+        );
+
+        assert_eq!(
+            proof_of_existence.revoke_batch_claim(bob, root.into()),
+            Err("revoking claim but caller does not match owner")
+        );
+        assert_eq!(
+            proof_of_existence.revoke_batch_claim(alice, root.into()),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.verify_inclusion(&documents[0], &proof),
+            None
+        );
+    }
+
+    /// This test checks the following:
+    /// - That a joint claim needs a valid threshold, and blocks single claims on the same content.
+    /// - That only co-owners can approve actions, and approvals from the same co-owner count once.
+    /// - That a transfer happens once `threshold` co-owners approved it.
+    /// - That approving a different action moves the approval of the caller, and keeps the
+    ///   approvals of the other co-owners, so a conflicting approval can not stall an action.
+    /// - That pending approvals expire after `ApprovalPeriod` blocks.
+    /// - That a revoke happens once `threshold` of the new co-owners approved it.
+    #[test]
+    fn joint_claims() {
+        use super::JointAction;
+        use crate::support::Hooks;
+
+        let mut proof_of_existence = super::Pallet::<TestConfig>::new();
+        let (alice, bob, charlie, dave) = ("alice", "bob", "charlie", "dave");
+        let content = "joint content";
+        let revoke = JointAction::Revoke;
+        let transfer = JointAction::Transfer {
+            owners: vec![charlie, dave],
+            threshold: 1,
+        };
+
+        proof_of_existence.on_initialize(1);
+
+        assert_eq!(
+            proof_of_existence.create_joint_claim(alice, content, vec![bob].into(), 3),
+            Err("threshold must be between one and the number of co-owners")
+        );
+        assert_eq!(
+            proof_of_existence.create_joint_claim(alice, content, vec![bob, charlie].into(), 2),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.create_claim(dave, content),
+            Err("this content is already claimed")
+        );
+
+        assert_eq!(
+            proof_of_existence.approve_joint_action(dave, content, revoke.clone()),
+            Err("caller is not a co-owner of the joint claim")
+        );
+
+        // Alice approving twice does not reach the threshold.
+        assert_eq!(
+            proof_of_existence.approve_joint_action(alice, content, transfer.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.approve_joint_action(alice, content, transfer.clone()),
+            Ok(())
+        );
+        // Bob approving something else keeps the approval of Alice.
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, content, revoke.clone()),
+            Ok(())
+        );
+        let pending = |proof_of_existence: &super::Pallet<TestConfig>| {
+            let joint_claim = proof_of_existence.get_joint_claim(&content).unwrap();
+            joint_claim
+                .pending
+                .iter()
+                .map(|pending| (pending.action.clone(), pending.approvals.len()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            pending(&proof_of_existence),
+            vec![(transfer.clone(), 1), (revoke.clone(), 1)]
+        );
+
+        // The approvals expire after two blocks.
+        proof_of_existence.on_initialize(2);
+        assert_eq!(pending(&proof_of_existence).len(), 2);
+        proof_of_existence.on_initialize(3);
+        assert_eq!(pending(&proof_of_existence), vec![]);
+
+        // Bob moves their approval from the revoke to a transfer to Bob alone, which drops the
+        // revoke, but can not wipe out the approval of Alice for the other transfer.
+        let to_bob = JointAction::Transfer {
+            owners: vec![bob],
+            threshold: 1,
+        };
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, content, revoke.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.approve_joint_action(alice, content, transfer.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, content, to_bob.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            pending(&proof_of_existence),
+            vec![(transfer.clone(), 1), (to_bob, 1)]
+        );
+        assert_eq!(
+            proof_of_existence.approve_joint_action(charlie, content, transfer.clone()),
+            Ok(())
+        );
+        let joint_claim = proof_of_existence.get_joint_claim(&content).unwrap();
+        assert_eq!(joint_claim.owners, [charlie, dave].into_iter().collect());
+        assert_eq!(joint_claim.threshold, 1);
+        assert_eq!(joint_claim.pending, vec![]);
+
+        assert_eq!(
+            proof_of_existence.approve_joint_action(alice, content, revoke.clone()),
+            Err("caller is not a co-owner of the joint claim")
+        );
+        assert_eq!(
+            proof_of_existence.approve_joint_action(dave, content, revoke),
+            Ok(())
+        );
+        assert_eq!(proof_of_existence.get_joint_claim(&content), None);
+    }
+
+    /// This test checks the following:
+    /// - That joint claims are indexed for every co-owner, and follow transfers and revokes.
+    /// - That a joint claim counts towards `MaxClaimsPerAccount` for every co-owner, when it is
+    ///   created and when it is transferred, and that a rejected transfer records no approval.
+    /// - That approving an action fails when its expiry overflows the block number.
+    #[test]
+    fn joint_claim_index_and_limit() {
+        use super::{Error, JointAction};
+        use crate::support::Hooks;
+
+        let mut proof_of_existence = super::Pallet::<TestConfig>::new();
+        let (alice, bob, charlie, dave) = ("alice", "bob", "charlie", "dave");
+        let to = |owners: Vec<&'static str>| JointAction::Transfer {
+            owners,
+            threshold: 1,
+        };
+
+        assert_eq!(proof_of_existence.create_claim(alice, "a"), Ok(()));
+        assert_eq!(
+            proof_of_existence.create_joint_claim(alice, "j1", vec![bob].into(), 1),
+            Ok(())
+        );
+        assert_eq!(
+            proof_of_existence.claims_of(&alice, 0, 10),
+            vec![&"a", &"j1"]
+        );
+        assert_eq!(proof_of_existence.claim_count(&bob), 1);
+        assert_eq!(
+            proof_of_existence.create_joint_claim(charlie, "j2", vec![alice].into(), 1),
+            Err(Error::CoOwnerHasTooManyClaims.into())
+        );
+        assert_eq!(
+            proof_of_existence.create_joint_claim(charlie, "j2", vec![bob].into(), 1),
+            Ok(())
+        );
+
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, "j1", to(vec![charlie, dave])),
+            Ok(())
+        );
+        assert_eq!(proof_of_existence.claims_of(&alice, 0, 10), vec![&"a"]);
+        assert_eq!(proof_of_existence.claims_of(&bob, 0, 10), vec![&"j2"]);
+        assert_eq!(
+            proof_of_existence.claims_of(&charlie, 0, 10),
+            vec![&"j1", &"j2"]
+        );
+
+        assert_eq!(proof_of_existence.create_claim(dave, "d"), Ok(()));
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, "j2", to(vec![dave])),
+            Err(Error::CoOwnerHasTooManyClaims.into())
+        );
+        assert_eq!(
+            proof_of_existence.get_joint_claim(&"j2").unwrap().pending,
+            vec![]
+        );
+
+        assert_eq!(
+            proof_of_existence.approve_joint_action(dave, "j1", JointAction::Revoke),
+            Ok(())
+        );
+        assert_eq!(proof_of_existence.claims_of(&charlie, 0, 10), vec![&"j2"]);
+        assert_eq!(proof_of_existence.claims_of(&dave, 0, 10), vec![&"d"]);
+
+        proof_of_existence.on_initialize(u32::MAX - 1);
+        assert_eq!(
+            proof_of_existence.approve_joint_action(bob, "j2", JointAction::Revoke),
+            Err(Error::ApprovalExpiryOverflow.into())
+        );
+    }
+
+    /// Checks the metadata generated for the calls of this pallet, in particular the rendering of
+    /// the argument types and doc comments, and the display of calls with list, hash and action
+    /// arguments.
+    #[test]
+    fn call_metadata() {
+        let metadata = super::Call::<TestConfig>::metadata();
+        let names = metadata.iter().map(|call| call.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "create_claim",
+                "revoke_claim",
+                "create_batch_claim",
+                "revoke_batch_claim",
+                "create_joint_claim",
+                "approve_joint_action"
+            ]
+        );
+
+        let create_joint_claim = &metadata[4];
+        assert_eq!(create_joint_claim.index, 4);
+        let args = create_joint_claim
+            .args
+            .iter()
+            .map(|arg| (arg.name, arg.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            args,
+            vec![
+                ("claim", "T::Content"),
+                ("co_owners", "List<T::AccountId>"),
+                ("threshold", "u32")
+            ]
+        );
+        assert_eq!(
+            create_joint_claim.docs[0],
+            "Create a new claim owned jointly by the `caller` and `co_owners`."
+        );
+
+        assert_eq!(metadata[2].args[0].ty, "merkle::HexHash");
+        assert_eq!(metadata[5].args[1].ty, "JointAction<T::AccountId>");
+
+        let create_batch_claim = super::Call::<TestConfig>::create_batch_claim {
+            root: [0xab; 32].into(),
+        };
+        assert_eq!(
+            create_batch_claim.to_string(),
+            format!("create_batch_claim(root={})", "ab".repeat(32))
+        );
+        let create_joint_claim = super::Call::<TestConfig>::create_joint_claim {
+            claim: "document",
+            co_owners: vec!["bob", "charlie"].into(),
+            threshold: 2,
+        };
+        assert_eq!(
+            create_joint_claim.to_string(),
+            "create_joint_claim(claim=document, co_owners=[bob, charlie], threshold=2)"
+        );
+        let approve_joint_action = super::Call::<TestConfig>::approve_joint_action {
+            claim: "document",
+            action: super::JointAction::Transfer {
+                owners: vec!["dave"],
+                threshold: 1,
+            },
+        };
+        assert_eq!(
+            approve_joint_action.to_string(),
+            "approve_joint_action(claim=document, action=transfer(owners=[dave], threshold=1))"
+        );
+    }
+
+    /// This test checks the following:
+    /// - That `MigrateToV1` turns the owner of each claim of version 0 into a `ClaimInfo`, created
+    ///   at the block of the upgrade, and leaves the other stored values alone.
+    /// - That the migration is skipped once the state is at version 1.
+    /// - That the migration fails if a claim of version 0 can not be decoded.
+    #[test]
+    fn migrate_to_v1() {
+        use crate::storage::{double_map_key, map_key, DecodeState, State, UNDECODABLE_STATE};
+        use crate::support::{Migrations, StorageVersion};
+
+        type Migration = (super::MigrateToV1<DecodableConfig>,);
+        let alice = String::from("alice");
+        let document = String::from("document");
+
+        let mut state = State::new();
+        state.insert("poe.block_number".to_string(), "7".to_string());
+        state.insert(map_key("poe.claims", &document), r#""alice""#.to_string());
+        state.insert(map_key("poe.claims", "other"), r#""bob""#.to_string());
+        state.insert(
+            double_map_key("poe.owners", &alice, &document),
+            "null".to_string(),
+        );
+
+        let mut version = StorageVersion(0);
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(version, StorageVersion(1));
+        assert_eq!(state.len(), 4);
+
+        let mut proof_of_existence = super::Pallet::<DecodableConfig>::new();
+        proof_of_existence.decode_state("poe", &state).unwrap();
+        assert_eq!(
+            proof_of_existence.get_claim_info(&document),
+            Some(&super::ClaimInfo {
+                owner: alice.clone(),
+                created_at: 7
+            })
+        );
+        assert_eq!(
+            proof_of_existence.get_claim(&String::from("other")),
+            Some(&String::from("bob"))
+        );
+        assert_eq!(proof_of_existence.claims_of(&alice, 0, 10), vec![&document]);
+
+        let migrated = state.clone();
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(state, migrated);
+
+        let mut version = StorageVersion(0);
+        state.insert(map_key("poe.claims", "broken"), "1".to_string());
+        assert_eq!(
+            Migration::migrate("poe", &mut version, &mut state),
+            Err(UNDECODABLE_STATE)
+        );
+    }
+
+    /// This test checks the following:
+    /// - That `MigrateToV2` indexes every joint claim for each of its co-owners, keeping the claims
+    ///   already in the index.
+    /// - That the migration only runs from version 1.
+    #[test]
+    fn migrate_to_v2() {
+        use crate::storage::{double_map_key, map_key, DecodeState, State};
+        use crate::support::{Migrations, StorageVersion};
+
+        type Migration = (
+            super::MigrateToV1<DecodableConfig>,
+            super::MigrateToV2<DecodableConfig>,
+            super::MigrateToV3<DecodableConfig>,
+        );
+        let (alice, bob) = (String::from("alice"), String::from("bob"));
+        let (document, joint) = (String::from("document"), String::from("joint"));
+
+        let mut state = State::new();
+        state.insert(
+            map_key("poe.claims", &document),
+            r#"{"owner":"alice","created_at":1}"#.to_string(),
+        );
+        state.insert(
+            double_map_key("poe.owners", &alice, &document),
+            "null".to_string(),
+        );
+        state.insert(
+            map_key("poe.joint_claims", &joint),
+            r#"{"owners":["alice","bob"],"threshold":1,"pending":null}"#.to_string(),
+        );
+
+        let mut version = StorageVersion(1);
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(version, StorageVersion(3));
+
+        let mut proof_of_existence = super::Pallet::<DecodableConfig>::new();
+        proof_of_existence.decode_state("poe", &state).unwrap();
+        assert_eq!(
+            proof_of_existence.claims_of(&alice, 0, 10),
+            vec![&document, &joint]
+        );
+        assert_eq!(proof_of_existence.claims_of(&bob, 0, 10), vec![&joint]);
+
+        let migrated = state.clone();
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(state, migrated);
+    }
+
+    /// This test checks the following:
+    /// - That `MigrateToV3` turns the single pending approval of each joint claim of version 2
+    ///   into a list of pending actions, keeping its approvals.
+    /// - That the migration only runs from version 2.
+    #[test]
+    fn migrate_to_v3() {
+        use crate::storage::{map_key, DecodeState, State};
+        use crate::support::{Migrations, StorageVersion};
+
+        type Migration = (super::MigrateToV3<DecodableConfig>,);
+        let (alice, bob) = (String::from("alice"), String::from("bob"));
+
+        let mut state = State::new();
+        state.insert(
+            map_key("poe.joint_claims", "idle"),
+            r#"{"owners":["alice","bob"],"threshold":2,"pending":null}"#.to_string(),
+        );
+        state.insert(
+            map_key("poe.joint_claims", "revoking"),
+            concat!(
+                r#"{"owners":["alice","bob"],"threshold":2,"#,
+                r#""pending":{"action":"Revoke","approvals":["bob"],"expires_at":5}}"#
+            )
+            .to_string(),
+        );
+        let old_state = state.clone();
+        let mut version = StorageVersion(1);
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(state, old_state);
+
+        let mut version = StorageVersion(2);
+        Migration::migrate("poe", &mut version, &mut state).unwrap();
+        assert_eq!(version, StorageVersion(3));
+
+        let mut proof_of_existence = super::Pallet::<DecodableConfig>::new();
+        proof_of_existence.decode_state("poe", &state).unwrap();
+        let idle = proof_of_existence.get_joint_claim(&String::from("idle"));
+        assert_eq!(idle.unwrap().pending, vec![]);
+        let revoking = proof_of_existence
+            .get_joint_claim(&String::from("revoking"))
+            .unwrap();
+        assert_eq!(
+            revoking.pending,
+            vec![super::PendingApproval {
+                action: super::JointAction::Revoke,
+                approvals: [bob].into_iter().collect(),
+                expires_at: 5,
+            }]
+        );
+        assert!(revoking.owners.contains(&alice));
     }
 }
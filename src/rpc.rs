@@ -0,0 +1,439 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::merkle;
+use crate::node::{self, Node};
+use crate::runtime::{types, Runtime};
+
+/// The message is not valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The message is not a valid JSON-RPC request.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The parameters of the method are missing or can not be decoded.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The node failed to handle a valid request.
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The transaction pool rejected the submitted extrinsic.
+pub const INVALID_EXTRINSIC: i64 = 1010;
+
+/// The error object of a JSON-RPC response.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RpcError {
+    /// One of the error codes of this module.
+    pub code: i64,
+    /// A short description of the error.
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A JSON-RPC 2.0 request. A request without an `id` is a notification, which gets no response,
+/// while a request whose `id` is `null` gets one.
+#[derive(Debug, serde::Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+}
+
+/// Deserialize a field which is given, even as `null`, to `Some`. Used with `#[serde(default)]`,
+/// only a missing field is `None`.
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// The positional parameters of a request.
+struct Params(Vec<Value>);
+
+impl Params {
+    fn new(params: Value) -> Result<Self, RpcError> {
+        match params {
+            Value::Null => Ok(Self(Vec::new())),
+            Value::Array(params) => Ok(Self(params)),
+            _ => Err(RpcError::new(INVALID_PARAMS, "params must be an array")),
+        }
+    }
+
+    /// The parameter at `index`, or `None` if it is missing or null.
+    fn optional<T: DeserializeOwned>(&self, index: usize) -> Result<Option<T>, RpcError> {
+        match self.0.get(index) {
+            None | Some(Value::Null) => Ok(None),
+            Some(param) => T::deserialize(param)
+                .map(Some)
+                .map_err(|error| RpcError::new(INVALID_PARAMS, error.to_string())),
+        }
+    }
+
+    /// The parameter at `index`, which must be given.
+    fn required<T: DeserializeOwned>(&self, index: usize) -> Result<T, RpcError> {
+        self.optional(index)?
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing parameter {}", index)))
+    }
+}
+
+/// Handle a message from a client, holding a single request or a batch of requests, on `node`.
+///
+/// Returns the response to send back, or `None` if the message only held notifications.
+pub fn handle_message(node: &Mutex<Node>, message: &str) -> Option<Value> {
+    let message = match serde_json::from_str(message) {
+        Ok(message) => message,
+        Err(_) => {
+            let error = RpcError::new(PARSE_ERROR, "message is not valid JSON");
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+    match message {
+        Value::Array(requests) if !requests.is_empty() => {
+            let responses = requests
+                .into_iter()
+                .filter_map(|request| handle_request(node, request))
+                .collect::<Vec<_>>();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => handle_request(node, request),
+    }
+}
+
+/// Handle a single request, and return its response unless it is a notification.
+fn handle_request(node: &Mutex<Node>, request: Value) -> Option<Value> {
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            let error = RpcError::new(INVALID_REQUEST, "message is not a JSON-RPC 2.0 request");
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+    let result = Params::new(request.params).and_then(|params| call(node, &request.method, params));
+    request.id.map(|id| response(id, result))
+}
+
+/// Call `method` on `node` with `params`.
+fn call(node: &Mutex<Node>, method: &str, params: Params) -> Result<Value, RpcError> {
+    let mut node = node
+        .lock()
+        .expect("no thread panics while holding the node; qed");
+    match method {
+        "author_submitExtrinsic" => {
+            let extrinsic = params.required::<types::Extrinsic>(0)?;
+            let hash = node.submit(extrinsic).map_err(|error| match error {
+                node::Error::Pool(error) => RpcError::new(INVALID_EXTRINSIC, <&str>::from(error)),
+                error => RpcError::new(INTERNAL_ERROR, <&str>::from(error)),
+            })?;
+            Ok(json!(merkle::to_hex(&hash)))
+        }
+        "chain_getBlock" => {
+            let hash = block_hash(&node, params.optional(0)?)?;
+            to_value(node.chain().block_by_hash(&hash))
+        }
+        "chain_getHeader" => {
+            let hash = block_hash(&node, params.optional(0)?)?;
+            let block = node.chain().block_by_hash(&hash);
+            to_value(block.map(|block| &block.header))
+        }
+        "state_getBalance" => {
+            let who = params.required::<types::AccountId>(0)?;
+            to_value(node.chain().runtime().balances.balance(&who))
+        }
+        "state_getClaim" => {
+            let claim = params.required::<types::Content>(0)?;
+            let runtime = node.chain().runtime();
+            to_value(runtime.proof_of_existence.get_claim_info(&claim))
+        }
+        "system_accountNonce" => {
+            let who = params.required::<types::AccountId>(0)?;
+            to_value(node.next_nonce(&who))
+        }
+        "state_getMetadata" => to_value(Runtime::metadata()),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("method {} does not exist", method),
+        )),
+    }
+}
+
+/// The hash of the block given by its hexadecimal `hash`, or of the best block if it is `None`.
+fn block_hash(node: &Node, hash: Option<String>) -> Result<merkle::Hash, RpcError> {
+    match hash {
+        Some(hash) => merkle::from_hex(&hash)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "block hash is not 32 bytes of hex")),
+        None => Ok(node.chain().best_hash()),
+    }
+}
+
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|error| RpcError::new(INTERNAL_ERROR, error.to_string()))
+}
+
+/// The response to the request with `id`.
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": id }),
+    }
+}
+
+/// A JSON-RPC 2.0 server for a `Node`, over TCP.
+///
+/// Every message is a single line of JSON, and so is every response. The node is shared with the
+/// rest of the process, which can keep producing blocks while the server handles requests.
+#[derive(Debug)]
+pub struct RpcServer {
+    listener: TcpListener,
+    node: Arc<Mutex<Node>>,
+}
+
+impl RpcServer {
+    /// Listen for clients on `address`, usually a local address like `127.0.0.1:9933`.
+    pub fn bind(address: impl ToSocketAddrs, node: Arc<Mutex<Node>>) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        Ok(Self { listener, node })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept clients forever, handling each of them on its own thread.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            // A client which failed to connect does not stop the server.
+            let Ok(stream) = stream else { continue };
+            let node = self.node.clone();
+            std::thread::spawn(move || serve_client(&node, stream));
+        }
+    }
+}
+
+/// Handle the messages of a client until it disconnects.
+fn serve_client(node: &Mutex<Node>, stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_message(node, &line) {
+            writeln!(writer, "{}", response)?;
+        }
+    }
+    Ok(())
+}
+
+/// The errors which can be returned by an `RpcClient`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The connection to the server failed.
+    Io(io::Error),
+    /// The server sent a response which is not valid JSON-RPC.
+    InvalidResponse,
+    /// The server returned an error for the request.
+    Rpc(RpcError),
+}
+
+impl From<io::Error> for ClientError {
+    fn from(error: io::Error) -> Self {
+        ClientError::Io(error)
+    }
+}
+
+/// A client of an `RpcServer`, sending one request at a time.
+#[derive(Debug)]
+pub struct RpcClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// The id of the next request.
+    next_id: u64,
+}
+
+impl RpcClient {
+    /// Connect to the server listening on `address`.
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let writer = TcpStream::connect(address)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self {
+            reader,
+            writer,
+            next_id: 0,
+        })
+    }
+
+    /// Call `method` with the positional `params`, and wait for its result.
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id });
+        writeln!(self.writer, "{}", request)?;
+
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let mut response =
+            serde_json::from_str::<Value>(&line).map_err(|_| ClientError::InvalidResponse)?;
+        if response["id"] != json!(id) {
+            return Err(ClientError::InvalidResponse);
+        }
+        match (response["result"].take(), response["error"].take()) {
+            (result, Value::Null) => Ok(result),
+            (Value::Null, error) => Err(ClientError::Rpc(
+                serde_json::from_value(error).map_err(|_| ClientError::InvalidResponse)?,
+            )),
+            _ => Err(ClientError::InvalidResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+
+    use super::{ClientError, RpcClient, RpcError, RpcServer};
+    use crate::crypto::Pair;
+    use crate::merkle;
+    use crate::node::Node;
+    use crate::runtime::{types, BalancesCall};
+
+    fn genesis() -> String {
+        let alice = Pair::from_seed("alice").public();
+        format!(
+            r#"{{
+                "balances": {{ "balances": [["alice", 100]] }},
+                "reward_points": {{ "balances": [] }},
+                "accounts": {{ "keys": [["alice", "{}"]] }}
+            }}"#,
+            alice
+        )
+    }
+
+    fn transfer(nonce: u32, amount: u128) -> Value {
+        let call = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount,
+        };
+        let extrinsic = types::Extrinsic::new(String::from("alice"), nonce, call)
+            .sign(&Pair::from_seed("alice"));
+        serde_json::to_value(extrinsic).unwrap()
+    }
+
+    fn error_code(result: Result<Value, ClientError>) -> i64 {
+        match result {
+            Err(ClientError::Rpc(RpcError { code, .. })) => code,
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    /// This test checks the following:
+    /// - A client submits extrinsics, and queries blocks, headers, balances, claims, nonces and
+    ///   metadata over TCP, while blocks are produced on the shared node.
+    /// - Unknown methods, invalid parameters and rejected extrinsics return their error codes.
+    /// - Batches are answered with one response per request, notifications get no response, unlike
+    ///   requests with a `null` id, and malformed messages get an error.
+    #[test]
+    fn serve_client() {
+        let dir = std::env::temp_dir().join(format!("rpc_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let node = Arc::new(Mutex::new(Node::init(&dir, &genesis()).unwrap()));
+        let server = RpcServer::bind("127.0.0.1:0", node.clone()).unwrap();
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+        let mut client = RpcClient::connect(address).unwrap();
+
+        let hash = client
+            .call("author_submitExtrinsic", json!([transfer(0, 30)]))
+            .unwrap();
+        assert_eq!(hash.as_str().map(str::len), Some(64));
+        assert_eq!(
+            client
+                .call("system_accountNonce", json!(["alice"]))
+                .unwrap(),
+            json!(1)
+        );
+        assert_eq!(
+            error_code(client.call("author_submitExtrinsic", json!([transfer(0, 30)]))),
+            super::INVALID_EXTRINSIC
+        );
+
+        let (block_hash, header) = {
+            let mut node = node.lock().unwrap();
+            let (block, _) = node.produce_block().unwrap();
+            (merkle::to_hex(&block.header.hash()), block.header.clone())
+        };
+        assert_eq!(
+            client.call("chain_getHeader", json!([])).unwrap(),
+            serde_json::to_value(&header).unwrap()
+        );
+        let block = client.call("chain_getBlock", json!([block_hash])).unwrap();
+        assert_eq!(block["extrinsics"], json!([transfer(0, 30)]));
+        assert_eq!(
+            client
+                .call("chain_getBlock", json!([merkle::to_hex(&[0; 32])]))
+                .unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            client.call("state_getBalance", json!(["bob"])).unwrap(),
+            json!(30)
+        );
+        assert_eq!(
+            client.call("state_getClaim", json!(["document"])).unwrap(),
+            Value::Null
+        );
+        let metadata = client.call("state_getMetadata", json!([])).unwrap();
+        assert_eq!(metadata["pallets"][0]["name"], json!("balances"));
+
+        assert_eq!(
+            error_code(client.call("state_getBalances", json!(["bob"]))),
+            super::METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            error_code(client.call("state_getBalance", json!([]))),
+            super::INVALID_PARAMS
+        );
+        assert_eq!(
+            error_code(client.call("chain_getBlock", json!(["not a hash"]))),
+            super::INVALID_PARAMS
+        );
+
+        let node = &*node;
+        let batch = r#"[
+            {"jsonrpc": "2.0", "method": "state_getBalance", "params": ["alice"], "id": "a"},
+            {"jsonrpc": "2.0", "method": "state_getBalance", "params": ["bob"]},
+            {"jsonrpc": "1.0", "method": "state_getBalance", "params": ["bob"], "id": 2}
+        ]"#;
+        let responses = super::handle_message(node, batch).unwrap();
+        assert_eq!(
+            responses[0],
+            json!({"jsonrpc": "2.0", "result": 70, "id": "a"})
+        );
+        assert_eq!(responses[1]["error"]["code"], json!(super::INVALID_REQUEST));
+        assert_eq!(responses.as_array().map(Vec::len), Some(2));
+        let notification = r#"{"jsonrpc": "2.0", "method": "state_getMetadata"}"#;
+        assert_eq!(super::handle_message(node, notification), None);
+        let null_id = r#"{"jsonrpc": "2.0", "method": "state_getBalance", "params": ["alice"],
+            "id": null}"#;
+        assert_eq!(
+            super::handle_message(node, null_id),
+            Some(json!({"jsonrpc": "2.0", "result": 70, "id": null}))
+        );
+        let malformed = super::handle_message(node, "{").unwrap();
+        assert_eq!(malformed["error"]["code"], json!(super::PARSE_ERROR));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
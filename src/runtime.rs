@@ -0,0 +1,603 @@
+use crate::{accounts, balances, call_filter, crypto, proof_of_existence, support, system};
+
+pub mod types {
+    pub type AccountId = String;
+    pub type BlockNumber = u32;
+    pub type Nonce = u32;
+    pub type Extrinsic = crate::support::Extrinsic<AccountId, Nonce, super::RuntimeCall>;
+    pub type Header = crate::support::Header<BlockNumber>;
+    pub type Block = crate::support::Block<Header, Extrinsic>;
+    pub type Content = String;
+    pub type ClaimInfo = crate::proof_of_existence::ClaimInfo<AccountId, BlockNumber>;
+}
+
+// This is our main Runtime.
+// It accumulates all of the different pallets we want to use.
+#[derive(Debug, Clone)]
+#[macros::runtime]
+pub struct Runtime {
+    pub system: system::Pallet<Self>,
+    #[pallet(calls, events, genesis, hooks)]
+    pub balances: balances::Pallet<Self>,
+    #[pallet(calls, events, hooks, versioned)]
+    pub proof_of_existence: proof_of_existence::Pallet<Self>,
+    #[pallet(calls, events)]
+    pub call_filter: call_filter::Pallet<Self>,
+    #[pallet(calls, events, genesis, hooks)]
+    pub reward_points: balances::Pallet<Self, RewardPoints>,
+    #[pallet(calls, events, genesis)]
+    pub accounts: accounts::Pallet<Self>,
+}
+
+impl system::Config for Runtime {
+    type AccountId = types::AccountId;
+    type BlockNumber = types::BlockNumber;
+    type Nonce = types::Nonce;
+}
+
+impl balances::Config for Runtime {
+    type Balance = u128;
+}
+
+/// The instance of the Balances pallet keeping track of reward points, separately from the
+/// balances of the main token.
+#[derive(Debug, Clone)]
+pub struct RewardPoints;
+
+impl balances::Config<RewardPoints> for Runtime {
+    type Balance = u64;
+}
+
+// The calls of the two instances of the Balances pallet can not be told apart from their arguments
+// alone, so these aliases name them, like in
+// `Extrinsic::new(alice, 0, BalancesCall::transfer { .. })`.
+
+/// The calls of the Balances pallet.
+pub type BalancesCall = balances::Call<Runtime>;
+/// The calls of the reward points instance of the Balances pallet.
+pub type RewardPointsCall = balances::Call<Runtime, RewardPoints>;
+
+/// The maximum number of claims a single account can own in the Proof of Existence pallet.
+pub struct MaxClaimsPerAccount;
+
+impl support::Get<u32> for MaxClaimsPerAccount {
+    fn get() -> u32 {
+        100
+    }
+}
+
+/// The number of blocks approvals on a joint claim of the Proof of Existence pallet stay valid for.
+pub struct ApprovalPeriod;
+
+impl support::Get<types::BlockNumber> for ApprovalPeriod {
+    fn get() -> types::BlockNumber {
+        10
+    }
+}
+
+impl proof_of_existence::Config for Runtime {
+    type Content = types::Content;
+    type MaxClaimsPerAccount = MaxClaimsPerAccount;
+    type ApprovalPeriod = ApprovalPeriod;
+}
+
+/// The account allowed to disable and enable calls with the Call Filter pallet, and to set the key
+/// of any account with the Accounts pallet.
+pub struct Root;
+
+impl support::Get<types::AccountId> for Root {
+    fn get() -> types::AccountId {
+        String::from("root")
+    }
+}
+
+impl call_filter::Config for Runtime {
+    type Root = Root;
+}
+
+impl accounts::Config for Runtime {
+    type Root = Root;
+}
+
+impl support::AccountKeys<types::AccountId> for Runtime {
+    fn account_key(&self, caller: &types::AccountId) -> Option<crypto::PublicKey> {
+        self.accounts.account_key(caller).copied()
+    }
+}
+
+impl support::CallFilter<RuntimeCall> for Runtime {
+    fn contains(&self, call: &RuntimeCall) -> bool {
+        match call {
+            // The calls of the Call Filter pallet are never filtered, so root can always enable
+            // what it disabled.
+            RuntimeCall::call_filter(_) => true,
+            call => !self
+                .call_filter
+                .is_disabled(call.pallet_name(), call.call_name()),
+        }
+    }
+}
+
+impl support::ChargeTransaction<types::AccountId, RuntimeCall> for Runtime {
+    // Calls have no fees of their own on this chain: the caller only pays the tip, in balances.
+    fn can_pay(
+        &self,
+        caller: &types::AccountId,
+        _call: &RuntimeCall,
+        tip: support::Tip,
+    ) -> support::DispatchResult {
+        self.balances.ensure_can_withdraw(caller, tip)
+    }
+
+    fn withdraw_fee(
+        &mut self,
+        caller: &types::AccountId,
+        _call: &RuntimeCall,
+        tip: support::Tip,
+    ) -> support::DispatchResult {
+        self.balances.withdraw(caller, tip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{types, BalancesCall, RewardPointsCall};
+    use super::{Runtime, RuntimeCall, RuntimeEvent, RuntimeGenesisConfig};
+    use crate::crypto::Pair;
+    use crate::support::{Dispatch, IsSubType, BAD_SIGNATURE, CALL_FILTERED, INVALID_NONCE};
+    use crate::{accounts, balances, call_filter, proof_of_existence};
+
+    /// This test checks the following:
+    /// - Calls disabled by root through the Call Filter pallet are rejected with `CALL_FILTERED`.
+    /// - Other calls are still dispatched, and disabled calls can be enabled again.
+    /// - The calls of the Call Filter pallet itself can not be disabled.
+    #[test]
+    fn call_filter() {
+        let alice = String::from("alice");
+        let root = String::from("root");
+        let mut runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&[]),
+        });
+        let claim = || {
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                claim: String::from("document"),
+            })
+        };
+        let transfer = || {
+            RuntimeCall::balances(balances::Call::transfer {
+                to: String::from("bob"),
+                amount: 10,
+            })
+        };
+        let filter = |pallet: &str, call: Option<&str>, enable: bool| {
+            let (pallet, call) = (pallet.to_string(), call.map(String::from).into());
+            RuntimeCall::call_filter(match enable {
+                true => call_filter::Call::enable { pallet, call },
+                false => call_filter::Call::disable { pallet, call },
+            })
+        };
+
+        assert_eq!(
+            runtime.dispatch(alice.clone(), filter("proof_of_existence", None, false)),
+            Err("caller is not the root account")
+        );
+        assert_eq!(
+            runtime.dispatch(root.clone(), filter("proof_of_existence", None, false)),
+            Ok(())
+        );
+        assert_eq!(runtime.dispatch(alice.clone(), claim()), Err(CALL_FILTERED));
+        assert_eq!(runtime.dispatch(alice.clone(), transfer()), Ok(()));
+
+        assert_eq!(
+            runtime.dispatch(root.clone(), filter("balances", Some("transfer"), false)),
+            Ok(())
+        );
+        assert_eq!(
+            runtime.dispatch(alice.clone(), transfer()),
+            Err(CALL_FILTERED)
+        );
+        assert_eq!(runtime.balances.balance(&alice), 90);
+
+        assert_eq!(
+            runtime.dispatch(root.clone(), filter("call_filter", None, false)),
+            Ok(())
+        );
+        assert_eq!(
+            runtime.dispatch(root.clone(), filter("proof_of_existence", None, true)),
+            Ok(())
+        );
+        assert_eq!(runtime.dispatch(alice.clone(), claim()), Ok(()));
+        assert_eq!(
+            runtime
+                .proof_of_existence
+                .get_claim(&String::from("document")),
+            Some(&alice)
+        );
+    }
+
+    /// This test checks the following:
+    /// - The main token and the reward points are independent instances of the Balances pallet.
+    /// - Their calls and events have distinct variants in the runtime.
+    #[test]
+    fn reward_points() {
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        let mut runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 5)],
+            },
+            accounts: accounts::GenesisConfig::dev(&[]),
+        });
+
+        let points = RuntimeCall::reward_points(balances::Call::transfer {
+            to: bob.clone(),
+            amount: 3,
+        });
+        assert_eq!(
+            points.to_string(),
+            "reward_points.transfer(to=bob, amount=3)"
+        );
+        assert_eq!(runtime.dispatch(alice.clone(), points), Ok(()));
+
+        assert_eq!(runtime.reward_points.balance(&alice), 2);
+        assert_eq!(runtime.reward_points.balance(&bob), 3);
+        assert_eq!(runtime.balances.balance(&alice), 100);
+        assert_eq!(runtime.balances.balance(&bob), 0);
+
+        let events = runtime.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [RuntimeEvent::reward_points(balances::Event::Transfer {
+                amount: 3,
+                ..
+            })]
+        ));
+
+        let metadata = Runtime::metadata();
+        assert_eq!(metadata.pallets[3].name, "reward_points");
+        assert_eq!(metadata.pallets[3].calls, metadata.pallets[0].calls);
+    }
+
+    /// This test checks the following:
+    /// - The call of a pallet converts into a `RuntimeCall`, and back only into the same pallet.
+    /// - `IsSubType` looks at the call of a pallet without taking the `RuntimeCall` apart.
+    /// - `Extrinsic::new` builds an extrinsic from the call of a pallet, and its signature covers its
+    ///   tip.
+    #[test]
+    fn call_conversions() {
+        let alice = String::from("alice");
+        let transfer = BalancesCall::transfer {
+            to: String::from("bob"),
+            amount: 10,
+        };
+        let claim = proof_of_existence::Call::create_claim {
+            claim: String::from("document"),
+        };
+
+        let call = RuntimeCall::from(transfer.clone());
+        assert_eq!(call, RuntimeCall::balances(transfer.clone()));
+        assert_eq!(
+            IsSubType::<BalancesCall>::is_sub_type(&call),
+            Some(&transfer)
+        );
+        assert_eq!(IsSubType::<RewardPointsCall>::is_sub_type(&call), None);
+        assert_eq!(BalancesCall::try_from(call.clone()), Ok(transfer.clone()));
+        assert_eq!(RewardPointsCall::try_from(call.clone()), Err(call));
+
+        let pair = Pair::from_seed("alice");
+        let extrinsic = types::Extrinsic::new(alice.clone(), 3, claim.clone())
+            .with_tip(5)
+            .sign(&pair);
+        assert_eq!(extrinsic.caller, alice);
+        assert_eq!((extrinsic.nonce, extrinsic.tip), (3, 5));
+        assert_eq!(extrinsic.call, RuntimeCall::proof_of_existence(claim));
+        assert!(extrinsic.is_signed_by(&pair.public()));
+        assert!(!extrinsic.with_tip(6).is_signed_by(&pair.public()));
+    }
+
+    /// This test checks the following:
+    /// - `execute_block` reports the outcome, weight and events of each extrinsic.
+    /// - A failing extrinsic does not make the block invalid, but a wrong block number does, and the
+    ///   block number is left unchanged.
+    /// - The tip of an extrinsic is paid even if its call fails, and an extrinsic whose tip can not be
+    ///   paid is skipped.
+    /// - An extrinsic signed with the key of another account than its caller, or changed after it
+    ///   was signed, is skipped with `BAD_SIGNATURE`.
+    #[test]
+    fn block_receipt() {
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        let mut runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice", "bob"]),
+        });
+        let transfer = |amount| BalancesCall::transfer {
+            to: bob.clone(),
+            amount,
+        };
+        let (alice_pair, bob_pair) = (Pair::from_seed("alice"), Pair::from_seed("bob"));
+        // Signed by `alice`, then changed to transfer more.
+        let mut forged = types::Extrinsic::new(alice.clone(), 1, transfer(10)).sign(&alice_pair);
+        forged.call = transfer(60).into();
+
+        let receipt = runtime
+            .execute_block(types::Block {
+                header: crate::support::Header {
+                    block_number: 1,
+                    ..Default::default()
+                },
+                extrinsics: vec![
+                    types::Extrinsic::new(alice.clone(), 0, transfer(30))
+                        .with_tip(5)
+                        .sign(&alice_pair),
+                    types::Extrinsic::new(bob.clone(), 0, transfer(50)).sign(&bob_pair),
+                    types::Extrinsic::new(bob.clone(), 1, transfer(10))
+                        .with_tip(40)
+                        .sign(&bob_pair),
+                    // Signed by `bob` on behalf of `alice`.
+                    types::Extrinsic::new(alice.clone(), 1, transfer(10)).sign(&bob_pair),
+                    forged,
+                ],
+            })
+            .unwrap();
+
+        assert_eq!(receipt.extrinsics.len(), 5);
+        let (ok, failed) = (&receipt.extrinsics[0], &receipt.extrinsics[1]);
+        assert_eq!((ok.index, ok.result, ok.weight), (0, Ok(()), 10_000));
+        assert!(matches!(
+            ok.events.as_slice(),
+            [RuntimeEvent::balances(balances::Event::Transfer {
+                amount: 30,
+                ..
+            })]
+        ));
+        assert_eq!(failed.index, 1);
+        assert_eq!(failed.result, Err("error subtracting balance"));
+        assert!(failed.events.is_empty());
+        let unpaid = &receipt.extrinsics[2];
+        assert_eq!(
+            (unpaid.result, unpaid.weight),
+            (Err("insufficient balance"), 0)
+        );
+        for unsigned in &receipt.extrinsics[3..] {
+            assert_eq!((unsigned.result, unsigned.weight), (Err(BAD_SIGNATURE), 0));
+        }
+        assert_eq!(receipt.weight, 20_000);
+        assert_eq!(runtime.balances.balance(&alice), 65);
+        assert_eq!(runtime.system.nonce(&alice), 1);
+        assert_eq!(runtime.system.nonce(&bob), 1);
+        assert!(receipt.initialize_events.is_empty() && receipt.finalize_events.is_empty());
+
+        let invalid = runtime.execute_block(types::Block {
+            header: crate::support::Header {
+                block_number: 3,
+                ..Default::default()
+            },
+            extrinsics: vec![],
+        });
+        assert_eq!(
+            invalid.err(),
+            Some("block number does not match what is expected")
+        );
+        assert_eq!(runtime.system.block_number(), 1);
+    }
+
+    /// This test checks the following:
+    /// - `dry_run` reports the outcome, weight, events and state changes of an extrinsic, including
+    ///   its fees, without changing the runtime.
+    /// - `simulate_block` reports the receipt and state changes of a block, without changing the
+    ///   runtime.
+    #[test]
+    fn dry_run_and_simulate() {
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        let runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice", "bob"]),
+        });
+        let state = runtime.state();
+        let transfer = |nonce, amount| {
+            let call = BalancesCall::transfer {
+                to: bob.clone(),
+                amount,
+            };
+            types::Extrinsic::new(alice.clone(), nonce, call)
+                .with_tip(5)
+                .sign(&Pair::from_seed("alice"))
+        };
+
+        let dry_run = runtime.dry_run(transfer(0, 30));
+        assert_eq!((dry_run.result, dry_run.weight), (Ok(()), 10_000));
+        assert_eq!(dry_run.events.len(), 1);
+        let change = |key: &str| {
+            let change = &dry_run.state_diff[key];
+            (change.before.as_deref(), change.after.as_deref())
+        };
+        assert_eq!(
+            change(r#"balances.balances/"alice""#),
+            (Some("100"), Some("65"))
+        );
+        assert_eq!(change(r#"balances.balances/"bob""#), (None, Some("30")));
+        assert_eq!(change(r#"system.nonce/"alice""#), (None, Some("1")));
+        assert_eq!(dry_run.state_diff.len(), 4);
+
+        let failed = runtime.dry_run(transfer(0, 100));
+        assert_eq!(failed.result, Err("error subtracting balance"));
+        assert!(failed.events.is_empty());
+        // The tip is paid even though the transfer fails.
+        assert!(failed
+            .state_diff
+            .contains_key(r#"balances.balances/"alice""#));
+        let invalid = runtime.dry_run(transfer(1, 30));
+        assert_eq!((invalid.result, invalid.weight), (Err(INVALID_NONCE), 0));
+        assert!(invalid.state_diff.is_empty());
+
+        let simulation = runtime
+            .simulate_block(types::Block {
+                header: crate::support::Header {
+                    block_number: 1,
+                    ..Default::default()
+                },
+                extrinsics: vec![transfer(0, 30), transfer(1, 30)],
+            })
+            .unwrap();
+        assert_eq!(simulation.receipt.extrinsics.len(), 2);
+        let alice_balance = &simulation.state_diff[r#"balances.balances/"alice""#];
+        assert_eq!(alice_balance.after.as_deref(), Some("30"));
+        assert!(simulation.state_diff.contains_key("system.block_number"));
+
+        assert_eq!(runtime.state(), state);
+        assert_eq!(runtime.balances.balance(&alice), 100);
+        assert_eq!(runtime.system.block_number(), 0);
+    }
+
+    /// This test checks the following:
+    /// - The state root after a block commits to the balances and claims of the runtime.
+    /// - `read_proof` proves the value of a balance or a claim, or the absence of a claim, against
+    ///   the state root.
+    #[test]
+    fn state_proofs() {
+        use crate::sparse_merkle::verify;
+        use crate::storage::map_key;
+
+        let alice = String::from("alice");
+        let mut runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice", "bob"]),
+        });
+        let genesis_root = runtime.state_root();
+
+        let claim = proof_of_existence::Call::create_claim {
+            claim: String::from("document"),
+        };
+        let receipt = runtime
+            .execute_block(types::Block {
+                header: crate::support::Header {
+                    block_number: 1,
+                    ..Default::default()
+                },
+                extrinsics: vec![types::Extrinsic::new(alice.clone(), 0, claim)
+                    .with_tip(10)
+                    .sign(&Pair::from_seed("alice"))],
+            })
+            .unwrap();
+        let root = receipt.state_root;
+        assert_eq!(root, runtime.state_root());
+        assert_ne!(root, genesis_root);
+
+        let balance_key = map_key("balances.balances", &alice);
+        let (balance, proof) = runtime.read_proof(&balance_key);
+        assert_eq!(balance.as_deref(), Some("90"));
+        assert!(verify(&root, &balance_key, Some("90"), &proof));
+        assert!(!verify(&root, &balance_key, Some("100"), &proof));
+        assert!(!verify(&genesis_root, &balance_key, Some("90"), &proof));
+
+        let claim_key = map_key("proof_of_existence.claims", "document");
+        let (claim, proof) = runtime.read_proof(&claim_key);
+        assert_eq!(
+            claim.as_deref(),
+            Some(r#"{"owner":"alice","created_at":1}"#)
+        );
+        assert!(verify(&root, &claim_key, claim.as_deref(), &proof));
+
+        let missing_key = map_key("proof_of_existence.claims", "missing");
+        let (missing, proof) = runtime.read_proof(&missing_key);
+        assert_eq!(missing, None);
+        assert!(verify(&root, &missing_key, None, &proof));
+    }
+
+    /// This test checks the following:
+    /// - A runtime is decoded back from its own state, and a state holding unknown or undecodable
+    ///   values is rejected.
+    /// - `upgrade` migrates the claims of a state stored before the Proof of Existence pallet was
+    ///   versioned, and records its new storage version.
+    /// - `upgrade` rejects a state stored by a newer version of a pallet.
+    #[test]
+    fn storage_upgrade() {
+        use crate::storage::{map_key, UNDECODABLE_STATE};
+        use crate::support::Versioned;
+
+        let alice = String::from("alice");
+        let document = String::from("document");
+        let mut runtime = Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(alice.clone(), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice", "bob"]),
+        });
+        let claim = proof_of_existence::Call::create_claim {
+            claim: document.clone(),
+        };
+        runtime
+            .execute_block(types::Block {
+                header: crate::support::Header {
+                    block_number: 1,
+                    ..Default::default()
+                },
+                extrinsics: vec![
+                    types::Extrinsic::new(alice.clone(), 0, claim).sign(&Pair::from_seed("alice"))
+                ],
+            })
+            .unwrap();
+
+        let state = runtime.state();
+        let decoded = Runtime::from_state(&state).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", runtime));
+        assert_eq!(decoded.state_root(), runtime.state_root());
+        let mut unknown = state.clone();
+        unknown.insert("balances.reserved".to_string(), "0".to_string());
+        assert!(Runtime::from_state(&unknown).is_err());
+
+        // The same state, as stored before claims recorded when they were created.
+        let version_key = map_key("system.storage_versions", "proof_of_existence");
+        let claim_key = map_key("proof_of_existence.claims", &document);
+        let mut old_state = state.clone();
+        old_state.remove(&version_key);
+        old_state.insert(claim_key.clone(), r#""alice""#.to_string());
+        assert_eq!(
+            Runtime::from_state(&old_state).err(),
+            Some(UNDECODABLE_STATE)
+        );
+
+        let upgraded = Runtime::upgrade(old_state).unwrap();
+        assert_eq!(upgraded.state(), state);
+        assert_eq!(
+            upgraded
+                .system
+                .storage_version(&String::from("proof_of_existence")),
+            <proof_of_existence::Pallet<Runtime> as Versioned>::STORAGE_VERSION
+        );
+        assert_eq!(
+            upgraded
+                .proof_of_existence
+                .get_claim_info(&document)
+                .map(|info| info.created_at),
+            Some(1)
+        );
+        assert!(Runtime::upgrade(state.clone()).is_ok());
+
+        let mut newer_state = state;
+        newer_state.insert(version_key, "4".to_string());
+        assert_eq!(
+            Runtime::upgrade(newer_state).err(),
+            Some("no migration to the current storage version of the pallet")
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::merkle::{self, Hash};
+use crate::storage::State;
+
+/// The number of levels of the tree: one per bit of the hash of a key.
+const DEPTH: usize = 256;
+
+/// The hash of an empty subtree, at every level of the tree.
+const EMPTY: Hash = [0; 32];
+
+/// Domain separation prefixes, so that a leaf can never be confused with an inner node.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn hash_leaf(path: &Hash, value: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(path);
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    // Empty subtrees stay empty all the way up, so the tree never hashes them.
+    if *left == EMPTY && *right == EMPTY {
+        return EMPTY;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// The bit of `path` at `depth`, from the most significant bit of the first byte. A set bit goes
+/// to the right.
+fn bit(path: &Hash, depth: usize) -> bool {
+    path[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// The position of a key in the tree: the hash of the key.
+fn path_of(key: &str) -> Hash {
+    merkle::hash(key.as_bytes())
+}
+
+/// A proof that a key has a value in the tree with a given root, or that it has none.
+///
+/// It holds the siblings of the path of the key which are not empty, by level, from the root
+/// (level 0) down to the leaf.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize)]
+pub struct StorageProof {
+    pub siblings: Vec<(u8, Hash)>,
+}
+
+/// A sparse Merkle tree committing to every value of a `State`.
+///
+/// Each value is a leaf at the path given by the hash of its key, among 2^256 possible paths. All
+/// the other leaves are empty, which lets the tree prove that a key has no value as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseMerkleTree {
+    /// The hash of the value of every leaf, by path.
+    leaves: BTreeMap<Hash, Hash>,
+}
+
+impl SparseMerkleTree {
+    /// Build the tree over all the values of `state`.
+    pub fn from_state(state: &State) -> Self {
+        let leaves = state
+            .iter()
+            .map(|(key, value)| (path_of(key), merkle::hash(value.as_bytes())))
+            .collect();
+        Self { leaves }
+    }
+
+    /// The root of the tree, which commits to all of the values. The root of an empty tree is
+    /// zero.
+    pub fn root(&self) -> Hash {
+        let leaves = self.leaves.iter().collect::<Vec<_>>();
+        subtree_root(&leaves, 0)
+    }
+
+    /// Generate the proof of the value of `key`, or that it has none.
+    pub fn proof(&self, key: &str) -> StorageProof {
+        let path = path_of(key);
+        let mut leaves = &self.leaves.iter().collect::<Vec<_>>()[..];
+        let mut proof = StorageProof::default();
+        for depth in 0..DEPTH {
+            let (left, right) = leaves.split_at(leaves.partition_point(|(p, _)| !bit(p, depth)));
+            let (next, sibling) = match bit(&path, depth) {
+                true => (right, left),
+                false => (left, right),
+            };
+            let sibling = subtree_root(sibling, depth + 1);
+            if sibling != EMPTY {
+                proof.siblings.push((depth as u8, sibling));
+            }
+            leaves = next;
+        }
+        proof
+    }
+}
+
+/// The root of the subtree at `depth` holding `leaves`, which are sorted by path and all share the
+/// first `depth` bits of their path.
+fn subtree_root(leaves: &[(&Hash, &Hash)], depth: usize) -> Hash {
+    match leaves {
+        [] => EMPTY,
+        [(path, value)] if depth == DEPTH => hash_leaf(path, value),
+        _ => {
+            let (left, right) = leaves.split_at(leaves.partition_point(|(p, _)| !bit(p, depth)));
+            hash_node(
+                &subtree_root(left, depth + 1),
+                &subtree_root(right, depth + 1),
+            )
+        }
+    }
+}
+
+/// Check that `key` has the encoded `value` in the tree with the given `root`, or no value if
+/// `value` is `None`.
+pub fn verify(root: &Hash, key: &str, value: Option<&str>, proof: &StorageProof) -> bool {
+    let path = path_of(key);
+    let siblings = proof.siblings.iter().copied().collect::<BTreeMap<_, _>>();
+    if siblings.len() != proof.siblings.len() {
+        return false;
+    }
+
+    let leaf = match value {
+        Some(value) => hash_leaf(&path, &merkle::hash(value.as_bytes())),
+        None => EMPTY,
+    };
+    let computed = (0..DEPTH).rev().fold(leaf, |current, depth| {
+        let sibling = siblings.get(&(depth as u8)).unwrap_or(&EMPTY);
+        match bit(&path, depth) {
+            true => hash_node(sibling, &current),
+            false => hash_node(&current, sibling),
+        }
+    });
+    computed == *root
+}
+
+#[cfg(test)]
+mod test {
+    use super::{verify, SparseMerkleTree};
+    use crate::storage::State;
+
+    fn state(entries: &[(&str, &str)]) -> State {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Checks the following:
+    /// - The root of an empty tree is zero, and the root changes with any of the values.
+    /// - Every value, and the absence of a value, can be proven against the root.
+    /// - A proof does not verify for a different value, or a different root.
+    #[test]
+    fn storage_proofs() {
+        assert_eq!(SparseMerkleTree::from_state(&State::new()).root(), [0; 32]);
+
+        let entries = [
+            ("alice", "100"),
+            ("bob", "50"),
+            ("charlie", "0"),
+            ("dave", "7"),
+        ];
+        let tree = SparseMerkleTree::from_state(&state(&entries));
+        let root = tree.root();
+
+        for (key, value) in entries {
+            let proof = tree.proof(key);
+            assert!(verify(&root, key, Some(value), &proof));
+            assert!(!verify(&root, key, Some("1"), &proof));
+            assert!(!verify(&root, key, None, &proof));
+        }
+        let proof = tree.proof("eve");
+        assert!(verify(&root, "eve", None, &proof));
+        assert!(!verify(&root, "eve", Some("100"), &proof));
+
+        let changed = state(&[
+            ("alice", "100"),
+            ("bob", "51"),
+            ("charlie", "0"),
+            ("dave", "7"),
+        ]);
+        let changed_root = SparseMerkleTree::from_state(&changed).root();
+        assert_ne!(changed_root, root);
+        assert!(!verify(
+            &changed_root,
+            "alice",
+            Some("100"),
+            &tree.proof("alice")
+        ));
+    }
+}
@@ -0,0 +1,884 @@
+use core::cell::Cell;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, PoisonError};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The state of a runtime, as a map from the key of each stored value to the value, both encoded
+/// as JSON.
+///
+/// The key of a value is the path to its storage item, like `balances.balances`, followed by its
+/// keys in a map, like `balances.balances/"alice"`.
+pub type State = BTreeMap<String, String>;
+
+/// A change of a single value between two `State`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    /// The value before the change, if there was one.
+    pub before: Option<String>,
+    /// The value after the change, if there is one.
+    pub after: Option<String>,
+}
+
+/// The changes between two `State`s, by key.
+pub type StateDiff = BTreeMap<String, StateChange>;
+
+/// Compute the changes from the state `before` to the state `after`.
+pub fn diff(before: &State, after: &State) -> StateDiff {
+    let keys = before
+        .keys()
+        .chain(after.keys())
+        .collect::<std::collections::BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| {
+            let change = StateChange {
+                before: before.get(key).cloned(),
+                after: after.get(key).cloned(),
+            };
+            (change.before != change.after).then(|| (key.clone(), change))
+        })
+        .collect()
+}
+
+/// A trait for anything holding storage, which can write its stored values into a `State`.
+///
+/// It is implemented by the storage types, and by pallets with `#[macros::storage]`.
+pub trait EncodeState {
+    /// Write every stored value into `state`, under keys starting with `prefix`.
+    fn encode_state(&self, prefix: &str, state: &mut State);
+}
+
+/// A trait for anything holding storage, which can read its stored values back from a `State`.
+///
+/// It is implemented by the storage types, and by pallets with `#[macros::storage]` whose stored
+/// types can all be decoded.
+pub trait DecodeState {
+    /// Replace the stored values with the ones in `state` under keys starting with `prefix`. A
+    /// `StorageValue` missing from `state` keeps its current value.
+    ///
+    /// Returns an error if one of the values can not be decoded, like when it was stored by an
+    /// older version of a pallet with another storage layout.
+    fn decode_state(&mut self, prefix: &str, state: &State) -> Result<(), &'static str>;
+}
+
+/// The keys of the values of a `State` accessed while recording accesses, see `record_accesses`.
+///
+/// A key ending with `/` stands for all the keys it is a prefix of, like `balances.balances/` when
+/// the whole map is iterated, or `proof_of_existence.owners/"alice"/` when the values of a double
+/// map under a first key are.
+pub type Accesses = BTreeSet<String>;
+
+/// A trait for anything holding storage, which records the values it reads or writes while
+/// recording accesses, and can encode and decode only some of its values.
+///
+/// It is implemented by the storage types, and by pallets with `#[macros::storage]` whose stored
+/// types can all be encoded and decoded.
+pub trait TrackAccesses {
+    /// Add the keys of the values accessed since the last call to `accesses`, under `prefix`, and
+    /// forget them.
+    fn take_accesses(&self, prefix: &str, accesses: &mut Accesses);
+
+    /// Write the values stored under `keys` into `state`, like `EncodeState::encode_state` does
+    /// for all of them. Keys which are not under `prefix` are ignored.
+    fn encode_keys(&self, prefix: &str, keys: &Accesses, state: &mut State);
+
+    /// Replace the values stored under `keys` with the ones in `state`, and remove the ones
+    /// missing from it, except for a `StorageValue` which keeps its current value. Keys which are
+    /// not under `prefix` are ignored.
+    ///
+    /// Returns an error if one of the keys or values can not be decoded.
+    fn decode_keys(
+        &mut self,
+        prefix: &str,
+        keys: &Accesses,
+        state: &State,
+    ) -> Result<(), &'static str>;
+}
+
+thread_local! {
+    /// Whether the storage types record the values accessed on this thread.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, while the storage types record the values it reads or writes, to be taken with
+/// `TrackAccesses::take_accesses`.
+///
+/// Only the accesses made on the current thread are recorded.
+pub fn record_accesses<R>(f: impl FnOnce() -> R) -> R {
+    let previous = RECORDING.with(|recording| recording.replace(true));
+    let result = f();
+    RECORDING.with(|recording| recording.set(previous));
+    result
+}
+
+/// The accesses to a storage item recorded by `record_accesses`, by key.
+///
+/// A clone does not hold any accesses, so a clone of a runtime records its own. The log is behind
+/// a lock only so a runtime can still be shared between threads, it is never contended.
+struct AccessLog<K> {
+    /// Whether all the values of the item were accessed, like when iterating over a map.
+    all: AtomicBool,
+    /// The keys of the values accessed.
+    keys: Mutex<BTreeSet<K>>,
+}
+
+impl<K> AccessLog<K> {
+    fn new() -> Self {
+        Self {
+            all: AtomicBool::new(false),
+            keys: Mutex::new(BTreeSet::new()),
+        }
+    }
+}
+
+impl<K: Ord> AccessLog<K> {
+    /// Record an access to all the values of the item, if recording.
+    fn all(&self) {
+        if RECORDING.with(Cell::get) {
+            self.all.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Record an access to the value under `key()`, if recording.
+    fn key(&self, key: impl FnOnce() -> K) {
+        if RECORDING.with(Cell::get) {
+            let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+            keys.insert(key());
+        }
+    }
+
+    /// Take the recorded accesses, as whether all the values were accessed and the keys of the
+    /// ones which were.
+    fn take(&self) -> (bool, BTreeSet<K>) {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        (
+            self.all.swap(false, Ordering::Relaxed),
+            std::mem::take(&mut *keys),
+        )
+    }
+}
+
+impl<K> Clone for AccessLog<K> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+/// Iterate over the keys of `keys` under `prefix/`, with `prefix/` stripped.
+fn keys_under<'a>(prefix: &str, keys: &'a Accesses) -> impl Iterator<Item = &'a str> {
+    let prefix = format!("{}/", prefix);
+    keys.range(prefix.clone()..)
+        .map_while(move |key| key.strip_prefix(&prefix))
+}
+
+/// The error returned when a key or a value in a `State` does not decode to its stored type.
+pub const UNDECODABLE_STATE: &str = "state can not be decoded to the stored types";
+
+/// Encode a key or a value of storage.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).expect("stored types always serialize")
+}
+
+/// Decode a key or a value of storage.
+pub fn decode<T: DeserializeOwned>(encoded: &str) -> Result<T, &'static str> {
+    serde_json::from_str(encoded).map_err(|_| UNDECODABLE_STATE)
+}
+
+/// Iterate over the values of `state` stored under `prefix/`, along with the rest of their key.
+fn entries<'a>(prefix: &str, state: &'a State) -> impl Iterator<Item = (&'a str, &'a String)> {
+    let prefix = format!("{}/", prefix);
+    state
+        .range(prefix.clone()..)
+        .map_while(move |(key, value)| Some((key.strip_prefix(&prefix)?, value)))
+}
+
+/// Decode the first key from the `rest` of a double map key, and return it with the second key.
+fn split_key<K: DeserializeOwned>(rest: &str) -> Result<(K, &str), &'static str> {
+    let mut keys = serde_json::Deserializer::from_str(rest).into_iter::<K>();
+    let key1 = keys.next().and_then(Result::ok).ok_or(UNDECODABLE_STATE)?;
+    let key2 = rest[keys.byte_offset()..]
+        .strip_prefix('/')
+        .ok_or(UNDECODABLE_STATE)?;
+    Ok((key1, key2))
+}
+
+/// The key in a `State` of the value stored under `key` in the map at `prefix`, like
+/// `map_key("balances.balances", &alice)`.
+pub fn map_key<K: Serialize + ?Sized>(prefix: &str, key: &K) -> String {
+    format!("{}/{}", prefix, encode(key))
+}
+
+/// The key in a `State` of the value stored under `(key1, key2)` in the double map at `prefix`.
+pub fn double_map_key<K1: Serialize + ?Sized, K2: Serialize + ?Sized>(
+    prefix: &str,
+    key1: &K1,
+    key2: &K2,
+) -> String {
+    format!("{}/{}/{}", prefix, encode(key1), encode(key2))
+}
+
+/// A single value in storage.
+///
+/// Pallets usually do not use this directly, but through the functions generated for it by
+/// `#[macros::storage]`.
+#[derive(Clone)]
+pub struct StorageValue<V> {
+    value: V,
+    accesses: AccessLog<()>,
+}
+
+impl<V> StorageValue<V> {
+    /// Create a new storage value, holding `value`.
+    pub fn new(value: V) -> Self {
+        Self {
+            value,
+            accesses: AccessLog::new(),
+        }
+    }
+
+    /// Get the stored value.
+    pub fn get(&self) -> &V {
+        self.accesses.all();
+        &self.value
+    }
+
+    /// Store `value`.
+    pub fn put(&mut self, value: V) {
+        self.accesses.all();
+        self.value = value;
+    }
+
+    /// Mutate the stored value in place.
+    pub fn mutate<R>(&mut self, f: impl FnOnce(&mut V) -> R) -> R {
+        self.accesses.all();
+        f(&mut self.value)
+    }
+
+    /// Store `value`, and return the previously stored value.
+    pub fn replace(&mut self, value: V) -> V {
+        self.accesses.all();
+        core::mem::replace(&mut self.value, value)
+    }
+}
+
+impl<V: Serialize> EncodeState for StorageValue<V> {
+    fn encode_state(&self, prefix: &str, state: &mut State) {
+        state.insert(prefix.to_string(), encode(&self.value));
+    }
+}
+
+impl<V: DeserializeOwned> DecodeState for StorageValue<V> {
+    fn decode_state(&mut self, prefix: &str, state: &State) -> Result<(), &'static str> {
+        if let Some(value) = state.get(prefix) {
+            self.value = decode(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<V: Serialize + DeserializeOwned> TrackAccesses for StorageValue<V> {
+    fn take_accesses(&self, prefix: &str, accesses: &mut Accesses) {
+        if self.accesses.take().0 {
+            accesses.insert(prefix.to_string());
+        }
+    }
+
+    fn encode_keys(&self, prefix: &str, keys: &Accesses, state: &mut State) {
+        if keys.contains(prefix) {
+            self.encode_state(prefix, state);
+        }
+    }
+
+    fn decode_keys(
+        &mut self,
+        prefix: &str,
+        keys: &Accesses,
+        state: &State,
+    ) -> Result<(), &'static str> {
+        if keys.contains(prefix) {
+            self.decode_state(prefix, state)?;
+        }
+        Ok(())
+    }
+}
+
+impl<V: PartialEq> PartialEq for StorageValue<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<V: Eq> Eq for StorageValue<V> {}
+
+impl<V: Debug> Debug for StorageValue<V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// A map from keys to values in storage, ordered by key.
+///
+/// Pallets usually do not use this directly, but through the functions generated for it by
+/// `#[macros::storage]`.
+#[derive(Clone)]
+pub struct StorageMap<K, V> {
+    map: BTreeMap<K, V>,
+    accesses: AccessLog<K>,
+}
+
+impl<K: Ord + Clone, V> StorageMap<K, V> {
+    /// Create a new empty storage map.
+    pub fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            accesses: AccessLog::new(),
+        }
+    }
+
+    /// Get the value stored under `key`, if any.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.accesses.key(|| key.clone());
+        self.map.get(key)
+    }
+
+    /// Check if a value is stored under `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.accesses.key(|| key.clone());
+        self.map.contains_key(key)
+    }
+
+    /// Store `value` under `key`, and return the previously stored value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.accesses.key(|| key.clone());
+        self.map.insert(key, value)
+    }
+
+    /// Remove the value stored under `key`, and return it.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.accesses.key(|| key.clone());
+        self.map.remove(key)
+    }
+
+    /// Mutate the value stored under `key` in place, if any.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.accesses.key(|| key.clone());
+        self.map.get_mut(key)
+    }
+
+    /// Mutate the value stored under `key` in place, first storing `default()` if there is none.
+    pub fn mutate_or_insert<R>(
+        &mut self,
+        key: K,
+        default: impl FnOnce() -> V,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        self.accesses.key(|| key.clone());
+        f(self.map.entry(key).or_insert_with(default))
+    }
+
+    /// Iterate over all the stored keys and values, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.accesses.all();
+        self.map.iter()
+    }
+
+    /// Iterate mutably over all the stored values, ordered by key.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.accesses.all();
+        self.map.values_mut()
+    }
+
+    /// The number of stored values.
+    pub fn len(&self) -> usize {
+        self.accesses.all();
+        self.map.len()
+    }
+
+    /// Check if there are no stored values.
+    pub fn is_empty(&self) -> bool {
+        self.accesses.all();
+        self.map.is_empty()
+    }
+}
+
+impl<K: Ord + Clone, V> Default for StorageMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Serialize, V: Serialize> EncodeState for StorageMap<K, V> {
+    fn encode_state(&self, prefix: &str, state: &mut State) {
+        for (key, value) in &self.map {
+            state.insert(map_key(prefix, key), encode(value));
+        }
+    }
+}
+
+impl<K: Ord + DeserializeOwned, V: DeserializeOwned> DecodeState for StorageMap<K, V> {
+    fn decode_state(&mut self, prefix: &str, state: &State) -> Result<(), &'static str> {
+        self.map = entries(prefix, state)
+            .map(|(key, value)| Ok((decode(key)?, decode(value)?)))
+            .collect::<Result<_, &'static str>>()?;
+        Ok(())
+    }
+}
+
+impl<K, V> TrackAccesses for StorageMap<K, V>
+where
+    K: Ord + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    fn take_accesses(&self, prefix: &str, accesses: &mut Accesses) {
+        let (all, keys) = self.accesses.take();
+        if all {
+            accesses.insert(format!("{}/", prefix));
+        }
+        accesses.extend(keys.iter().map(|key| map_key(prefix, key)));
+    }
+
+    fn encode_keys(&self, prefix: &str, keys: &Accesses, state: &mut State) {
+        for rest in keys_under(prefix, keys) {
+            if rest.is_empty() {
+                self.encode_state(prefix, state);
+                continue;
+            }
+            // A key which does not decode is not stored.
+            let Ok(key) = decode(rest) else { continue };
+            if let Some(value) = self.map.get(&key) {
+                state.insert(map_key(prefix, &key), encode(value));
+            }
+        }
+    }
+
+    fn decode_keys(
+        &mut self,
+        prefix: &str,
+        keys: &Accesses,
+        state: &State,
+    ) -> Result<(), &'static str> {
+        for rest in keys_under(prefix, keys) {
+            if rest.is_empty() {
+                self.decode_state(prefix, state)?;
+                continue;
+            }
+            let key = decode(rest)?;
+            match state.get(&map_key(prefix, &key)) {
+                Some(value) => self.map.insert(key, decode(value)?),
+                None => self.map.remove(&key),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for StorageMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for StorageMap<K, V> {}
+
+impl<K: Debug, V: Debug> Debug for StorageMap<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+/// A map from pairs of keys to values in storage, ordered by the first and then the second key.
+/// All the values sharing the same first key can be iterated efficiently.
+///
+/// Pallets usually do not use this directly, but through the functions generated for it by
+/// `#[macros::storage]`.
+#[derive(Clone)]
+pub struct StorageDoubleMap<K1, K2, V> {
+    map: BTreeMap<K1, BTreeMap<K2, V>>,
+    /// The second key is `None` for an access to all the values under the first key.
+    accesses: AccessLog<(K1, Option<K2>)>,
+}
+
+impl<K1: Ord + Clone, K2: Ord + Clone, V> StorageDoubleMap<K1, K2, V> {
+    /// Create a new empty storage double map.
+    pub fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            accesses: AccessLog::new(),
+        }
+    }
+
+    /// Get the value stored under `(key1, key2)`, if any.
+    pub fn get(&self, key1: &K1, key2: &K2) -> Option<&V> {
+        self.accesses.key(|| (key1.clone(), Some(key2.clone())));
+        self.map.get(key1).and_then(|inner| inner.get(key2))
+    }
+
+    /// Check if a value is stored under `(key1, key2)`.
+    pub fn contains_key(&self, key1: &K1, key2: &K2) -> bool {
+        self.get(key1, key2).is_some()
+    }
+
+    /// Store `value` under `(key1, key2)`, and return the previously stored value.
+    pub fn insert(&mut self, key1: K1, key2: K2, value: V) -> Option<V> {
+        self.accesses.key(|| (key1.clone(), Some(key2.clone())));
+        self.map.entry(key1).or_default().insert(key2, value)
+    }
+
+    /// Remove the value stored under `(key1, key2)`, and return it.
+    pub fn remove(&mut self, key1: &K1, key2: &K2) -> Option<V> {
+        self.accesses.key(|| (key1.clone(), Some(key2.clone())));
+        let inner = self.map.get_mut(key1)?;
+        let value = inner.remove(key2);
+        // Never keep an empty inner map around.
+        if inner.is_empty() {
+            self.map.remove(key1);
+        }
+        value
+    }
+
+    /// Mutate the value stored under `(key1, key2)` in place, first storing `default()` if there
+    /// is none.
+    pub fn mutate_or_insert<R>(
+        &mut self,
+        key1: K1,
+        key2: K2,
+        default: impl FnOnce() -> V,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        self.accesses.key(|| (key1.clone(), Some(key2.clone())));
+        f(self
+            .map
+            .entry(key1)
+            .or_default()
+            .entry(key2)
+            .or_insert_with(default))
+    }
+
+    /// Iterate over the second keys and values stored under `key1`, ordered by the second key.
+    pub fn iter_prefix(&self, key1: &K1) -> impl Iterator<Item = (&K2, &V)> {
+        self.accesses.key(|| (key1.clone(), None));
+        self.map.get(key1).into_iter().flatten()
+    }
+
+    /// Iterate over all the stored keys and values, ordered by the first and then the second key.
+    pub fn iter(&self) -> impl Iterator<Item = (&K1, &K2, &V)> {
+        self.accesses.all();
+        self.map
+            .iter()
+            .flat_map(|(key1, inner)| inner.iter().map(move |(key2, value)| (key1, key2, value)))
+    }
+}
+
+impl<K1: Ord + Clone, K2: Ord + Clone, V> Default for StorageDoubleMap<K1, K2, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K1: Serialize, K2: Serialize, V: Serialize> EncodeState for StorageDoubleMap<K1, K2, V> {
+    fn encode_state(&self, prefix: &str, state: &mut State) {
+        for (key1, inner) in &self.map {
+            for (key2, value) in inner {
+                state.insert(double_map_key(prefix, key1, key2), encode(value));
+            }
+        }
+    }
+}
+
+impl<K1, K2, V> DecodeState for StorageDoubleMap<K1, K2, V>
+where
+    K1: Ord + DeserializeOwned,
+    K2: Ord + DeserializeOwned,
+    V: DeserializeOwned,
+{
+    fn decode_state(&mut self, prefix: &str, state: &State) -> Result<(), &'static str> {
+        let mut map = BTreeMap::<K1, BTreeMap<K2, V>>::new();
+        for (rest, value) in entries(prefix, state) {
+            let (key1, key2) = split_key(rest)?;
+            map.entry(key1)
+                .or_default()
+                .insert(decode(key2)?, decode(value)?);
+        }
+        self.map = map;
+        Ok(())
+    }
+}
+
+impl<K1, K2, V> TrackAccesses for StorageDoubleMap<K1, K2, V>
+where
+    K1: Ord + Serialize + DeserializeOwned,
+    K2: Ord + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    fn take_accesses(&self, prefix: &str, accesses: &mut Accesses) {
+        let (all, keys) = self.accesses.take();
+        if all {
+            accesses.insert(format!("{}/", prefix));
+        }
+        accesses.extend(keys.iter().map(|(key1, key2)| match key2 {
+            Some(key2) => double_map_key(prefix, key1, key2),
+            None => format!("{}/", map_key(prefix, key1)),
+        }));
+    }
+
+    fn encode_keys(&self, prefix: &str, keys: &Accesses, state: &mut State) {
+        for rest in keys_under(prefix, keys) {
+            if rest.is_empty() {
+                self.encode_state(prefix, state);
+                continue;
+            }
+            // A key which does not decode is not stored.
+            let Ok((key1, key2)) = split_key::<K1>(rest) else {
+                continue;
+            };
+            let Some(inner) = self.map.get(&key1) else {
+                continue;
+            };
+            if key2.is_empty() {
+                for (key2, value) in inner {
+                    state.insert(double_map_key(prefix, &key1, key2), encode(value));
+                }
+            } else if let Some(value) = decode(key2).ok().and_then(|key2| inner.get(&key2)) {
+                state.insert(format!("{}/{}", prefix, rest), encode(value));
+            }
+        }
+    }
+
+    fn decode_keys(
+        &mut self,
+        prefix: &str,
+        keys: &Accesses,
+        state: &State,
+    ) -> Result<(), &'static str> {
+        for rest in keys_under(prefix, keys) {
+            if rest.is_empty() {
+                self.decode_state(prefix, state)?;
+                continue;
+            }
+            let (key1, key2) = split_key::<K1>(rest)?;
+            let prefix1 = map_key(prefix, &key1);
+            let inner = if key2.is_empty() {
+                entries(&prefix1, state)
+                    .map(|(key2, value)| Ok((decode(key2)?, decode(value)?)))
+                    .collect::<Result<_, &'static str>>()?
+            } else {
+                let mut inner = self.map.remove(&key1).unwrap_or_default();
+                let key2 = decode(key2)?;
+                match state.get(&format!("{}/{}", prefix, rest)) {
+                    Some(value) => inner.insert(key2, decode(value)?),
+                    None => inner.remove(&key2),
+                };
+                inner
+            };
+            // Never keep an empty inner map around.
+            if !inner.is_empty() {
+                self.map.insert(key1, inner);
+            } else {
+                self.map.remove(&key1);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K1: PartialEq, K2: PartialEq, V: PartialEq> PartialEq for StorageDoubleMap<K1, K2, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<K1: Eq, K2: Eq, V: Eq> Eq for StorageDoubleMap<K1, K2, V> {}
+
+impl<K1: Debug, K2: Debug, V: Debug> Debug for StorageDoubleMap<K1, K2, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Accesses, DecodeState, EncodeState, State, StateChange, StorageDoubleMap, StorageMap,
+        StorageValue, TrackAccesses, UNDECODABLE_STATE,
+    };
+
+    /// Checks the basic operations of each storage type.
+    #[test]
+    fn storage_types() {
+        let mut value = StorageValue::new(1u32);
+        value.put(2);
+        value.mutate(|v| *v += 1);
+        assert_eq!(value.replace(0), 3);
+        assert_eq!(*value.get(), 0);
+
+        let mut map = StorageMap::<&str, u32>::new();
+        map.insert("alice", 1);
+        map.mutate_or_insert("alice", || 0, |v| *v += 1);
+        map.mutate_or_insert("bob", || 10, |v| *v += 1);
+        assert_eq!(map.get(&"alice"), Some(&2));
+        assert_eq!(map.remove(&"bob"), Some(11));
+        assert_eq!(map.len(), 1);
+
+        let mut double_map = StorageDoubleMap::<&str, &str, ()>::new();
+        double_map.insert("alice", "b", ());
+        double_map.insert("alice", "a", ());
+        double_map.insert("bob", "c", ());
+        let alice = double_map
+            .iter_prefix(&"alice")
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        assert_eq!(alice, vec!["a", "b"]);
+        assert_eq!(double_map.remove(&"bob", &"c"), Some(()));
+        assert_eq!(double_map.iter_prefix(&"bob").count(), 0);
+        assert_eq!(double_map.iter().count(), 2);
+    }
+
+    /// Checks that the storage types encode their values under their keys, and the changes between
+    /// two states.
+    #[test]
+    fn encode_state() {
+        let value = StorageValue::new(7u32);
+        let mut map = StorageMap::<&str, u32>::new();
+        map.insert("alice", 1);
+        let mut double_map = StorageDoubleMap::<&str, u8, ()>::new();
+        double_map.insert("alice", 2, ());
+
+        let mut before = State::new();
+        value.encode_state("value", &mut before);
+        map.encode_state("map", &mut before);
+        double_map.encode_state("double_map", &mut before);
+        assert_eq!(
+            before.into_iter().collect::<Vec<_>>(),
+            vec![
+                (r#"double_map/"alice"/2"#.to_string(), "null".to_string()),
+                (r#"map/"alice""#.to_string(), "1".to_string()),
+                ("value".to_string(), "7".to_string()),
+            ]
+        );
+
+        let mut before = State::new();
+        map.encode_state("map", &mut before);
+        map.insert("alice", 2);
+        map.insert("bob", 3);
+        let mut after = State::new();
+        map.encode_state("map", &mut after);
+        let diff = super::diff(&before, &after);
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            diff[r#"map/"alice""#],
+            StateChange {
+                before: Some("1".to_string()),
+                after: Some("2".to_string())
+            }
+        );
+        assert_eq!(diff[r#"map/"bob""#].before, None);
+    }
+
+    /// Checks the following:
+    /// - The storage types decode the values they encoded, even with keys containing `/`.
+    /// - Values under other prefixes are ignored, and a missing `StorageValue` keeps its value.
+    /// - A value which does not decode to the stored type is an error.
+    #[test]
+    fn decode_state() {
+        let value = StorageValue::new(7u32);
+        let mut map = StorageMap::<String, u32>::new();
+        map.insert("alice".to_string(), 1);
+        let mut double_map = StorageDoubleMap::<String, String, u8>::new();
+        double_map.insert("a/b".to_string(), "c/d".to_string(), 2);
+        double_map.insert("a".to_string(), "b/c/d".to_string(), 3);
+
+        let mut state = State::new();
+        value.encode_state("value", &mut state);
+        map.encode_state("map", &mut state);
+        map.encode_state("map_other", &mut state);
+        double_map.encode_state("double_map", &mut state);
+
+        let mut decoded_value = StorageValue::new(0u32);
+        let mut decoded_map = StorageMap::<String, u32>::new();
+        let mut decoded_double_map = StorageDoubleMap::<String, String, u8>::new();
+        decoded_value.decode_state("value", &state).unwrap();
+        decoded_map.decode_state("map", &state).unwrap();
+        decoded_double_map
+            .decode_state("double_map", &state)
+            .unwrap();
+        assert!(decoded_value == value);
+        assert!(decoded_map == map);
+        assert!(decoded_double_map == double_map);
+
+        decoded_value.decode_state("missing", &state).unwrap();
+        assert_eq!(*decoded_value.get(), 7);
+
+        state.insert(r#"map/"bob""#.to_string(), r#""not a number""#.to_string());
+        assert_eq!(
+            decoded_map.decode_state("map", &state),
+            Err(UNDECODABLE_STATE)
+        );
+    }
+
+    /// Checks the following:
+    /// - Only the values accessed while recording accesses are recorded, by key, or by prefix when
+    ///   iterating, and taking them forgets them.
+    /// - Only the values under the given keys are encoded, and decoding them replaces or removes
+    ///   those values only.
+    #[test]
+    fn track_accesses() {
+        let value = StorageValue::new(7u32);
+        let mut map = StorageMap::<String, u32>::new();
+        map.insert("alice".to_string(), 1);
+        map.insert("bob".to_string(), 2);
+        let mut double_map = StorageDoubleMap::<String, u8, u8>::new();
+        double_map.insert("alice".to_string(), 1, 1);
+        double_map.insert("bob".to_string(), 2, 2);
+
+        map.get(&"charlie".to_string());
+        super::record_accesses(|| {
+            value.get();
+            map.get(&"alice".to_string());
+            map.contains_key(&"dave".to_string());
+            double_map.iter_prefix(&"alice".to_string()).count();
+        });
+        let mut accesses = Accesses::new();
+        value.take_accesses("value", &mut accesses);
+        map.take_accesses("map", &mut accesses);
+        double_map.take_accesses("double_map", &mut accesses);
+        let expected = [
+            r#"double_map/"alice"/"#,
+            r#"map/"alice""#,
+            r#"map/"dave""#,
+            "value",
+        ];
+        assert_eq!(accesses, expected.map(String::from).into());
+        let mut taken = Accesses::new();
+        map.take_accesses("map", &mut taken);
+        assert!(taken.is_empty());
+
+        let mut state = State::new();
+        value.encode_state("value", &mut state);
+        map.encode_keys("map", &accesses, &mut state);
+        double_map.encode_keys("double_map", &accesses, &mut state);
+        assert_eq!(
+            state.keys().collect::<Vec<_>>(),
+            vec![r#"double_map/"alice"/1"#, r#"map/"alice""#, "value"]
+        );
+
+        let mut copy = map.clone();
+        copy.insert("alice".to_string(), 10);
+        copy.insert("dave".to_string(), 4);
+        copy.remove(&"bob".to_string());
+        let mut changed = State::new();
+        copy.encode_keys("map", &accesses, &mut changed);
+        map.decode_keys("map", &accesses, &changed).unwrap();
+        let entries = map
+            .iter()
+            .map(|(k, v)| (k.as_str(), *v))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![("alice", 10), ("bob", 2), ("dave", 4)]);
+
+        double_map
+            .decode_keys("double_map", &accesses, &State::new())
+            .unwrap();
+        assert_eq!(double_map.iter().count(), 1);
+    }
+}
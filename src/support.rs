@@ -1,4 +1,13 @@
+use crate::crypto::{Pair, PublicKey, Signature};
+use crate::merkle::{self, Hash};
+use crate::storage::{State, StateDiff};
+
+// The generated code serializes calls with the `serde` of this crate, so crates defining runtimes
+// do not need to depend on it themselves.
+pub use serde;
+
 /// The most primitive representation of a Blockchain block.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Block<Header, Extrinsic> {
     /// The block header contains metadata about the block.
     pub header: Header,
@@ -6,28 +15,215 @@ pub struct Block<Header, Extrinsic> {
     pub extrinsics: Vec<Extrinsic>,
 }
 
-/// We are using an extremely simplified header which only contains the current block number.
-/// On a real blockchain, you would expect to also find:
-/// - parent block hash
-/// - state root
-/// - extrinsics root
+/// We are using a simplified header, which links the block to its parent and commits to its
+/// extrinsics and to the state after the block. On a real blockchain, you would expect to also
+/// find:
+/// - a digest, with the author of the block
 /// - etc...
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Header<BlockNumber> {
+    /// The hash of the header of the parent block, or zero for the genesis block.
+    pub parent_hash: Hash,
     pub block_number: BlockNumber,
+    /// The root of the sparse Merkle tree of the state after the block, see `Runtime::state_root`.
+    pub state_root: Hash,
+    /// The root of the Merkle tree of the extrinsics of the block, see `extrinsics_root`.
+    pub extrinsics_root: Hash,
+}
+
+impl<BlockNumber: serde::Serialize> Header<BlockNumber> {
+    /// The hash of the header, which identifies the block.
+    pub fn hash(&self) -> Hash {
+        hash_of(self)
+    }
 }
 
 /// This is an "extrinsic": literally an external message from outside of the blockchain.
 /// This simplified version of an extrinsic tells us who is making the call, and which call they are
 /// making.
-pub struct Extrinsic<Caller, Call> {
+///
+/// The `nonce` must be the current nonce of the caller, so each extrinsic can only be executed once,
+/// in the order its caller sent them. The `tip` is paid on top of the fees of the call, to get the
+/// extrinsic included sooner, see `ChargeTransaction`. The `signature` must be made over all the
+/// other fields with the key of the caller, see `AccountKeys`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Extrinsic<Caller, Nonce, Call> {
     pub caller: Caller,
+    pub nonce: Nonce,
+    pub tip: Tip,
     pub call: Call,
+    pub signature: Signature,
+}
+
+impl<Caller, Nonce, Call> Extrinsic<Caller, Nonce, Call> {
+    /// Create an extrinsic where `caller` makes `call` with their nonce `nonce`, and no tip. The call
+    /// can also be the call of a single pallet, like
+    /// `Extrinsic::new(alice, 0, balances::Call::transfer { to: bob, amount: 30 })`.
+    ///
+    /// The extrinsic is not signed yet: it must be signed with `sign` once its tip is set.
+    pub fn new(caller: Caller, nonce: Nonce, call: impl Into<Call>) -> Self {
+        Self {
+            caller,
+            nonce,
+            tip: 0,
+            call: call.into(),
+            signature: Signature::default(),
+        }
+    }
+
+    /// Pay `tip` on top of the fees of the call.
+    pub fn with_tip(self, tip: Tip) -> Self {
+        Self { tip, ..self }
+    }
+}
+
+impl<Caller, Nonce, Call> Extrinsic<Caller, Nonce, Call>
+where
+    Caller: serde::Serialize,
+    Nonce: serde::Serialize,
+    Call: serde::Serialize,
+{
+    /// The message signed by the caller: the hash of every field but the signature.
+    pub fn signing_payload(&self) -> Hash {
+        hash_of(&(&self.caller, &self.nonce, self.tip, &self.call))
+    }
+
+    /// Sign the extrinsic with `pair`, the key pair of its caller.
+    pub fn sign(self, pair: &Pair) -> Self {
+        let signature = pair.sign(&self.signing_payload());
+        Self { signature, ..self }
+    }
+
+    /// Check that the extrinsic was signed with the secret key of `key`.
+    pub fn is_signed_by(&self, key: &PublicKey) -> bool {
+        key.verify(&self.signing_payload(), &self.signature)
+    }
+}
+
+/// Hash any serializable value, using its JSON encoding, which is deterministic for the types used
+/// on chain.
+pub fn hash_of<T: serde::Serialize + ?Sized>(value: &T) -> Hash {
+    let encoded = serde_json::to_vec(value).expect("on chain types always serialize");
+    merkle::hash(&encoded)
+}
+
+/// The root of the Merkle tree over the hashes of `extrinsics`, which is zero if there are none.
+pub fn extrinsics_root<Extrinsic: serde::Serialize>(extrinsics: &[Extrinsic]) -> Hash {
+    let leaves = extrinsics.iter().map(hash_of).collect::<Vec<_>>();
+    merkle::MerkleTree::new(&leaves)
+        .map(|tree| tree.root())
+        .unwrap_or_default()
 }
 
 /// The Result type for our runtime. When everything completes successfully, we return `Ok(())`,
 /// otherwise return a static error message.
 pub type DispatchResult = Result<(), &'static str>;
 
+/// The weight of a call: an estimation of the resources needed to execute it.
+pub type Weight = u64;
+
+/// The amount paid by the caller of an extrinsic on top of its fees, in the smallest unit of the
+/// currency of the runtime.
+pub type Tip = u128;
+
+/// A list passed as the argument of a call. Calls display their arguments, which a `Vec` can not
+/// do, so a list is displayed like `[alice, bob]` instead. It is encoded like the `Vec` it wraps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct List<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for List<T> {
+    fn from(items: Vec<T>) -> Self {
+        List(items)
+    }
+}
+
+impl<T> core::ops::Deref for List<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl<T: core::fmt::Display> core::fmt::Display for List<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let items = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "[{}]", items.join(", "))
+    }
+}
+
+/// An optional argument of a call. Calls display their arguments, which an `Option` can not do,
+/// so it is displayed as its value, or `none`. It is encoded like the `Option` it wraps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Optional<T>(pub Option<T>);
+
+impl<T> From<Option<T>> for Optional<T> {
+    fn from(value: Option<T>) -> Self {
+        Optional(value)
+    }
+}
+
+impl<T: core::fmt::Display> core::fmt::Display for Optional<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match &self.0 {
+            Some(value) => value.fmt(f),
+            None => f.write_str("none"),
+        }
+    }
+}
+
+/// The outcome of executing a block, returned by the `execute_block` of a runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockReceipt<Event> {
+    /// The receipt of each extrinsic of the block, in order.
+    pub extrinsics: Vec<ExtrinsicReceipt<Event>>,
+    /// The events emitted by the `on_initialize` hooks, before the extrinsics.
+    pub initialize_events: Vec<Event>,
+    /// The events emitted by the `on_finalize` hooks, after the extrinsics.
+    pub finalize_events: Vec<Event>,
+    /// The total weight of the block, including the hooks.
+    pub weight: Weight,
+    /// The root of the state after the block.
+    pub state_root: Hash,
+}
+
+/// The outcome of executing a single extrinsic of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtrinsicReceipt<Event> {
+    /// The index of the extrinsic in the block.
+    pub index: usize,
+    /// The result of dispatching the call of the extrinsic.
+    pub result: DispatchResult,
+    /// The weight of the call of the extrinsic.
+    pub weight: Weight,
+    /// The events emitted while dispatching the call of the extrinsic.
+    pub events: Vec<Event>,
+}
+
+/// The outcome of a dry run of a single extrinsic, returned by the `dry_run` of a runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun<Event> {
+    /// The result of the extrinsic: an error if it can not be executed or its call fails.
+    pub result: DispatchResult,
+    /// The weight of the call of the extrinsic, or zero if it can not be executed.
+    pub weight: Weight,
+    /// The events emitted while executing the extrinsic.
+    pub events: Vec<Event>,
+    /// The changes the extrinsic would make to the state of the runtime, including its fees.
+    pub state_diff: StateDiff,
+}
+
+/// The outcome of simulating a block, returned by the `simulate_block` of a runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation<Event> {
+    /// The receipt of executing the block.
+    pub receipt: BlockReceipt<Event>,
+    /// The changes the block would make to the state of the runtime.
+    pub state_diff: StateDiff,
+}
+
 /// A trait which allows us to dispatch an incoming extrinsic to the appropriate state transition
 /// function call.
 pub trait Dispatch {
@@ -40,3 +236,248 @@ pub trait Dispatch {
     /// based on the outcome of that function call.
     fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
 }
+
+/// A trait which decides whether a call may be dispatched, implemented by every runtime for its
+/// `RuntimeCall`. It is consulted before a call is routed to its pallet, so calls can be disabled
+/// without changing the pallets, either by fixed rules or by reading state like the
+/// `call_filter` pallet.
+pub trait CallFilter<Call> {
+    /// Return `true` if `call` may be dispatched.
+    fn contains(&self, call: &Call) -> bool;
+}
+
+/// A trait which makes the caller of an extrinsic pay for it, implemented by every runtime for its
+/// `RuntimeCall`. The fees are withdrawn before the call is dispatched, and are not refunded if it
+/// fails.
+pub trait ChargeTransaction<Caller, Call> {
+    /// Check that `caller` can pay the fees of `call` and `tip`, without changing any state.
+    fn can_pay(&self, caller: &Caller, call: &Call, tip: Tip) -> DispatchResult;
+
+    /// Withdraw the fees of `call` and `tip` from `caller`. If an error is returned, nothing was
+    /// withdrawn, and the call must not be dispatched.
+    fn withdraw_fee(&mut self, caller: &Caller, call: &Call, tip: Tip) -> DispatchResult;
+}
+
+/// A trait which gives the public key of each caller, implemented by every runtime. Extrinsics are
+/// only executed if they are signed with the key of their caller, so nobody can act on behalf of an
+/// account without its secret key.
+pub trait AccountKeys<Caller> {
+    /// The public key of `caller`, or `None` if the caller can not sign extrinsics.
+    fn account_key(&self, caller: &Caller) -> Option<PublicKey>;
+}
+
+/// A trait implemented by the `RuntimeCall` of a runtime, to look at the call of a single pallet
+/// without taking the `RuntimeCall` apart, for example in a `CallFilter`.
+pub trait IsSubType<Call> {
+    /// Return the call of the pallet, if the `RuntimeCall` belongs to it.
+    fn is_sub_type(&self) -> Option<&Call>;
+}
+
+/// The error returned when dispatching a call rejected by the `CallFilter` of the runtime.
+pub const CALL_FILTERED: &str = "call is filtered by the runtime";
+
+/// The error of an extrinsic whose nonce is not the current nonce of its caller, which is not
+/// executed.
+pub const INVALID_NONCE: &str = "extrinsic nonce does not match the nonce of the caller";
+
+/// The error of an extrinsic which is not signed with the key of its caller, which is not executed.
+pub const BAD_SIGNATURE: &str = "extrinsic is not signed by its caller";
+
+/// Hooks which a pallet can implement to run logic at the start and the end of every block, like
+/// expiring old data or paying out rewards. Both hooks do nothing by default.
+///
+/// The runtime calls them on all pallets in the order they are declared: `on_initialize` before the
+/// extrinsics of the block are executed, and `on_finalize` after.
+pub trait Hooks<BlockNumber> {
+    /// Called at the start of block `block_number`. Returns the weight it consumed.
+    fn on_initialize(&mut self, _block_number: BlockNumber) -> Weight {
+        0
+    }
+
+    /// Called at the end of block `block_number`. Returns the weight it consumed.
+    fn on_finalize(&mut self, _block_number: BlockNumber) -> Weight {
+        0
+    }
+}
+
+/// A trait for pallets which emit events, like a successful transfer.
+///
+/// The pallet keeps the events it emits until the runtime takes them.
+pub trait Events {
+    /// The type of the events emitted by the pallet.
+    type Event;
+
+    /// Take all the events emitted since the last call, in the order they were emitted.
+    fn take_events(&mut self) -> Vec<Self::Event>;
+}
+
+/// A trait for pallets which can be configured with some initial state when the chain starts.
+pub trait BuildGenesis {
+    /// The initial state of the pallet.
+    type GenesisConfig;
+
+    /// Put the initial state `config` in storage.
+    fn build_genesis(&mut self, config: &Self::GenesisConfig);
+}
+
+/// The version of the storage layout of a pallet. A pallet bumps it whenever it changes how its
+/// values are stored, and provides a migration from the previous version.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct StorageVersion(pub u16);
+
+impl StorageVersion {
+    /// The version following this one.
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+/// A trait for pallets whose storage layout is versioned, so that a state stored by an older
+/// version of the pallet can be migrated when the runtime is upgraded.
+///
+/// The runtime keeps the version of the state of each of these pallets in the system pallet.
+pub trait Versioned {
+    /// The version of the storage layout of the pallet.
+    const STORAGE_VERSION: StorageVersion;
+
+    /// The migrations from the older versions, in order, like `(MigrateToV1<T>, MigrateToV2<T>)`.
+    type Migrations: Migrations;
+}
+
+/// A migration of the encoded state of a pallet from one version of its storage layout to the
+/// next.
+///
+/// It works on the `State` rather than on the pallet, since the values stored by the older version
+/// do not decode to the current types anymore.
+pub trait OnRuntimeUpgrade {
+    /// The version this migrates from. The state is at the next version afterwards.
+    const FROM: StorageVersion;
+
+    /// Migrate the values stored under `prefix` in `state`.
+    ///
+    /// Returns an error if the stored values can not be migrated, in which case `state` must be
+    /// discarded.
+    fn on_runtime_upgrade(prefix: &str, state: &mut State) -> DispatchResult;
+
+    /// Check `state` before the migration, and return the data `post_upgrade` needs to check the
+    /// migration went right.
+    fn pre_upgrade(_prefix: &str, _state: &State) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+
+    /// Check `state` after the migration, with the data returned by `pre_upgrade`.
+    fn post_upgrade(_prefix: &str, _state: &State, _data: Vec<u8>) -> DispatchResult {
+        Ok(())
+    }
+}
+
+/// A sequence of migrations, implemented by `()` and tuples of `OnRuntimeUpgrade`.
+pub trait Migrations {
+    /// Run, in order, every migration from the current `version` of the state stored under
+    /// `prefix`, with its checks, and update `version` as they go. Migrations from other versions
+    /// are skipped.
+    ///
+    /// Returns an error if a migration or one of its checks fails, in which case `state` must be
+    /// discarded.
+    fn migrate(prefix: &str, version: &mut StorageVersion, state: &mut State) -> DispatchResult;
+}
+
+impl Migrations for () {
+    fn migrate(_prefix: &str, _version: &mut StorageVersion, _state: &mut State) -> DispatchResult {
+        Ok(())
+    }
+}
+
+macro_rules! impl_migrations_for_tuple {
+    ($($migration:ident),+) => {
+        impl<$($migration: OnRuntimeUpgrade),+> Migrations for ($($migration,)+) {
+            fn migrate(
+                prefix: &str,
+                version: &mut StorageVersion,
+                state: &mut State,
+            ) -> DispatchResult {
+                $(
+                    if *version == $migration::FROM {
+                        let data = $migration::pre_upgrade(prefix, state)?;
+                        $migration::on_runtime_upgrade(prefix, state)?;
+                        $migration::post_upgrade(prefix, state, data)?;
+                        *version = version.next();
+                    }
+                )+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_migrations_for_tuple!(A);
+impl_migrations_for_tuple!(A, B);
+impl_migrations_for_tuple!(A, B, C);
+impl_migrations_for_tuple!(A, B, C, D);
+
+/// A trait for types which provide a single value, used to pass configurable parameters (such as
+/// limits) to a pallet through its `Config` trait.
+pub trait Get<T> {
+    /// Return the value.
+    fn get() -> T;
+}
+
+/// A machine-readable description of everything a runtime can do, generated by
+/// `#[macros::runtime]` from the calls of each pallet.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RuntimeMetadata {
+    /// The pallets with callable functions, in the order they are declared in the runtime.
+    pub pallets: Vec<PalletMetadata>,
+}
+
+impl RuntimeMetadata {
+    /// Render the metadata as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("metadata only contains strings and numbers")
+    }
+}
+
+/// The description of a single pallet of the runtime.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PalletMetadata {
+    /// The name of the pallet in the runtime, which is also its `RuntimeCall` variant.
+    pub name: &'static str,
+    /// The index of the pallet in the `RuntimeCall` enum.
+    pub index: u8,
+    /// The calls exposed by the pallet.
+    pub calls: Vec<CallMetadata>,
+}
+
+/// The description of a single call, generated by `#[macros::call]`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct CallMetadata {
+    /// The name of the function, which is also its `Call` variant.
+    pub name: &'static str,
+    /// The index of the call, see `#[call_index(..)]`.
+    pub index: u8,
+    /// The arguments of the call, not including the `caller`.
+    pub args: Vec<ArgMetadata>,
+    /// The doc comments of the function, one entry per line.
+    pub docs: Vec<&'static str>,
+}
+
+/// The description of a single argument of a call.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ArgMetadata {
+    /// The name of the argument.
+    pub name: &'static str,
+    /// The type of the argument, as written in the pallet, like `T::AccountId`.
+    #[serde(rename = "type")]
+    pub ty: &'static str,
+}
@@ -1,6 +1,9 @@
-use std::{collections::BTreeMap, ops::AddAssign};
+use std::ops::AddAssign;
 
-use num::{One, Zero};
+use num::{CheckedAdd, One, Zero};
+
+use crate::storage::{StorageMap, StorageValue};
+use crate::support::{Hooks, StorageVersion, Weight};
 
 /// The configuration trait for the System Pallet.
 /// This controls the common types used throughout our state machine.
@@ -11,49 +14,67 @@ pub trait Config {
 
     /// A type which can be used to represent the current block number.
     /// Usually a basic unsigned integer.
-    type BlockNumber: Zero + One + AddAssign + Copy;
+    type BlockNumber: Zero + One + AddAssign + CheckedAdd + Copy + Ord;
 
     /// A type which can be used to keep track of the number of transactions from each account.
     /// Usually a basic unsigned integer.
-    type Nonce: Zero + One + Copy;
+    type Nonce: Zero + One + Copy + Ord;
 }
 
 /// This is the System Pallet.
 /// It handles low level state needed for your blockchain.
-#[derive(Debug)]
+#[macros::storage]
+#[derive(Debug, Clone)]
 pub struct Pallet<T: Config> {
     /// The current block number.
-    block_number: T::BlockNumber,
+    #[storage(default = T::BlockNumber::zero())]
+    block_number: StorageValue<T::BlockNumber>,
     /// A map from an account to their nonce.
-    nonce: BTreeMap<T::AccountId, T::Nonce>,
+    #[storage(default = T::Nonce::zero())]
+    nonce: StorageMap<T::AccountId, T::Nonce>,
+    /// The total weight consumed by hooks and extrinsics in the current block.
+    block_weight: StorageValue<Weight>,
+    /// A map from the name of a versioned pallet in the runtime to the version of its stored state.
+    /// A pallet missing from the map is at version zero, which is the layout from before it was
+    /// versioned.
+    #[storage(getter = storage_version, default = StorageVersion::default())]
+    storage_versions: StorageMap<String, StorageVersion>,
 }
 
 impl<T: Config> Pallet<T> {
-    /// Create a new instance of the System Pallet.
-    pub fn new() -> Self {
-        Self {
-            block_number: T::BlockNumber::zero(),
-            nonce: BTreeMap::new(),
-        }
+    // This function can be used to increment the block number.
+    // Increases the block number by one.
+    pub fn inc_block_number(&mut self) {
+        self.mutate_block_number(|block_number| *block_number += T::BlockNumber::one());
     }
 
-    /// Get the current block number.
-    pub fn block_number(&self) -> T::BlockNumber {
-        self.block_number
+    // The number of the block following the current one, which is the next block to execute.
+    pub fn next_block_number(&self) -> T::BlockNumber {
+        let mut block_number = self.block_number();
+        block_number += T::BlockNumber::one();
+        block_number
     }
 
-    // This function can be used to increment the block number.
-    // Increases the block number by one.
-    pub fn inc_block_number(&mut self) {
-        self.block_number += T::BlockNumber::one();
+    // Add `weight` to the weight consumed in the current block.
+    pub fn register_weight(&mut self, weight: Weight) {
+        self.mutate_block_weight(|block_weight| {
+            *block_weight = block_weight.saturating_add(weight)
+        });
     }
 
     // Increment the nonce of an account. This helps us keep track of how many transactions each
     // account has made.
     pub fn inc_nonce(&mut self, who: &T::AccountId) {
-        let current_nonce = *self.nonce.get(who).unwrap_or(&T::Nonce::zero());
-        let new_nonce = current_nonce + T::Nonce::one();
-        self.nonce.insert(who.clone(), new_nonce);
+        self.mutate_nonce(who, |nonce| *nonce = *nonce + T::Nonce::one());
+    }
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {
+    // The system pallet is the first pallet of the runtime, so it resets the weight consumed in the
+    // previous block before any other pallet registers its own.
+    fn on_initialize(&mut self, _block_number: T::BlockNumber) -> Weight {
+        self.set_block_weight(0);
+        0
     }
 }
 
@@ -77,13 +98,28 @@ mod test {
         let alice = String::from("alice");
         let bob = String::from("bob");
 
-        assert_eq!(system.block_number, 0);
+        assert_eq!(system.block_number(), 0);
 
         system.inc_block_number();
         system.inc_nonce(&alice);
 
-        assert_eq!(system.block_number, 1);
+        assert_eq!(system.block_number(), 1);
         assert_eq!(system.nonce.get(&alice), Some(&1));
         assert_eq!(system.nonce.get(&bob), None);
     }
+
+    /// Checks that weight accumulates during a block, and is reset when the next block starts.
+    #[test]
+    fn block_weight() {
+        use crate::support::Hooks;
+
+        let mut system = super::Pallet::<TestConfig>::new();
+
+        system.register_weight(10);
+        system.register_weight(5);
+        assert_eq!(system.block_weight(), 15);
+
+        system.on_initialize(1);
+        assert_eq!(system.block_weight(), 0);
+    }
 }
@@ -0,0 +1,628 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use crate::merkle::Hash;
+use crate::runtime::{types, Runtime};
+use crate::support::{self, AccountKeys, CallFilter, ChargeTransaction};
+
+/// How far the nonce of an extrinsic may be ahead of the next nonce of its caller, counting their
+/// ready extrinsics in the pool.
+pub const MAX_FUTURE_NONCES: types::Nonce = 16;
+
+/// The errors which can be returned when submitting an extrinsic to the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The nonce of the extrinsic was already used by its caller.
+    Stale,
+    /// The nonce of the extrinsic is more than `MAX_FUTURE_NONCES` ahead of the next nonce of its
+    /// caller.
+    TooFarInFuture,
+    /// The extrinsic is not signed with the key of its caller.
+    BadSignature,
+    /// The same extrinsic is already in the pool.
+    AlreadyInPool,
+    /// An extrinsic with the same caller and nonce is in the pool, with a tip at least as high.
+    TipTooLow,
+    /// The pool is full, and the extrinsic does not pay a higher tip than the ones it could evict.
+    PoolFull,
+    /// The call of the extrinsic is rejected by the `CallFilter` of the runtime.
+    Filtered,
+    /// The caller can not pay the fees of the extrinsic.
+    CannotPay(&'static str),
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Stale => "extrinsic nonce was already used",
+            Error::TooFarInFuture => "extrinsic nonce is too far ahead of the caller nonce",
+            Error::BadSignature => support::BAD_SIGNATURE,
+            Error::AlreadyInPool => "extrinsic is already in the pool",
+            Error::TipTooLow => "replacement extrinsic must pay a higher tip",
+            Error::PoolFull => "transaction pool is full",
+            Error::Filtered => support::CALL_FILTERED,
+            Error::CannotPay(error) => error,
+        }
+    }
+}
+
+/// The number of extrinsics in the pool, by whether they can be included in the next block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// The extrinsics whose nonce follows the nonce of their caller, directly or after other
+    /// extrinsics of the pool.
+    pub ready: usize,
+    /// The extrinsics waiting for an extrinsic with a lower nonce from their caller.
+    pub future: usize,
+}
+
+/// An extrinsic waiting in the pool.
+#[derive(Debug)]
+struct PoolEntry {
+    extrinsic: types::Extrinsic,
+    hash: Hash,
+    /// The order in which the extrinsic was submitted, to order extrinsics with the same tip.
+    insertion: u64,
+}
+
+impl PoolEntry {
+    /// The priority of the extrinsic: a higher tip first, then the earliest submitted.
+    fn priority(&self) -> (support::Tip, Reverse<u64>) {
+        (self.extrinsic.tip, Reverse(self.insertion))
+    }
+}
+
+/// A pool of the extrinsics submitted to the chain, waiting to be included in a block.
+///
+/// Extrinsics are validated against the state of the runtime when submitted, and kept by caller
+/// and nonce. The ones whose nonce is not the next one of their caller wait in the pool until the
+/// extrinsics before them are included.
+#[derive(Debug)]
+pub struct TransactionPool {
+    /// The maximum number of extrinsics in the pool.
+    max_size: usize,
+    /// The extrinsics in the pool, by caller and nonce.
+    pending: BTreeMap<types::AccountId, BTreeMap<types::Nonce, PoolEntry>>,
+    /// The insertion order of the next extrinsic submitted.
+    next_insertion: u64,
+}
+
+impl TransactionPool {
+    /// Create an empty pool, holding at most `max_size` extrinsics.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            pending: BTreeMap::new(),
+            next_insertion: 0,
+        }
+    }
+
+    /// The number of extrinsics in the pool.
+    pub fn len(&self) -> usize {
+        self.pending.values().map(BTreeMap::len).sum()
+    }
+
+    /// Check if the pool has no extrinsics.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Check if the extrinsic with the given hash is in the pool.
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.entries().any(|entry| entry.hash == *hash)
+    }
+
+    /// The extrinsics in the pool, in the order they were submitted.
+    pub fn extrinsics(&self) -> Vec<types::Extrinsic> {
+        let mut entries = self.entries().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.insertion);
+        entries
+            .into_iter()
+            .map(|entry| entry.extrinsic.clone())
+            .collect()
+    }
+
+    /// The nonce of the next extrinsic of `who`, after their ready extrinsics in the pool.
+    pub fn next_nonce(&self, runtime: &Runtime, who: &types::AccountId) -> types::Nonce {
+        let ready = self
+            .pending
+            .get(who)
+            .map_or(0, |entries| Self::ready_chain(runtime, who, entries).len());
+        runtime.system.nonce(who) + ready as types::Nonce
+    }
+
+    /// Submit `extrinsic` to the pool, after validating it against the state of `runtime`.
+    ///
+    /// An extrinsic with the same caller and nonce as one in the pool replaces it if it pays a
+    /// higher tip. When the pool is full, the extrinsic evicts the last extrinsic of another
+    /// caller, see `eviction_candidate`, if that one is waiting for a nonce while this one is
+    /// ready, or if it has the same readiness and pays a lower tip.
+    ///
+    /// Returns the hash of the extrinsic.
+    pub fn submit(
+        &mut self,
+        runtime: &Runtime,
+        extrinsic: types::Extrinsic,
+    ) -> Result<Hash, Error> {
+        let next_nonce = self.next_nonce(runtime, &extrinsic.caller);
+        if extrinsic.nonce > next_nonce.saturating_add(MAX_FUTURE_NONCES) {
+            return Err(Error::TooFarInFuture);
+        }
+        // The extrinsic is paid for along with the other extrinsics of its caller, except the one
+        // it replaces.
+        let pending_tips = self
+            .pending
+            .get(&extrinsic.caller)
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .filter(|entry| entry.extrinsic.nonce != extrinsic.nonce)
+            .fold(0, |tips: support::Tip, entry| {
+                tips.saturating_add(entry.extrinsic.tip)
+            });
+        Self::validate(runtime, &extrinsic, pending_tips)?;
+        let hash = support::hash_of(&extrinsic);
+
+        let existing = self
+            .pending
+            .get(&extrinsic.caller)
+            .and_then(|entries| entries.get(&extrinsic.nonce));
+        match existing {
+            Some(entry) if entry.hash == hash => return Err(Error::AlreadyInPool),
+            Some(entry) if entry.extrinsic.tip >= extrinsic.tip => return Err(Error::TipTooLow),
+            // The extrinsic replaces the existing one, so the size of the pool does not change.
+            Some(_) => {}
+            None if self.len() < self.max_size => {}
+            None => {
+                let ready = extrinsic.nonce <= next_nonce;
+                let (caller, nonce) = self
+                    .eviction_candidate(runtime, &extrinsic.caller)
+                    .filter(|(evicted_ready, entry)| {
+                        (*evicted_ready, entry.extrinsic.tip) < (ready, extrinsic.tip)
+                    })
+                    .map(|(_, entry)| (entry.extrinsic.caller.clone(), entry.extrinsic.nonce))
+                    .ok_or(Error::PoolFull)?;
+                self.remove(&caller, nonce);
+            }
+        }
+
+        let entry = PoolEntry {
+            extrinsic,
+            hash,
+            insertion: self.next_insertion,
+        };
+        self.next_insertion += 1;
+        self.pending
+            .entry(entry.extrinsic.caller.clone())
+            .or_default()
+            .insert(entry.extrinsic.nonce, entry);
+        Ok(hash)
+    }
+
+    /// The extrinsics which can be included in the next block, in the order they should be
+    /// executed: by priority, while keeping the extrinsics of each caller in nonce order.
+    pub fn ready(&self, runtime: &Runtime) -> Vec<types::Extrinsic> {
+        let chains = self
+            .pending
+            .iter()
+            .map(|(caller, entries)| Self::ready_chain(runtime, caller, entries))
+            .collect::<Vec<_>>();
+
+        // Take the extrinsic with the highest priority among the next one of each caller.
+        let mut heads = chains
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chain)| Some((chain.first()?.priority(), index, 0)))
+            .collect::<BinaryHeap<_>>();
+        let mut ready = Vec::new();
+        while let Some((_, index, position)) = heads.pop() {
+            ready.push(chains[index][position].extrinsic.clone());
+            if let Some(next) = chains[index].get(position + 1) {
+                heads.push((next.priority(), index, position + 1));
+            }
+        }
+        ready
+    }
+
+    /// The number of ready and future extrinsics in the pool, for the state of `runtime`.
+    pub fn status(&self, runtime: &Runtime) -> PoolStatus {
+        let ready = self
+            .pending
+            .iter()
+            .map(|(caller, entries)| Self::ready_chain(runtime, caller, entries).len())
+            .sum();
+        PoolStatus {
+            ready,
+            future: self.len() - ready,
+        }
+    }
+
+    /// Remove the extrinsics which are no longer valid for the state of `runtime`, usually after a
+    /// block was imported: the ones whose nonce was used, and the ones which can not be paid for.
+    ///
+    /// Returns the number of extrinsics removed.
+    pub fn prune(&mut self, runtime: &Runtime) -> usize {
+        let len = self.len();
+        for entries in self.pending.values_mut() {
+            // Entries are visited in nonce order, so each one is paid for after the extrinsics
+            // kept before it.
+            let mut pending_tips: support::Tip = 0;
+            entries.retain(|_, entry| {
+                let valid = Self::validate(runtime, &entry.extrinsic, pending_tips).is_ok();
+                if valid {
+                    pending_tips = pending_tips.saturating_add(entry.extrinsic.tip);
+                }
+                valid
+            });
+        }
+        self.pending.retain(|_, entries| !entries.is_empty());
+        len - self.len()
+    }
+
+    /// Check that `extrinsic` can be executed on the state of `runtime`, now or after the other
+    /// extrinsics of its caller, whose tips add up to `pending_tips`.
+    fn validate(
+        runtime: &Runtime,
+        extrinsic: &types::Extrinsic,
+        pending_tips: support::Tip,
+    ) -> Result<(), Error> {
+        if extrinsic.nonce < runtime.system.nonce(&extrinsic.caller) {
+            return Err(Error::Stale);
+        }
+        let signed_by_caller = runtime
+            .account_key(&extrinsic.caller)
+            .is_some_and(|key| extrinsic.is_signed_by(&key));
+        if !signed_by_caller {
+            return Err(Error::BadSignature);
+        }
+        if !CallFilter::contains(runtime, &extrinsic.call) {
+            return Err(Error::Filtered);
+        }
+        runtime
+            .can_pay(
+                &extrinsic.caller,
+                &extrinsic.call,
+                pending_tips.saturating_add(extrinsic.tip),
+            )
+            .map_err(Error::CannotPay)
+    }
+
+    /// The extrinsics of `caller` whose nonces follow their current nonce without gaps.
+    fn ready_chain<'a>(
+        runtime: &Runtime,
+        caller: &types::AccountId,
+        entries: &'a BTreeMap<types::Nonce, PoolEntry>,
+    ) -> Vec<&'a PoolEntry> {
+        let mut next = runtime.system.nonce(caller);
+        entries
+            .range(next..)
+            .map_while(|(nonce, entry)| {
+                (*nonce == next).then(|| {
+                    next += 1;
+                    entry
+                })
+            })
+            .collect()
+    }
+
+    /// The extrinsic to evict when the pool is full for an extrinsic of `submitter`, and whether
+    /// it is ready.
+    ///
+    /// It is the last extrinsic of a caller other than `submitter`, so no caller is left with a gap
+    /// in their nonces: one waiting for a nonce if there is any, then the one with the lowest
+    /// priority.
+    fn eviction_candidate(
+        &self,
+        runtime: &Runtime,
+        submitter: &types::AccountId,
+    ) -> Option<(bool, &PoolEntry)> {
+        self.pending
+            .iter()
+            .filter(|(caller, _)| *caller != submitter)
+            .filter_map(|(caller, entries)| {
+                let last = entries.values().next_back()?;
+                let ready = Self::ready_chain(runtime, caller, entries).len() == entries.len();
+                Some((ready, last))
+            })
+            .min_by_key(|(ready, entry)| (*ready, entry.priority()))
+    }
+
+    /// Remove the extrinsic of `caller` with `nonce` from the pool.
+    fn remove(&mut self, caller: &types::AccountId, nonce: types::Nonce) {
+        if let Some(entries) = self.pending.get_mut(caller) {
+            entries.remove(&nonce);
+            if entries.is_empty() {
+                self.pending.remove(caller);
+            }
+        }
+    }
+
+    /// All the extrinsics in the pool.
+    fn entries(&self) -> impl Iterator<Item = &PoolEntry> {
+        self.pending.values().flat_map(BTreeMap::values)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, PoolStatus, TransactionPool, MAX_FUTURE_NONCES};
+    use crate::chain::Chain;
+    use crate::crypto::Pair;
+    use crate::runtime::{types, BalancesCall, Runtime, RuntimeGenesisConfig};
+    use crate::{accounts, balances};
+
+    fn new_chain() -> Chain {
+        Chain::new(Runtime::from_genesis(RuntimeGenesisConfig {
+            balances: balances::GenesisConfig {
+                balances: vec![(String::from("alice"), 100), (String::from("bob"), 100)],
+            },
+            reward_points: balances::GenesisConfig { balances: vec![] },
+            accounts: accounts::GenesisConfig::dev(&["alice", "bob"]),
+        }))
+    }
+
+    fn transfer(caller: &str, nonce: u32, tip: u128) -> types::Extrinsic {
+        let call = BalancesCall::transfer {
+            to: String::from("charlie"),
+            amount: 1,
+        };
+        types::Extrinsic::new(String::from(caller), nonce, call)
+            .with_tip(tip)
+            .sign(&Pair::from_seed(caller))
+    }
+
+    /// This test checks the following:
+    /// - Extrinsics with a future nonce wait until the extrinsics before them are submitted.
+    /// - Ready extrinsics are ordered by tip, keeping the extrinsics of each caller in nonce order.
+    /// - Imported extrinsics are pruned from the pool.
+    /// - The next nonce of a caller follows their ready extrinsics, and the pool lists its
+    ///   extrinsics in submission order.
+    #[test]
+    fn nonce_and_priority_order() {
+        let mut chain = new_chain();
+        let mut pool = TransactionPool::new(10);
+
+        pool.submit(chain.runtime(), transfer("alice", 1, 50))
+            .unwrap();
+        assert_eq!(
+            pool.status(chain.runtime()),
+            PoolStatus {
+                ready: 0,
+                future: 1
+            }
+        );
+        assert!(pool.ready(chain.runtime()).is_empty());
+        assert_eq!(pool.next_nonce(chain.runtime(), &String::from("alice")), 0);
+
+        pool.submit(chain.runtime(), transfer("bob", 0, 10))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("alice", 0, 1))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("bob", 1, 0)).unwrap();
+        assert_eq!(
+            pool.ready(chain.runtime()),
+            vec![
+                transfer("bob", 0, 10),
+                transfer("alice", 0, 1),
+                transfer("alice", 1, 50),
+                transfer("bob", 1, 0),
+            ]
+        );
+        assert_eq!(pool.next_nonce(chain.runtime(), &String::from("alice")), 2);
+        assert_eq!(
+            pool.extrinsics(),
+            vec![
+                transfer("alice", 1, 50),
+                transfer("bob", 0, 10),
+                transfer("alice", 0, 1),
+                transfer("bob", 1, 0),
+            ]
+        );
+
+        let block = chain.new_block(pool.ready(chain.runtime())[..3].to_vec());
+        chain.import_block(block).unwrap();
+        assert_eq!(pool.prune(chain.runtime()), 3);
+        assert_eq!(pool.ready(chain.runtime()), vec![transfer("bob", 1, 0)]);
+        assert_eq!(pool.next_nonce(chain.runtime(), &String::from("bob")), 2);
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 1, 60)),
+            Err(Error::Stale)
+        );
+    }
+
+    /// This test checks the following:
+    /// - An extrinsic replaces the one with the same caller and nonce only if it pays a higher tip.
+    /// - Extrinsics whose tip can not be paid are rejected.
+    #[test]
+    fn replace_by_fee() {
+        let chain = new_chain();
+        let mut pool = TransactionPool::new(10);
+
+        let hash = pool
+            .submit(chain.runtime(), transfer("alice", 0, 5))
+            .unwrap();
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 0, 5)),
+            Err(Error::AlreadyInPool)
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 0, 4)),
+            Err(Error::TipTooLow)
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 0, 500)),
+            Err(Error::CannotPay("insufficient balance"))
+        );
+
+        let replacement = pool
+            .submit(chain.runtime(), transfer("alice", 0, 6))
+            .unwrap();
+        assert!(!pool.contains(&hash) && pool.contains(&replacement));
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.ready(chain.runtime()), vec![transfer("alice", 0, 6)]);
+    }
+
+    /// This test checks the following:
+    /// - The tips of the extrinsics of a caller add up, so extrinsics are rejected when the caller
+    ///   can not pay them all, unless they replace an extrinsic with a higher tip.
+    /// - Pruning keeps the extrinsics which can still be paid for in nonce order, and removes the
+    ///   ones after them.
+    #[test]
+    fn cumulative_tips() {
+        let mut chain = new_chain();
+        let mut pool = TransactionPool::new(10);
+
+        pool.submit(chain.runtime(), transfer("alice", 0, 10))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("alice", 1, 60))
+            .unwrap();
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 2, 31)),
+            Err(Error::CannotPay("insufficient balance"))
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", 0, 41)),
+            Err(Error::CannotPay("insufficient balance"))
+        );
+        pool.submit(chain.runtime(), transfer("alice", 0, 20))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("alice", 2, 20))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("bob", 0, 100))
+            .unwrap();
+
+        // Alice spends most of her balance in an extrinsic which is not in the pool.
+        let call = BalancesCall::transfer {
+            to: String::from("charlie"),
+            amount: 30,
+        };
+        let spend =
+            types::Extrinsic::new(String::from("alice"), 0, call).sign(&Pair::from_seed("alice"));
+        let block = chain.new_block(vec![spend]);
+        chain.import_block(block).unwrap();
+        assert_eq!(pool.prune(chain.runtime()), 2);
+        assert_eq!(
+            pool.ready(chain.runtime()),
+            vec![transfer("bob", 0, 100), transfer("alice", 1, 60)]
+        );
+    }
+
+    /// This test checks the following:
+    /// - A full pool rejects extrinsics which do not pay a higher tip than the ones it can evict.
+    /// - Otherwise, the last extrinsic of a caller with the lowest tip is evicted.
+    #[test]
+    fn size_cap() {
+        let chain = new_chain();
+        let mut pool = TransactionPool::new(3);
+
+        pool.submit(chain.runtime(), transfer("alice", 0, 1))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("alice", 1, 9))
+            .unwrap();
+        pool.submit(chain.runtime(), transfer("bob", 0, 3)).unwrap();
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("bob", 1, 3)),
+            Err(Error::PoolFull)
+        );
+
+        // `alice`'s first extrinsic has the lowest tip, but evicting it would leave a gap.
+        pool.submit(chain.runtime(), transfer("alice", 2, 4))
+            .unwrap();
+        assert_eq!(pool.len(), 3);
+        assert_eq!(
+            pool.ready(chain.runtime()),
+            vec![
+                transfer("alice", 0, 1),
+                transfer("alice", 1, 9),
+                transfer("alice", 2, 4),
+            ]
+        );
+    }
+
+    /// This test checks the following:
+    /// - Extrinsics signed with the key of another account than their caller are rejected.
+    /// - Extrinsics whose content was changed after they were signed are rejected.
+    /// - Extrinsics of a caller without a key are rejected.
+    #[test]
+    fn bad_signature() {
+        let chain = new_chain();
+        let mut pool = TransactionPool::new(10);
+
+        let wrong_signer = transfer("alice", 0, 1).sign(&Pair::from_seed("bob"));
+        assert_eq!(
+            pool.submit(chain.runtime(), wrong_signer),
+            Err(Error::BadSignature)
+        );
+        let mut forged = transfer("alice", 0, 1);
+        forged.tip = 2;
+        assert_eq!(
+            pool.submit(chain.runtime(), forged),
+            Err(Error::BadSignature)
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("charlie", 0, 0)),
+            Err(Error::BadSignature)
+        );
+        assert!(pool.is_empty());
+
+        pool.submit(chain.runtime(), transfer("alice", 0, 1))
+            .unwrap();
+        assert_eq!(pool.len(), 1);
+    }
+
+    /// This test checks the following:
+    /// - Extrinsics too far ahead of the nonce of their caller are rejected.
+    /// - A full pool evicts the extrinsics waiting for a nonce before the ready ones, whatever their
+    ///   tip, and extrinsics waiting for a nonce never evict ready ones.
+    /// - An extrinsic never evicts an extrinsic of its own caller.
+    #[test]
+    fn future_flood() {
+        let chain = new_chain();
+        let mut pool = TransactionPool::new(3);
+
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", u32::MAX, 1)),
+            Err(Error::TooFarInFuture)
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", MAX_FUTURE_NONCES + 1, 1)),
+            Err(Error::TooFarInFuture)
+        );
+
+        // Alice fills the pool with high tips at nonces which are not ready.
+        for nonce in MAX_FUTURE_NONCES - 2..=MAX_FUTURE_NONCES {
+            pool.submit(chain.runtime(), transfer("alice", nonce, 30))
+                .unwrap();
+        }
+        pool.submit(chain.runtime(), transfer("bob", 0, 0)).unwrap();
+        assert_eq!(
+            pool.status(chain.runtime()),
+            PoolStatus {
+                ready: 1,
+                future: 2
+            }
+        );
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("alice", MAX_FUTURE_NONCES, 35)),
+            Err(Error::PoolFull)
+        );
+        pool.submit(chain.runtime(), transfer("bob", 1, 0)).unwrap();
+        assert_eq!(
+            pool.extrinsics(),
+            vec![
+                transfer("alice", MAX_FUTURE_NONCES - 2, 30),
+                transfer("bob", 0, 0),
+                transfer("bob", 1, 0),
+            ]
+        );
+
+        // Evicting the last extrinsic of Bob for another one of Bob would leave a gap in the nonces of Bob.
+        let mut pool = TransactionPool::new(2);
+        pool.submit(chain.runtime(), transfer("bob", 0, 1)).unwrap();
+        pool.submit(chain.runtime(), transfer("bob", 1, 1)).unwrap();
+        assert_eq!(
+            pool.submit(chain.runtime(), transfer("bob", 2, 5)),
+            Err(Error::PoolFull)
+        );
+        assert_eq!(pool.ready(chain.runtime()).len(), 2);
+    }
+}
@@ -0,0 +1,388 @@
+//! Runtimes defined outside of the framework crate, using `#[macros::runtime]` and
+//! `#[macros::call]` with `framework = rust_state_machine`.
+
+use rust_state_machine::crypto::Pair;
+use rust_state_machine::support::{
+    Block, Dispatch, DispatchResult, Extrinsic, Header, CALL_FILTERED, INVALID_NONCE,
+};
+
+/// A pallet defined outside of the framework crate, which counts how many times each account was
+/// counted.
+mod counter {
+    use rust_state_machine::storage::StorageMap;
+    use rust_state_machine::support::DispatchResult;
+
+    pub trait Config: rust_state_machine::system::Config {}
+
+    #[macros::storage(framework = rust_state_machine)]
+    #[derive(Debug, Clone)]
+    pub struct Pallet<T: Config> {
+        #[storage(default = 0)]
+        count: StorageMap<T::AccountId, u32>,
+    }
+
+    #[macros::call(framework = rust_state_machine)]
+    impl<T: Config> Pallet<T> {
+        /// Increase the count of `who` by one.
+        #[call_index(0)]
+        #[weight(5)]
+        pub fn increment(&mut self, _caller: T::AccountId, who: T::AccountId) -> DispatchResult {
+            self.mutate_count(&who, |count| *count += 1);
+            Ok(())
+        }
+    }
+}
+
+/// A runtime with the balances pallet of the framework, and a pallet of its own.
+mod ledger {
+    use super::counter;
+    use rust_state_machine::crypto::{Pair, PublicKey};
+    use rust_state_machine::support::{DispatchResult, Tip};
+    use rust_state_machine::{balances, system};
+
+    #[derive(Debug, Clone)]
+    #[macros::runtime(framework = rust_state_machine)]
+    pub struct LedgerRuntime {
+        pub system: system::Pallet<Self>,
+        #[pallet(calls, events, genesis)]
+        pub balances: balances::Pallet<Self>,
+        #[pallet(calls)]
+        pub counter: counter::Pallet<Self>,
+    }
+
+    impl system::Config for LedgerRuntime {
+        type AccountId = String;
+        type BlockNumber = u64;
+        type Nonce = u32;
+    }
+
+    impl balances::Config for LedgerRuntime {
+        type Balance = u64;
+    }
+
+    impl counter::Config for LedgerRuntime {}
+
+    /// Nothing can be transferred to `mallory`.
+    impl rust_state_machine::support::CallFilter<RuntimeCall> for LedgerRuntime {
+        fn contains(&self, call: &RuntimeCall) -> bool {
+            !matches!(
+                call,
+                RuntimeCall::balances(balances::Call::transfer { to, .. }) if to == "mallory"
+            )
+        }
+    }
+
+    /// Every account signs with the development key of its name, which is enough for a test.
+    impl rust_state_machine::support::AccountKeys<String> for LedgerRuntime {
+        fn account_key(&self, caller: &String) -> Option<PublicKey> {
+            Some(Pair::from_seed(caller).public())
+        }
+    }
+
+    /// Every extrinsic pays a fee of 1, plus its tip.
+    impl rust_state_machine::support::ChargeTransaction<String, RuntimeCall> for LedgerRuntime {
+        fn can_pay(&self, caller: &String, _call: &RuntimeCall, tip: Tip) -> DispatchResult {
+            self.balances.ensure_can_withdraw(caller, fee(tip)?)
+        }
+
+        fn withdraw_fee(
+            &mut self,
+            caller: &String,
+            _call: &RuntimeCall,
+            tip: Tip,
+        ) -> DispatchResult {
+            self.balances.withdraw(caller, fee(tip)?)
+        }
+    }
+
+    fn fee(tip: Tip) -> Result<u64, &'static str> {
+        u64::try_from(tip)
+            .ok()
+            .and_then(|tip| tip.checked_add(1))
+            .ok_or("fee overflow")
+    }
+}
+
+/// A second runtime in the same binary, referring to its pallets by their full path.
+mod notary {
+    use rust_state_machine::crypto::{Pair, PublicKey};
+    use rust_state_machine::support::{DispatchResult, Tip};
+
+    #[derive(Debug, Clone)]
+    #[macros::runtime(framework = ::rust_state_machine)]
+    pub struct NotaryRuntime {
+        pub system: ::rust_state_machine::system::Pallet<Self>,
+        #[pallet(calls, events, hooks)]
+        pub proof_of_existence: ::rust_state_machine::proof_of_existence::Pallet<Self>,
+    }
+
+    impl rust_state_machine::system::Config for NotaryRuntime {
+        type AccountId = String;
+        type BlockNumber = u32;
+        type Nonce = u64;
+    }
+
+    pub struct MaxClaimsPerAccount;
+
+    impl rust_state_machine::support::Get<u32> for MaxClaimsPerAccount {
+        fn get() -> u32 {
+            1
+        }
+    }
+
+    pub struct ApprovalPeriod;
+
+    impl rust_state_machine::support::Get<u32> for ApprovalPeriod {
+        fn get() -> u32 {
+            5
+        }
+    }
+
+    impl rust_state_machine::proof_of_existence::Config for NotaryRuntime {
+        type Content = u32;
+        type MaxClaimsPerAccount = MaxClaimsPerAccount;
+        type ApprovalPeriod = ApprovalPeriod;
+    }
+
+    impl rust_state_machine::support::CallFilter<RuntimeCall> for NotaryRuntime {
+        fn contains(&self, _call: &RuntimeCall) -> bool {
+            true
+        }
+    }
+
+    /// Every account signs with the development key of its name, which is enough for a test.
+    impl rust_state_machine::support::AccountKeys<String> for NotaryRuntime {
+        fn account_key(&self, caller: &String) -> Option<PublicKey> {
+            Some(Pair::from_seed(caller).public())
+        }
+    }
+
+    /// Extrinsics are free.
+    impl rust_state_machine::support::ChargeTransaction<String, RuntimeCall> for NotaryRuntime {
+        fn can_pay(&self, _caller: &String, _call: &RuntimeCall, _tip: Tip) -> DispatchResult {
+            Ok(())
+        }
+
+        fn withdraw_fee(
+            &mut self,
+            _caller: &String,
+            _call: &RuntimeCall,
+            _tip: Tip,
+        ) -> DispatchResult {
+            Ok(())
+        }
+    }
+}
+
+/// A runtime without a pallet providing calls, whose balances are only set at genesis.
+mod vault {
+    use rust_state_machine::crypto::PublicKey;
+    use rust_state_machine::support::{DispatchResult, Tip};
+    use rust_state_machine::{balances, system};
+
+    #[derive(Debug, Clone)]
+    #[macros::runtime(framework = rust_state_machine)]
+    pub struct VaultRuntime {
+        pub system: system::Pallet<Self>,
+        #[pallet(genesis)]
+        pub balances: balances::Pallet<Self>,
+    }
+
+    impl system::Config for VaultRuntime {
+        type AccountId = String;
+        type BlockNumber = u32;
+        type Nonce = u32;
+    }
+
+    impl balances::Config for VaultRuntime {
+        type Balance = u64;
+    }
+
+    impl rust_state_machine::support::CallFilter<RuntimeCall> for VaultRuntime {
+        fn contains(&self, _call: &RuntimeCall) -> bool {
+            true
+        }
+    }
+
+    /// No account can sign extrinsics, since there is no call to make anyway.
+    impl rust_state_machine::support::AccountKeys<String> for VaultRuntime {
+        fn account_key(&self, _caller: &String) -> Option<PublicKey> {
+            None
+        }
+    }
+
+    /// Extrinsics are free, though there is no call to make.
+    impl rust_state_machine::support::ChargeTransaction<String, RuntimeCall> for VaultRuntime {
+        fn can_pay(&self, _caller: &String, _call: &RuntimeCall, _tip: Tip) -> DispatchResult {
+            Ok(())
+        }
+
+        fn withdraw_fee(
+            &mut self,
+            _caller: &String,
+            _call: &RuntimeCall,
+            _tip: Tip,
+        ) -> DispatchResult {
+            Ok(())
+        }
+    }
+}
+
+/// This test checks the following:
+/// - A runtime can be defined in another crate, with pallets from the framework and its own.
+/// - Its calls are displayed with the name of their pallet.
+/// - Its calls are filtered by its `CallFilter`.
+/// - Its blocks use the types of its own system config.
+/// - Its extrinsics pay the fees of its `ChargeTransaction`, and are skipped if they can not pay or
+///   reuse a nonce.
+#[test]
+fn ledger_runtime() -> DispatchResult {
+    use ledger::{LedgerRuntime, RuntimeCall, RuntimeGenesisConfig};
+    use rust_state_machine::balances;
+
+    let transfer = RuntimeCall::balances(balances::Call::transfer {
+        to: String::from("bob"),
+        amount: 40,
+    });
+    assert_eq!(transfer.to_string(), "balances.transfer(to=bob, amount=40)");
+
+    let mut runtime = LedgerRuntime::from_genesis(RuntimeGenesisConfig {
+        balances: balances::GenesisConfig {
+            balances: vec![(String::from("alice"), 100)],
+        },
+    });
+
+    let receipt = runtime.execute_block(Block {
+        header: Header {
+            block_number: 1u64,
+            ..Default::default()
+        },
+        extrinsics: vec![
+            Extrinsic::new(String::from("alice"), 0, transfer.clone())
+                .with_tip(2)
+                .sign(&Pair::from_seed("alice")),
+            Extrinsic::new(
+                String::from("bob"),
+                0,
+                counter::Call::increment {
+                    who: String::from("carol"),
+                },
+            )
+            .sign(&Pair::from_seed("bob")),
+            // Skipped, since `carol` can not pay the fee.
+            Extrinsic::new(
+                String::from("carol"),
+                0,
+                counter::Call::increment {
+                    who: String::from("carol"),
+                },
+            )
+            .sign(&Pair::from_seed("carol")),
+            // Skipped, since `alice` already used her nonce 0.
+            Extrinsic::new(String::from("alice"), 0, transfer).sign(&Pair::from_seed("alice")),
+        ],
+    })?;
+
+    let to_mallory = RuntimeCall::balances(balances::Call::transfer {
+        to: String::from("mallory"),
+        amount: 1,
+    });
+    assert_eq!(
+        Dispatch::dispatch(&mut runtime, String::from("alice"), to_mallory),
+        Err(CALL_FILTERED)
+    );
+
+    assert_eq!(runtime.balances.balance(&String::from("alice")), 57);
+    assert_eq!(runtime.balances.balance(&String::from("bob")), 39);
+    assert_eq!(runtime.counter.count(&String::from("carol")), 1);
+    assert_eq!(runtime.system.nonce(&String::from("alice")), 1);
+    assert_eq!(receipt.weight, 10_005);
+    assert_eq!(receipt.extrinsics[0].events.len(), 1);
+    assert!(receipt.extrinsics[1].events.is_empty());
+    assert_eq!(receipt.extrinsics[2].result, Err("insufficient balance"));
+    assert_eq!(receipt.extrinsics[3].result, Err(INVALID_NONCE));
+    assert_eq!(receipt.extrinsics[3].weight, 0);
+
+    let metadata = LedgerRuntime::metadata();
+    assert_eq!(metadata.pallets[1].name, "counter");
+    assert_eq!(
+        metadata.pallets[1].calls[0].docs,
+        vec!["Increase the count of `who` by one."]
+    );
+    Ok(())
+}
+
+/// This test checks the following:
+/// - A second runtime can be defined next to the first one, with a different system config.
+/// - The receipt of its block reports the extrinsic which failed.
+#[test]
+fn notary_runtime() -> DispatchResult {
+    use notary::NotaryRuntime;
+    use rust_state_machine::proof_of_existence;
+
+    let mut runtime = NotaryRuntime::new();
+    let alice = String::from("alice");
+
+    let receipt = runtime.execute_block(Block {
+        header: Header {
+            block_number: 1u32,
+            ..Default::default()
+        },
+        extrinsics: vec![
+            Extrinsic::new(
+                alice.clone(),
+                0,
+                proof_of_existence::Call::create_claim { claim: 7 },
+            )
+            .sign(&Pair::from_seed("alice")),
+            // Fails, since `alice` can own a single claim.
+            Extrinsic::new(
+                alice.clone(),
+                1,
+                proof_of_existence::Call::create_claim { claim: 8 },
+            )
+            .sign(&Pair::from_seed("alice")),
+        ],
+    })?;
+
+    assert_eq!(runtime.proof_of_existence.get_claim(&7), Some(&alice));
+    assert_eq!(runtime.proof_of_existence.get_claim(&8), None);
+    assert_eq!(
+        receipt.extrinsics[1].result,
+        Err("caller has reached the maximum number of claims")
+    );
+    assert_eq!(runtime.system.nonce(&alice), 2);
+    Ok(())
+}
+
+/// This test checks the following:
+/// - A runtime without a pallet providing calls can be defined, and starts from its genesis.
+/// - Its blocks can only be empty, and still advance the block number.
+#[test]
+fn vault_runtime() -> DispatchResult {
+    use rust_state_machine::balances;
+    use vault::{RuntimeGenesisConfig, VaultRuntime};
+
+    let mut runtime = VaultRuntime::from_genesis(RuntimeGenesisConfig {
+        balances: balances::GenesisConfig {
+            balances: vec![(String::from("alice"), 100)],
+        },
+    });
+
+    let receipt = runtime.execute_block(Block {
+        header: Header {
+            block_number: 1u32,
+            ..Default::default()
+        },
+        extrinsics: vec![],
+    })?;
+
+    assert!(receipt.extrinsics.is_empty());
+    assert_eq!(runtime.system.block_number(), 1);
+    assert_eq!(runtime.balances.balance(&String::from("alice")), 100);
+    assert!(VaultRuntime::metadata()
+        .pallets
+        .iter()
+        .all(|pallet| pallet.calls.is_empty()));
+    Ok(())
+}